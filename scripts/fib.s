@@ -0,0 +1,42 @@
+main:
+    li $a0, 10
+    jal fib
+    move $a0, $v0
+    li $v0, 1
+    syscall
+    li $a0, 10
+    li $v0, 11
+    syscall
+    la $t0, fib
+    li $a0, 7
+    jalr $t0
+    move $a0, $v0
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+
+# fib(n) recursive
+fib:
+    addi $sp, $sp, -12
+    sw $ra, 0($sp)
+    sw $s0, 4($sp)
+    sw $s1, 8($sp)
+    move $s0, $a0
+    li $t1, 2
+    blt $s0, $t1, fib_base
+    addi $a0, $s0, -1
+    jal fib
+    move $s1, $v0
+    addi $a0, $s0, -2
+    jal fib
+    add $v0, $v0, $s1
+    j fib_done
+fib_base:
+    move $v0, $s0
+fib_done:
+    lw $ra, 0($sp)
+    lw $s0, 4($sp)
+    lw $s1, 8($sp)
+    addi $sp, $sp, 12
+    jr $ra
@@ -1,25 +1,42 @@
 use std::fs::read_to_string;
 
-use eframe::egui::{self, menu, ScrollArea};
+use eframe::egui::{self, menu, DragValue, ScrollArea, TextureHandle};
 
 use rfd::FileDialog;
 
-use crate::{Machine, Register};
+use crate::{
+    BranchStage, Machine, Predictor, Register, BAD_VADDR, CAUSE, ENTRY_HI, ENTRY_LO, EPC, INDEX,
+    STATUS,
+};
 
 use self::{
+    branch_view::BranchView,
+    cache_view::{cache_menu, CacheView},
     console::Console,
+    display_view::DisplayView,
     editor::Editor,
+    files_view::FilesView,
     memory::MemoryView,
     pipeline_view::PipelineView,
     run_menu::RunMenu,
+    stats_view::StatsView,
+    timeline_view::TimelineView,
+    tlb_view::TlbView,
     watches::{Watch, WatchList},
 };
 
+mod branch_view;
+mod cache_view;
 mod console;
+mod display_view;
 mod editor;
+mod files_view;
 mod memory;
 mod pipeline_view;
 mod run_menu;
+mod stats_view;
+mod timeline_view;
+mod tlb_view;
 mod watches;
 
 #[derive(Default)]
@@ -36,6 +53,15 @@ pub struct App {
     watches: Vec<Watch>,
     running: bool,
     show_memory: bool,
+    show_branches: bool,
+    show_stats: bool,
+    show_timeline: bool,
+    show_caches: bool,
+    show_tlb: bool,
+    show_display: bool,
+    display_texture: Option<TextureHandle>,
+    show_files: bool,
+    selected_file: Option<String>,
     view_address: usize,
     view_endian: bool,
 }
@@ -67,6 +93,15 @@ impl eframe::App for App {
             watches,
             running,
             show_memory,
+            show_branches,
+            show_stats,
+            show_timeline,
+            show_caches,
+            show_tlb,
+            show_display,
+            display_texture,
+            show_files,
+            selected_file,
             view_address,
             view_endian,
         } = self;
@@ -77,6 +112,34 @@ impl eframe::App for App {
             .open(show_watches)
             .show(ctx, |ui| ui.add(WatchList::new(watches, machine, console)));
 
+        egui::Window::new("Branch Prediction")
+            .open(show_branches)
+            .show(ctx, |ui| ui.add(BranchView::new(machine)));
+
+        egui::Window::new("Statistics")
+            .open(show_stats)
+            .show(ctx, |ui| ui.add(StatsView::new(machine)));
+
+        egui::Window::new("Caches")
+            .open(show_caches)
+            .show(ctx, |ui| ui.add(CacheView::new(machine)));
+
+        egui::Window::new("TLB")
+            .open(show_tlb)
+            .show(ctx, |ui| ui.add(TlbView::new(machine)));
+
+        egui::Window::new("Bitmap Display")
+            .open(show_display)
+            .show(ctx, |ui| ui.add(DisplayView::new(machine, display_texture)));
+
+        egui::Window::new("Files")
+            .open(show_files)
+            .show(ctx, |ui| ui.add(FilesView::new(machine, selected_file)));
+
+        egui::Window::new("Pipeline Diagram")
+            .open(show_timeline)
+            .show(ctx, |ui| ui.add(TimelineView::new(machine)));
+
         egui::Window::new("Memory")
             .open(show_memory)
             .show(ctx, |ui| {
@@ -116,6 +179,34 @@ impl eframe::App for App {
                         *show_memory = true;
                         ui.close_menu();
                     }
+                    if ui.button("Branch Prediction").clicked() {
+                        *show_branches = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Statistics").clicked() {
+                        *show_stats = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Caches").clicked() {
+                        *show_caches = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("TLB").clicked() {
+                        *show_tlb = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Bitmap Display").clicked() {
+                        *show_display = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Files").clicked() {
+                        *show_files = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Pipeline Diagram").clicked() {
+                        *show_timeline = true;
+                        ui.close_menu();
+                    }
                     if ui.button("Toggle Stack View").clicked() {
                         *show_stack = !*show_stack;
                         ui.close_menu();
@@ -129,6 +220,58 @@ impl eframe::App for App {
                         ui.close_menu();
                     }
                 });
+                ui.menu_button("Pipeline", |ui| {
+                    let mut config = machine.config();
+                    ui.checkbox(&mut config.single_cycle, "Single Cycle");
+                    ui.add_enabled_ui(!config.single_cycle, |ui| {
+                        ui.checkbox(&mut config.forwarding, "Forwarding");
+                        ui.menu_button("Resolve Branches In", |ui| {
+                            for stage in BranchStage::ALL {
+                                ui.radio_value(&mut config.branch_stage, stage, stage.name());
+                            }
+                        });
+                    });
+                    ui.checkbox(&mut config.delay_slot, "Delay Slot");
+
+                    ui.separator();
+                    ui.add_enabled_ui(!config.single_cycle, |ui| {
+                        ui.menu_button("Branch Predictor", |ui| {
+                            for predictor in Predictor::ALL {
+                                ui.radio_value(&mut config.predictor, predictor, predictor.name());
+                            }
+                        });
+                        ui.add(
+                            DragValue::new(&mut config.bht_entries)
+                                .clamp_range(1..=4096)
+                                .prefix("BHT Entries: "),
+                        );
+                        ui.add(
+                            DragValue::new(&mut config.btb_entries)
+                                .clamp_range(0..=4096)
+                                .prefix("BTB Entries: "),
+                        );
+                    });
+
+                    ui.separator();
+                    cache_menu(ui, "Instruction Cache", &mut config.icache, false);
+                    cache_menu(ui, "Data Cache", &mut config.dcache, true);
+
+                    ui.separator();
+                    ui.checkbox(&mut config.mmu, "MMU");
+                    ui.add_enabled_ui(config.mmu, |ui| {
+                        ui.add(
+                            DragValue::new(&mut config.tlb_entries)
+                                .clamp_range(1..=64)
+                                .prefix("TLB Entries: "),
+                        );
+                    });
+
+                    // changing the pipeline restarts the program
+                    if config != machine.config() {
+                        machine.set_config(config);
+                        *running = false;
+                    }
+                });
             });
 
             // draw toolbar
@@ -149,11 +292,17 @@ impl eframe::App for App {
             .resizable(true)
             .show(ctx, |ui| {
                 if ui.add(console.view()).changed() {
-                    // if we get input from the console we want to try to use that to resolve a
-                    // system call
-                    if let Some(input) = console.input() {
-                        // if resolving the system call failed send an error to the console
-                        if let Err(e) = machine.resolve_input(input) {
+                    // once a program reads the memory mapped keyboard it is typed to directly,
+                    // the program echoes the keys itself if it wants them shown
+                    if machine.terminal().connected() && !machine.pending_syscall() {
+                        if let Some(keys) = console.keys() {
+                            machine.terminal_mut().type_keys(&keys);
+                        }
+                    } else if let Some(input) = console.input() {
+                        // otherwise input from the console is used to resolve a system call, if
+                        // resolving it failed send an error to the console. Whatever the syscall
+                        // leaves of the line is dropped
+                        if let Err(e) = machine.resolve_input(&format!("{}\n", input)) {
                             console.error(&e.to_string());
                             // and stop the program
                             *running = false;
@@ -207,6 +356,57 @@ impl eframe::App for App {
                             ui.label(format!("{val}"));
                         });
                     }
+                    let (hi, lo) = machine.hi_lo();
+                    for (name, val) in [("hi", hi), ("lo", lo)] {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{name}: "));
+                            let val = if *regs_hex {
+                                format!("0x{val:X}")
+                            } else {
+                                format!("{val}")
+                            };
+                            ui.label(val);
+                        });
+                    }
+
+                    ui.separator();
+                    ui.label("Coprocessor 1");
+                    for r in 0..32 {
+                        let val = machine.fp_register(Register::from(r));
+                        ui.horizontal(|ui| {
+                            ui.label(format!("f{r}: "));
+                            // floating point registers are shown as singles in decimal mode
+                            let val = if *regs_hex {
+                                format!("0x{val:X}")
+                            } else {
+                                format!("{:?}", f32::from_bits(val))
+                            };
+                            ui.label(val);
+                        });
+                    }
+                    let fcsr = machine.fcsr();
+                    ui.horizontal(|ui| {
+                        ui.label("fcsr: ");
+                        ui.label(format!("0x{fcsr:08X}"));
+                    });
+
+                    ui.separator();
+                    ui.label("Coprocessor 0");
+                    for (name, r) in [
+                        ("index", INDEX),
+                        ("entrylo", ENTRY_LO),
+                        ("vaddr", BAD_VADDR),
+                        ("entryhi", ENTRY_HI),
+                        ("status", STATUS),
+                        ("cause", CAUSE),
+                        ("epc", EPC),
+                    ] {
+                        let val = machine.cp0_register(r);
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{name}: "));
+                            ui.label(format!("0x{val:08X}"));
+                        });
+                    }
                 });
             });
         }
@@ -0,0 +1,74 @@
+use eframe::egui::{Grid, Response, ScrollArea, Ui, Widget};
+
+use crate::{Machine, Predictor};
+
+/// Displays how well every branch and jump that has executed was predicted
+pub struct BranchView<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> BranchView<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self { machine }
+    }
+}
+
+impl<'a> Widget for BranchView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let stats = self.machine.branch_stats();
+        let config = self.machine.config();
+
+        ui.vertical(|ui| {
+            ui.label(format!("Predictor: {}", config.predictor.name()));
+            let executed: u64 = stats.iter().map(|(_, s)| s.executed).sum();
+            let correct: u64 = stats.iter().map(|(_, s)| s.correct).sum();
+            if executed > 0 {
+                let accuracy = correct as f64 / executed as f64 * 100.0;
+                ui.label(format!("Correct: {correct}/{executed} ({accuracy:.1}%)"));
+            }
+
+            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                Grid::new("branches").striped(true).show(ui, |ui| {
+                    ui.label("Address");
+                    ui.label("Line");
+                    ui.label("Executed");
+                    ui.label("Taken");
+                    ui.label("Accuracy");
+                    ui.end_row();
+
+                    for (pc, stat) in &stats {
+                        ui.label(format!("0x{pc:08X}"));
+                        match self.machine.source_line(*pc) {
+                            Some(line) => ui.label(format!("{}", line + 1)),
+                            None => ui.label(""),
+                        };
+                        ui.label(format!("{}", stat.executed));
+                        ui.label(format!("{}", stat.taken));
+                        ui.label(format!("{:.1}%", stat.accuracy() * 100.0));
+                        ui.end_row();
+                    }
+                });
+            });
+
+            // only the dynamic predictors use the history table
+            if matches!(config.predictor, Predictor::OneBit | Predictor::TwoBit) {
+                ui.separator();
+                ui.label("Branch History Table");
+                let bits = if config.predictor == Predictor::TwoBit {
+                    2
+                } else {
+                    1
+                };
+                Grid::new("bht").show(ui, |ui| {
+                    for (i, counter) in self.machine.branch_history().iter().enumerate() {
+                        ui.label(format!("{i}: {counter:0bits$b}"));
+                        if i % 8 == 7 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            }
+        })
+        .response
+    }
+}
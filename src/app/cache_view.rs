@@ -0,0 +1,144 @@
+use eframe::egui::{DragValue, Grid, Response, ScrollArea, Ui, Widget};
+
+use crate::{cache::Cache, CacheConfig, Machine, Replacement, WritePolicy};
+
+/// Displays the counters and contents of the instruction and data caches
+pub struct CacheView<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> CacheView<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self { machine }
+    }
+}
+
+impl<'a> Widget for CacheView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.vertical(|ui| {
+            let caches = [
+                ("Instruction Cache", self.machine.icache()),
+                ("Data Cache", self.machine.dcache()),
+            ];
+            if caches.iter().all(|(_, c)| !c.config().enabled) {
+                ui.label("Enable a cache from the Pipeline menu");
+            }
+            for (name, cache) in caches {
+                if cache.config().enabled {
+                    ui.collapsing(name, |ui| cache_ui(ui, name, cache));
+                }
+            }
+        })
+        .response
+    }
+}
+
+fn cache_ui(ui: &mut Ui, name: &str, cache: &Cache) {
+    let config = cache.config();
+    let stats = cache.stats();
+    ui.label(format!(
+        "{} bytes, {} byte blocks, {}-way, {}",
+        config.size,
+        config.block_size,
+        config.associativity,
+        config.replacement.name()
+    ));
+
+    Grid::new(format!("{name} counters"))
+        .striped(true)
+        .show(ui, |ui| {
+            let counters = [
+                ("Reads", stats.reads.to_string()),
+                ("Read misses", stats.read_misses.to_string()),
+                ("Writes", stats.writes.to_string()),
+                ("Write misses", stats.write_misses.to_string()),
+                ("Writebacks", stats.writebacks.to_string()),
+                ("Hit rate", format!("{:.1}%", stats.hit_rate() * 100.0)),
+                ("Stall cycles", stats.stall_cycles.to_string()),
+            ];
+            for (name, val) in counters {
+                ui.label(name);
+                ui.label(val);
+                ui.end_row();
+            }
+        });
+
+    ui.separator();
+    ScrollArea::vertical()
+        .id_source(name)
+        .max_height(300.0)
+        .show(ui, |ui| {
+            Grid::new(format!("{name} sets"))
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Set");
+                    for way in 0..config.associativity {
+                        ui.label(format!("Way {way}"));
+                    }
+                    ui.end_row();
+
+                    for (i, set) in cache.sets().iter().enumerate() {
+                        ui.label(i.to_string());
+                        for line in set {
+                            // valid and dirty bits followed by the tag
+                            let text = if line.valid {
+                                format!("V{} {:X}", if line.dirty { "D" } else { " " }, line.tag)
+                            } else {
+                                "-".to_string()
+                            };
+                            ui.monospace(text);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Draws the options of a cache in the Pipeline menu
+pub fn cache_menu(ui: &mut Ui, name: &str, config: &mut CacheConfig, data: bool) {
+    ui.menu_button(name, |ui| {
+        ui.checkbox(&mut config.enabled, "Enabled");
+        ui.add_enabled_ui(config.enabled, |ui| {
+            let mut new = *config;
+            ui.menu_button("Size", |ui| {
+                for size in (6..=14).map(|n| 1 << n) {
+                    ui.radio_value(&mut new.size, size, format!("{size} bytes"));
+                }
+            });
+            ui.menu_button("Block Size", |ui| {
+                for block in (2..=7).map(|n| 1 << n) {
+                    ui.radio_value(&mut new.block_size, block, format!("{block} bytes"));
+                }
+            });
+            ui.menu_button("Associativity", |ui| {
+                for ways in (0..=4).map(|n| 1 << n) {
+                    ui.radio_value(&mut new.associativity, ways, format!("{ways}-way"));
+                }
+            });
+            ui.menu_button("Replacement", |ui| {
+                for replacement in Replacement::ALL {
+                    ui.radio_value(&mut new.replacement, replacement, replacement.name());
+                }
+            });
+            // the instruction cache is never written
+            if data {
+                ui.menu_button("Write Policy", |ui| {
+                    for policy in WritePolicy::ALL {
+                        ui.radio_value(&mut new.write_policy, policy, policy.name());
+                    }
+                });
+                ui.checkbox(&mut new.write_allocate, "Write Allocate");
+            }
+            ui.add(
+                DragValue::new(&mut new.miss_penalty)
+                    .clamp_range(0..=1000)
+                    .prefix("Miss Penalty: "),
+            );
+
+            // a geometry that doesn't fit in the cache is ignored
+            if new.validate().is_ok() {
+                *config = new;
+            }
+        });
+    });
+}
@@ -48,6 +48,14 @@ impl Console {
         None
     }
 
+    /// Takes every key typed since the last output so it can be sent to the terminal
+    pub fn keys(&mut self) -> Option<String> {
+        if self.cursor >= self.text.len() {
+            return None;
+        }
+        Some(self.text.split_off(self.cursor))
+    }
+
     pub fn view<'a>(&'a mut self) -> ConsoleView<'a> {
         ConsoleView {
             text: &mut self.text,
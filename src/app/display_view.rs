@@ -0,0 +1,104 @@
+use eframe::egui::{
+    Color32, ColorImage, ComboBox, DragValue, Response, TextureHandle, Ui, Vec2, Widget,
+};
+
+use crate::{
+    parser::model::{DATA_BASE, HEAP_BASE},
+    Machine,
+};
+
+/// Addresses the framebuffer can be placed at
+const BASES: [(&str, u32); 3] = [
+    (".data", DATA_BASE),
+    ("$gp", 0x10008000),
+    ("heap", HEAP_BASE),
+];
+
+/// Largest texture side uploaded, pixels are scaled down to fit and stretched back when drawn
+const MAX_TEXTURE_SIDE: u32 = 2048;
+
+/// Draws the bitmap display along with its settings
+pub struct DisplayView<'a> {
+    machine: &'a mut Machine,
+    texture: &'a mut Option<TextureHandle>,
+}
+
+impl<'a> DisplayView<'a> {
+    pub fn new(machine: &'a mut Machine, texture: &'a mut Option<TextureHandle>) -> Self {
+        Self { machine, texture }
+    }
+}
+
+impl<'a> Widget for DisplayView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self { machine, texture } = self;
+
+        let mut config = machine.display_config();
+        ui.horizontal(|ui| {
+            let selected = BASES
+                .iter()
+                .find(|(_, base)| *base == config.base)
+                .map_or_else(
+                    || format!("0x{:08X}", config.base),
+                    |(name, _)| name.to_string(),
+                );
+            ComboBox::from_id_source("display base")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (name, base) in BASES {
+                        ui.selectable_value(&mut config.base, base, name);
+                    }
+                });
+            ui.add(
+                DragValue::new(&mut config.width)
+                    .clamp_range(1..=512)
+                    .prefix("Width: "),
+            );
+            ui.add(
+                DragValue::new(&mut config.height)
+                    .clamp_range(1..=512)
+                    .prefix("Height: "),
+            );
+            ui.add(
+                DragValue::new(&mut config.pixel_size)
+                    .clamp_range(1..=32)
+                    .prefix("Pixel Size: "),
+            );
+        });
+        if config != machine.display_config() && config.validate().is_ok() {
+            machine.set_display_config(config);
+        }
+
+        // the frame is read again every time the window is drawn so it follows the program
+        let frame = machine.display_frame();
+        let scale = config
+            .pixel_size
+            .min(MAX_TEXTURE_SIDE / frame.width.max(frame.height))
+            .max(1);
+        let pixels = frame
+            .to_rgb(scale)
+            .chunks(3)
+            .map(|rgb| Color32::from_rgb(rgb[0], rgb[1], rgb[2]))
+            .collect();
+        let image = ColorImage {
+            size: [
+                (frame.width * scale) as usize,
+                (frame.height * scale) as usize,
+            ],
+            pixels,
+        };
+        let texture = match texture {
+            Some(texture) => {
+                texture.set(image);
+                texture
+            }
+            None => texture.insert(ui.ctx().load_texture("display", image)),
+        };
+
+        let size = Vec2::new(
+            (frame.width * config.pixel_size) as f32,
+            (frame.height * config.pixel_size) as f32,
+        );
+        ui.image(texture.id(), size)
+    }
+}
@@ -0,0 +1,88 @@
+use eframe::egui::{Grid, Response, ScrollArea, TextEdit, Ui, Widget};
+
+use crate::Machine;
+
+/// Lists the files the file syscalls work on so they can be uploaded, downloaded and removed
+pub struct FilesView<'a> {
+    machine: &'a mut Machine,
+    /// File whose contents are shown below the list
+    selected: &'a mut Option<String>,
+}
+
+impl<'a> FilesView<'a> {
+    pub fn new(machine: &'a mut Machine, selected: &'a mut Option<String>) -> Self {
+        Self { machine, selected }
+    }
+}
+
+impl<'a> Widget for FilesView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self { machine, selected } = self;
+        ui.vertical(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Upload").clicked() {
+                if let Some(path) = rfd::FileDialog::new().set_directory(".").pick_file() {
+                    let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+                    if let (Some(name), Ok(data)) = (name, std::fs::read(&path)) {
+                        let _ = machine.file_system_mut().write(&name, &data);
+                        *selected = Some(name);
+                    }
+                }
+            }
+
+            let files = machine.file_system().list();
+            if files.is_empty() {
+                ui.label("Programs open files from here with syscall 13");
+            }
+            let mut removed = None;
+            Grid::new("files").striped(true).show(ui, |ui| {
+                for name in &files {
+                    let size = machine
+                        .file_system()
+                        .read(name)
+                        .map_or(0, |data| data.len());
+                    if ui
+                        .selectable_label(selected.as_ref() == Some(name), name)
+                        .clicked()
+                    {
+                        *selected = Some(name.clone());
+                    }
+                    ui.label(format!("{} bytes", size));
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button("Download").clicked() {
+                        let path = rfd::FileDialog::new()
+                            .set_directory(".")
+                            .set_file_name(name)
+                            .save_file();
+                        if let (Some(path), Ok(data)) = (path, machine.file_system().read(name)) {
+                            let _ = std::fs::write(path, data);
+                        }
+                    }
+                    if ui.button("Delete").clicked() {
+                        removed = Some(name.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(name) = removed {
+                let _ = machine.file_system_mut().remove(&name);
+            }
+
+            if let Some(name) = selected.as_ref() {
+                if let Ok(data) = machine.file_system().read(name) {
+                    ui.separator();
+                    let mut text = String::from_utf8_lossy(&data).into_owned();
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        ui.add(
+                            TextEdit::multiline(&mut text)
+                                .code_editor()
+                                .interactive(false),
+                        );
+                    });
+                }
+            }
+        })
+        .response
+    }
+}
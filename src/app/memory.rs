@@ -1,7 +1,7 @@
 use eframe::egui::{DragValue, Response, Slider, Ui, Widget};
 
 use crate::{
-    parser::model::{DATA_BASE, STACK_BASE, TEXT_BASE},
+    parser::model::{DATA_BASE, HEAP_BASE, STACK_BASE, TEXT_BASE},
     Machine,
 };
 
@@ -40,6 +40,13 @@ impl<'a> Widget for MemoryView<'a> {
             if ui.button("DATA").clicked() {
                 *view_address = DATA_BASE as usize;
             }
+            if ui
+                .button("HEAP")
+                .on_hover_text(format!("Break at 0x{:08X}", machine.heap_break()))
+                .clicked()
+            {
+                *view_address = HEAP_BASE as usize;
+            }
             if ui.button("STACK").clicked() {
                 *view_address = STACK_BASE as usize;
             }
@@ -1,6 +1,6 @@
 use eframe::egui::{Frame, Response, Ui, Widget};
 
-use crate::{parser::opcode_name, Machine};
+use crate::{parser::opcode_name, Bubble, Machine};
 
 use super::editor::{DECODE_COLOR, EXECUTE_COLOR, FETCH_COLOR, MEMORY_COLOR, WRITEBACK_COLOR};
 
@@ -22,6 +22,10 @@ impl<'a> Widget for PipelineView<'a> {
                 ui.vertical(|ui| {
                     ui.label("Fetch");
                     ui.label(opcode_name(pipeline.if_id.instruction).unwrap_or(""));
+                    bubble(ui, pipeline.if_id.bubble);
+                    if let Some(target) = pipeline.if_id.prediction {
+                        ui.label(format!("Predict: 0x{target:X}"));
+                    }
                 });
             });
 
@@ -29,6 +33,7 @@ impl<'a> Widget for PipelineView<'a> {
                 ui.vertical(|ui| {
                     ui.label("Decode");
                     ui.label(opcode_name(pipeline.id_ex.instruction).unwrap_or(""));
+                    bubble(ui, pipeline.id_ex.bubble);
                     let rd = pipeline.id_ex.rd.name();
                     let rs = pipeline.id_ex.rs.name();
                     let rt = pipeline.id_ex.rt.name();
@@ -43,6 +48,7 @@ impl<'a> Widget for PipelineView<'a> {
                 ui.vertical(|ui| {
                     ui.label("Execute");
                     ui.label(opcode_name(pipeline.ex_mem.instruction).unwrap_or(""));
+                    bubble(ui, pipeline.ex_mem.bubble);
 
                     let result = pipeline.ex_mem.alu_result;
 
@@ -51,11 +57,7 @@ impl<'a> Widget for PipelineView<'a> {
                         // display memory access
                         ui.label(format!("addr: {result} reg: {reg}"));
                     } else if pipeline.ex_mem.branch {
-                        let should_branch = if pipeline.ex_mem.branch_not {
-                            result != 0
-                        } else {
-                            result == 0
-                        };
+                        let should_branch = pipeline.ex_mem.branch_taken;
                         ui.label(format!("Branch: {should_branch}"));
                     } else {
                         ui.label(format!("ALU result: {result}"));
@@ -67,6 +69,7 @@ impl<'a> Widget for PipelineView<'a> {
                 ui.vertical(|ui| {
                     ui.label("Memory");
                     ui.label(opcode_name(pipeline.mem_wb.instruction).unwrap_or(""));
+                    bubble(ui, pipeline.mem_wb.bubble);
 
                     if pipeline.mem_wb.reg_write {
                         let reg = pipeline.mem_wb.write_register.name();
@@ -84,9 +87,17 @@ impl<'a> Widget for PipelineView<'a> {
                 ui.vertical(|ui| {
                     ui.label("Writeback");
                     ui.label(opcode_name(pipeline.pipe_out.instruction).unwrap_or(""));
+                    bubble(ui, pipeline.pipe_out.bubble);
                 });
             });
         })
         .response
     }
 }
+
+/// Labels a stage holding a bubble with the reason it was inserted
+fn bubble(ui: &mut Ui, bubble: Option<Bubble>) {
+    if let Some(bubble) = bubble {
+        ui.label(bubble.to_string());
+    }
+}
@@ -138,9 +138,10 @@ impl<'a> Widget for RunMenu<'a> {
                     Syscall::Quit => ControlFlow::Break(running = false),
                     _ => ControlFlow::Continue(()),
                 });
+                print.push_str(&self.machine.terminal_mut().take_output());
                 self.ctx.request_repaint();
 
-                // apply the result of syscalls
+                // apply the result of syscalls and the output of the terminal
                 if print.len() > 0 {
                     self.console.print(&print);
                 }
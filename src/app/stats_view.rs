@@ -0,0 +1,57 @@
+use eframe::egui::{Grid, Response, ScrollArea, Ui, Widget};
+
+use crate::Machine;
+
+/// Displays the performance counters of the running program
+pub struct StatsView<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> StatsView<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self { machine }
+    }
+}
+
+impl<'a> Widget for StatsView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let stats = self.machine.stats();
+        ui.vertical(|ui| {
+            Grid::new("counters").striped(true).show(ui, |ui| {
+                let counters = [
+                    ("Cycles", stats.cycles.to_string()),
+                    ("Instructions", stats.instructions.to_string()),
+                    ("CPI", stats.cpi_text()),
+                    ("Load-use stalls", stats.load_use_stalls.to_string()),
+                    ("Data hazard stalls", stats.data_stalls.to_string()),
+                    ("Syscall stalls", stats.syscall_stalls.to_string()),
+                    ("Cache miss stalls", stats.cache_stalls.to_string()),
+                    ("Flushes", stats.flushes.to_string()),
+                    ("Branches taken", stats.branches_taken.to_string()),
+                    ("Branches not taken", stats.branches_not_taken.to_string()),
+                    ("Loads", stats.loads.to_string()),
+                    ("Stores", stats.stores.to_string()),
+                ];
+                for (name, val) in counters {
+                    ui.label(name);
+                    ui.label(val);
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            ui.label("Instruction Mix");
+            ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                Grid::new("mix").striped(true).show(ui, |ui| {
+                    for (name, count) in stats.mix() {
+                        ui.label(name);
+                        ui.label(count.to_string());
+                        ui.label(format!("{:.1}%", stats.percent(count)));
+                        ui.end_row();
+                    }
+                });
+            });
+        })
+        .response
+    }
+}
@@ -0,0 +1,128 @@
+use eframe::{
+    egui::{vec2, Align2, Color32, Rect, Response, ScrollArea, Sense, Ui, Widget},
+    epaint::FontId,
+};
+
+use crate::Machine;
+
+use super::editor::{DECODE_COLOR, EXECUTE_COLOR, FETCH_COLOR, MEMORY_COLOR, WRITEBACK_COLOR};
+
+const STAGE_COLORS: [Color32; 5] = [
+    FETCH_COLOR,
+    DECODE_COLOR,
+    EXECUTE_COLOR,
+    MEMORY_COLOR,
+    WRITEBACK_COLOR,
+];
+
+const LABEL_WIDTH: f32 = 260.0;
+const CELL_WIDTH: f32 = 44.0;
+const ROW_HEIGHT: f32 = 18.0;
+
+/// Draws the recent history of the pipeline as a pipeline diagram with one row per instruction
+/// and one column per cycle
+pub struct TimelineView<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> TimelineView<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self { machine }
+    }
+}
+
+impl<'a> Widget for TimelineView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let timeline = self.machine.timeline();
+        let rows = timeline.rows();
+        let cycles = timeline.cycles();
+        let font = FontId::monospace(12.0);
+        let fg = ui.style().visuals.widgets.noninteractive.fg_stroke.color;
+        let bubble_bg = ui.style().visuals.extreme_bg_color;
+        let width = LABEL_WIDTH + cycles.len() as f32 * CELL_WIDTH;
+
+        ui.vertical(|ui| {
+            // the header row is part of the scrolled rows so it lines up with the cycles
+            ScrollArea::both()
+                .max_height(400.0)
+                .stick_to_right()
+                .show_rows(ui, ROW_HEIGHT, rows.len() + 1, |ui, range| {
+                    for i in range {
+                        let (rect, response) =
+                            ui.allocate_exact_size(vec2(width, ROW_HEIGHT), Sense::hover());
+                        let clip = ui.clip_rect();
+                        let painter = ui.painter();
+                        let cell_rect = |cycle: usize| {
+                            let min = rect.min + vec2(LABEL_WIDTH + cycle as f32 * CELL_WIDTH, 0.0);
+                            Rect::from_min_size(min, vec2(CELL_WIDTH - 2.0, ROW_HEIGHT - 2.0))
+                        };
+
+                        if i == 0 {
+                            painter.text(
+                                rect.left_center(),
+                                Align2::LEFT_CENTER,
+                                "Cycle",
+                                font.clone(),
+                                fg,
+                            );
+                            for (c, cycle) in cycles.iter().enumerate() {
+                                let cell = cell_rect(c);
+                                if clip.intersects(cell) {
+                                    painter.text(
+                                        cell.center(),
+                                        Align2::CENTER_CENTER,
+                                        cycle.number,
+                                        font.clone(),
+                                        fg,
+                                    );
+                                }
+                            }
+                            continue;
+                        }
+
+                        let row = &rows[i - 1];
+                        painter.text(
+                            rect.left_center(),
+                            Align2::LEFT_CENTER,
+                            row.occupant,
+                            font.clone(),
+                            fg,
+                        );
+                        let mut hovered = None;
+                        for (c, cell) in row.cells.iter().enumerate() {
+                            let cell = match cell {
+                                Some(cell) => cell,
+                                None => continue,
+                            };
+                            let cell_rect = cell_rect(c);
+                            if !clip.intersects(cell_rect) {
+                                continue;
+                            }
+                            let bg = match cell.bubble {
+                                Some(_) => bubble_bg,
+                                None => STAGE_COLORS[cell.stage],
+                            };
+                            painter.rect_filled(cell_rect, 2.0, bg);
+                            painter.text(
+                                cell_rect.center(),
+                                Align2::CENTER_CENTER,
+                                cell.label(),
+                                font.clone(),
+                                fg,
+                            );
+
+                            let pointer = response.hover_pos();
+                            if pointer.map(|p| cell_rect.contains(p)).unwrap_or(false) {
+                                hovered = cell.bubble;
+                            }
+                        }
+                        // explain why an instruction was stalled or squashed
+                        if let Some(bubble) = hovered {
+                            response.on_hover_text(bubble.to_string());
+                        }
+                    }
+                });
+        })
+        .response
+    }
+}
@@ -0,0 +1,101 @@
+use eframe::egui::{Grid, Response, ScrollArea, Ui, Widget};
+
+use crate::{Machine, PAGE_SIZE};
+
+/// Displays the entries of the TLB and the status bits of every page in the page table
+pub struct TlbView<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> TlbView<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self { machine }
+    }
+}
+
+/// Shows a status bit as its letter when it is set
+fn flag(set: bool, letter: &str) -> &str {
+    if set {
+        letter
+    } else {
+        "-"
+    }
+}
+
+impl<'a> Widget for TlbView<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let mmu = self.machine.mmu();
+        ui.vertical(|ui| {
+            if !mmu.enabled() {
+                ui.label("Enable the MMU from the Pipeline menu");
+                return;
+            }
+
+            let stats = mmu.stats();
+            Grid::new("tlb counters").striped(true).show(ui, |ui| {
+                let counters = [
+                    ("Lookups", stats.lookups.to_string()),
+                    ("Misses", stats.misses.to_string()),
+                    ("Write faults", stats.write_faults.to_string()),
+                    ("Refills", stats.refills.to_string()),
+                    ("Hit rate", format!("{:.1}%", stats.hit_rate() * 100.0)),
+                ];
+                for (name, val) in counters {
+                    ui.label(name);
+                    ui.label(val);
+                    ui.end_row();
+                }
+            });
+
+            ui.collapsing("TLB", |ui| {
+                Grid::new("tlb entries").striped(true).show(ui, |ui| {
+                    for heading in ["Index", "VPN", "Frame", "Flags"] {
+                        ui.label(heading);
+                    }
+                    ui.end_row();
+
+                    for (i, entry) in mmu.entries().iter().enumerate() {
+                        ui.label(i.to_string());
+                        match entry {
+                            // valid and writable bits
+                            Some(entry) => {
+                                ui.monospace(format!("0x{:05X}", entry.vpn));
+                                ui.monospace(format!("0x{:05X}", entry.frame));
+                                ui.monospace(format!(
+                                    "{}{}",
+                                    flag(entry.valid, "V"),
+                                    flag(entry.writable, "D")
+                                ));
+                            }
+                            None => {
+                                ui.monospace("-");
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.collapsing("Page Table", |ui| {
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    Grid::new("page table").striped(true).show(ui, |ui| {
+                        for heading in ["Page", "Frame", "Valid", "Dirty", "Writable"] {
+                            ui.label(heading);
+                        }
+                        ui.end_row();
+
+                        for (vpn, page) in mmu.pages() {
+                            ui.monospace(format!("0x{:08X}", vpn * PAGE_SIZE));
+                            ui.monospace(format!("0x{:05X}", page.frame));
+                            ui.monospace(flag(page.valid, "V"));
+                            ui.monospace(flag(page.dirty, "D"));
+                            ui.monospace(flag(page.writable, "W"));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        })
+        .response
+    }
+}
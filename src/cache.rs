@@ -0,0 +1,400 @@
+//! Models of the L1 instruction and data caches
+//!
+//! The caches only keep tags and status bits, the data itself is always read from and written to
+//! [`Memory`](crate::Memory) so they decide how long an access takes but never what it returns.
+
+use crate::PipelineConfig;
+
+/// Policies used to pick the line of a set that is evicted on a miss
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    /// Least recently used
+    #[default]
+    Lru,
+    /// First in first out
+    Fifo,
+    Random,
+}
+
+impl Replacement {
+    pub const ALL: [Replacement; 3] = [Self::Lru, Self::Fifo, Self::Random];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lru => "LRU",
+            Self::Fifo => "FIFO",
+            Self::Random => "Random",
+        }
+    }
+}
+
+/// When stores update memory
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Stores only update the cache, dirty lines are written to memory when they are evicted
+    #[default]
+    WriteBack,
+    /// Stores update the cache and memory at the same time
+    WriteThrough,
+}
+
+impl WritePolicy {
+    pub const ALL: [WritePolicy; 2] = [Self::WriteBack, Self::WriteThrough];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::WriteBack => "Write-back",
+            Self::WriteThrough => "Write-through",
+        }
+    }
+}
+
+/// Geometry and policies of a cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Capacity in bytes
+    pub size: u32,
+    /// Bytes in each line
+    pub block_size: u32,
+    /// Lines in each set
+    pub associativity: u32,
+    pub replacement: Replacement,
+    pub write_policy: WritePolicy,
+    /// Load a block into the cache when a store misses
+    pub write_allocate: bool,
+    /// Cycles the pipeline waits for a block to arrive from memory
+    pub miss_penalty: u32,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 1024,
+            block_size: 16,
+            associativity: 1,
+            replacement: Replacement::Lru,
+            write_policy: WritePolicy::WriteBack,
+            write_allocate: true,
+            miss_penalty: 0,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Number of sets the lines are divided into
+    pub fn sets(&self) -> u32 {
+        (self.size / (self.block_size * self.associativity).max(1)).max(1)
+    }
+
+    /// Checks that every dimension is a power of two and the lines fill the cache exactly
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, val) in [
+            ("size", self.size),
+            ("block size", self.block_size),
+            ("associativity", self.associativity),
+        ] {
+            if !val.is_power_of_two() {
+                return Err(format!(
+                    "Cache {} must be a power of two, got {}",
+                    name, val
+                ));
+            }
+        }
+        if self.block_size < 4 {
+            return Err("Cache blocks must hold at least one word".to_string());
+        }
+        if self.block_size * self.associativity > self.size {
+            return Err(format!(
+                "A {} byte cache can't hold {} ways of {} byte blocks",
+                self.size, self.associativity, self.block_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A line of a cache
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    pub valid: bool,
+    pub dirty: bool,
+    pub tag: u32,
+    /// Time of the last access used by LRU replacement
+    used: u64,
+    /// Time the block was loaded used by FIFO replacement
+    loaded: u64,
+}
+
+/// Hit and miss counters of a cache
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub reads: u64,
+    pub read_misses: u64,
+    pub writes: u64,
+    pub write_misses: u64,
+    /// Dirty lines written back to memory when they were evicted
+    pub writebacks: u64,
+    /// Cycles the pipeline spent waiting on this cache
+    pub stall_cycles: u64,
+}
+
+impl CacheStats {
+    pub fn accesses(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.read_misses + self.write_misses
+    }
+
+    /// Fraction of accesses that hit
+    pub fn hit_rate(&self) -> f64 {
+        if self.accesses() == 0 {
+            0.0
+        } else {
+            1.0 - self.misses() as f64 / self.accesses() as f64
+        }
+    }
+}
+
+/// A set associative cache
+#[derive(Debug, Clone)]
+pub struct Cache {
+    config: CacheConfig,
+    sets: Vec<Vec<Line>>,
+    stats: CacheStats,
+    clock: u64,
+    /// State of the xorshift generator used by random replacement
+    seed: u32,
+}
+
+impl Cache {
+    /// Creates a cache with every line invalid
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            sets: vec![
+                vec![Line::default(); config.associativity.max(1) as usize];
+                config.sets() as usize
+            ],
+            stats: CacheStats::default(),
+            clock: 0,
+            seed: 0x2545_F491,
+        }
+    }
+
+    pub fn config(&self) -> CacheConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    /// Gets the lines of every set
+    pub fn sets(&self) -> &[Vec<Line>] {
+        &self.sets
+    }
+
+    /// Splits an address into the index of its set and its tag
+    pub fn locate(&self, address: u32) -> (usize, u32) {
+        let block = address / self.config.block_size.max(1);
+        let sets = self.sets.len() as u32;
+        ((block % sets) as usize, block / sets)
+    }
+
+    /// Looks up an address, loading its block on a miss
+    ///
+    /// # Returns
+    /// The number of cycles the pipeline has to wait for the access, 0 on a hit
+    pub fn access(&mut self, address: u32, write: bool) -> u32 {
+        if !self.config.enabled {
+            return 0;
+        }
+        self.clock += 1;
+        let (index, tag) = self.locate(address);
+        let write_back = write && self.config.write_policy == WritePolicy::WriteBack;
+        if write {
+            self.stats.writes += 1;
+        } else {
+            self.stats.reads += 1;
+        }
+
+        let set = &mut self.sets[index];
+        if let Some(line) = set.iter_mut().find(|l| l.valid && l.tag == tag) {
+            line.used = self.clock;
+            line.dirty |= write_back;
+            return 0;
+        }
+
+        if write {
+            self.stats.write_misses += 1;
+            // without write allocate the store goes straight to memory through the write buffer
+            if !self.config.write_allocate {
+                return 0;
+            }
+        } else {
+            self.stats.read_misses += 1;
+        }
+
+        let way = match set.iter().position(|l| !l.valid) {
+            Some(way) => way,
+            None => self.victim(index),
+        };
+        let line = &mut self.sets[index][way];
+        let mut penalty = self.config.miss_penalty;
+        // the evicted block has to reach memory before it can be replaced
+        if line.valid && line.dirty {
+            self.stats.writebacks += 1;
+            penalty += self.config.miss_penalty;
+        }
+        *line = Line {
+            valid: true,
+            dirty: write_back,
+            tag,
+            used: self.clock,
+            loaded: self.clock,
+        };
+        penalty
+    }
+
+    /// Counts cycles the pipeline was held waiting on this cache
+    pub(crate) fn stall(&mut self) {
+        self.stats.stall_cycles += 1;
+    }
+
+    /// Picks the line of a full set to evict
+    fn victim(&mut self, index: usize) -> usize {
+        let set = &self.sets[index];
+        let oldest =
+            |key: fn(&Line) -> u64| (0..set.len()).min_by_key(|&i| key(&set[i])).unwrap_or(0);
+        match self.config.replacement {
+            Replacement::Lru => oldest(|l| l.used),
+            Replacement::Fifo => oldest(|l| l.loaded),
+            Replacement::Random => {
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 17;
+                self.seed ^= self.seed << 5;
+                self.seed as usize % set.len()
+            }
+        }
+    }
+}
+
+/// The instruction and data caches
+#[derive(Debug, Clone)]
+pub struct Caches {
+    pub instruction: Cache,
+    pub data: Cache,
+}
+
+impl Default for Caches {
+    fn default() -> Self {
+        Self::new(PipelineConfig::default())
+    }
+}
+
+impl Caches {
+    pub fn new(config: PipelineConfig) -> Self {
+        Self {
+            instruction: Cache::new(config.icache),
+            data: Cache::new(config.dcache),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 64 byte cache of 16 byte blocks with a miss penalty of 10 cycles
+    fn cache(associativity: u32, replacement: Replacement) -> Cache {
+        Cache::new(CacheConfig {
+            enabled: true,
+            size: 64,
+            block_size: 16,
+            associativity,
+            replacement,
+            miss_penalty: 10,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn repeated_accesses_to_a_block_hit() {
+        let mut cache = cache(1, Replacement::Lru);
+        assert_eq!(cache.access(0x1000, false), 10);
+        assert_eq!(cache.access(0x1004, false), 0);
+        assert_eq!(cache.access(0x100C, true), 0);
+
+        let stats = cache.stats();
+        assert_eq!((stats.reads, stats.read_misses), (2, 1));
+        assert_eq!((stats.writes, stats.write_misses), (1, 0));
+        assert_eq!((stats.accesses(), stats.misses()), (3, 1));
+    }
+
+    #[test]
+    fn blocks_mapping_to_the_same_set_evict_each_other() {
+        // four sets of one line so addresses 64 bytes apart share a set
+        let mut cache = cache(1, Replacement::Lru);
+        cache.access(0x1000, false);
+        cache.access(0x1040, false);
+        cache.access(0x1000, false);
+        assert_eq!(cache.stats().read_misses, 3);
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_used_line() {
+        // two sets of two lines, 0x1000, 0x1020 and 0x1040 all share set 0
+        let mut cache = cache(2, Replacement::Lru);
+        cache.access(0x1000, false);
+        cache.access(0x1020, false);
+        cache.access(0x1000, false);
+        cache.access(0x1040, false);
+        assert_eq!(cache.access(0x1000, false), 0);
+        assert_eq!(cache.access(0x1020, false), 10);
+        assert_eq!(cache.stats().read_misses, 4);
+    }
+
+    #[test]
+    fn fifo_evicts_the_oldest_line() {
+        let mut cache = cache(2, Replacement::Fifo);
+        cache.access(0x1000, false);
+        cache.access(0x1020, false);
+        cache.access(0x1000, false);
+        cache.access(0x1040, false);
+        assert_eq!(cache.access(0x1020, false), 0);
+        assert_eq!(cache.access(0x1000, false), 10);
+    }
+
+    #[test]
+    fn evicting_a_dirty_line_writes_it_back() {
+        let mut cache = cache(1, Replacement::Lru);
+        cache.access(0x1000, true);
+        assert_eq!(cache.access(0x1040, false), 20);
+        assert_eq!(cache.stats().writebacks, 1);
+        assert_eq!(cache.stats().write_misses, 1);
+    }
+
+    #[test]
+    fn store_misses_without_write_allocate_skip_the_cache() {
+        let mut cache = Cache::new(CacheConfig {
+            enabled: true,
+            write_allocate: false,
+            miss_penalty: 10,
+            ..Default::default()
+        });
+        assert_eq!(cache.access(0x1000, true), 0);
+        assert_eq!(cache.access(0x1000, false), 10);
+        assert_eq!(cache.stats().misses(), 2);
+    }
+
+    #[test]
+    fn disabled_cache_counts_nothing() {
+        let mut cache = Cache::new(CacheConfig::default());
+        assert_eq!(cache.access(0x1000, false), 0);
+        assert_eq!(cache.stats().accesses(), 0);
+    }
+}
@@ -0,0 +1,245 @@
+//! Command line interface used to run the simulator without a display
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+mod asm;
+mod run;
+
+/// Builds the command line argument parser
+pub fn app() -> App<'static, 'static> {
+    App::new("simulator")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Just Another Mips Editor and Simulator")
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Assembles and runs a program without launching the editor")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Assembly source file to run")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("max-cycles")
+                        .long("max-cycles")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Stop the program with an error after N cycles"),
+                )
+                .arg(
+                    Arg::with_name("stats")
+                        .long("stats")
+                        .help("Print performance counters to stderr once the program stops"),
+                )
+                .arg(
+                    Arg::with_name("timeline")
+                        .long("timeline")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help(
+                            "Write a pipeline diagram of the last 1000 cycles to FILE once the \
+                             program stops, as CSV if FILE ends in .csv and as text otherwise",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("delay-slot")
+                        .long("delay-slot")
+                        .help("Always execute the instruction after a branch or jump"),
+                )
+                .arg(
+                    Arg::with_name("no-forwarding")
+                        .long("no-forwarding")
+                        .help("Stall on every data hazard instead of forwarding results"),
+                )
+                .arg(
+                    Arg::with_name("branch-stage")
+                        .long("branch-stage")
+                        .takes_value(true)
+                        .possible_values(&["id", "ex", "mem"])
+                        .default_value("mem")
+                        .help("Pipeline stage branches and jumps are resolved in"),
+                )
+                .arg(
+                    Arg::with_name("predictor")
+                        .long("predictor")
+                        .takes_value(true)
+                        .possible_values(&["not-taken", "taken", "btfnt", "1-bit", "2-bit"])
+                        .default_value("not-taken")
+                        .help(
+                            "Predicts the direction of conditional branches when they are fetched",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("bht")
+                        .long("bht")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("16")
+                        .help("Number of entries in the branch history table"),
+                )
+                .arg(
+                    Arg::with_name("btb")
+                        .long("btb")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("0")
+                        .help("Number of entries in the branch target buffer, 0 disables it"),
+                )
+                .arg(
+                    Arg::with_name("icache")
+                        .long("icache")
+                        .takes_value(true)
+                        .value_name("SIZE,BLOCK,WAYS")
+                        .help("Add an instruction cache of SIZE bytes with BLOCK byte lines"),
+                )
+                .arg(
+                    Arg::with_name("dcache")
+                        .long("dcache")
+                        .takes_value(true)
+                        .value_name("SIZE,BLOCK,WAYS")
+                        .help("Add a data cache of SIZE bytes with BLOCK byte lines"),
+                )
+                .arg(
+                    Arg::with_name("replacement")
+                        .long("replacement")
+                        .takes_value(true)
+                        .possible_values(&["lru", "fifo", "random"])
+                        .default_value("lru")
+                        .help("Picks the line of a full cache set that is evicted"),
+                )
+                .arg(
+                    Arg::with_name("write-through")
+                        .long("write-through")
+                        .help("Write stores to memory immediately instead of on eviction"),
+                )
+                .arg(
+                    Arg::with_name("no-write-allocate")
+                        .long("no-write-allocate")
+                        .help("Send stores that miss the data cache straight to memory"),
+                )
+                .arg(
+                    Arg::with_name("miss-penalty")
+                        .long("miss-penalty")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("0")
+                        .help("Cycles the pipeline stalls for on each cache miss"),
+                )
+                .arg(
+                    Arg::with_name("mmu")
+                        .long("mmu")
+                        .help("Translate user addresses through a software managed TLB"),
+                )
+                .arg(
+                    Arg::with_name("tlb")
+                        .long("tlb")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("16")
+                        .help("Number of entries in the TLB"),
+                )
+                .arg(
+                    Arg::with_name("single-cycle")
+                        .long("single-cycle")
+                        .help("Run each instruction to completion in one cycle without pipelining"),
+                )
+                .arg(
+                    Arg::with_name("sandbox")
+                        .long("sandbox")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Directory the file syscalls open files in, files outside it are refused. Without it files are kept in memory"),
+                )
+                .arg(
+                    Arg::with_name("display")
+                        .long("display")
+                        .takes_value(true)
+                        .value_name("WIDTH,HEIGHT,SIZE")
+                        .default_value("64,64,8")
+                        .help("Size of the bitmap display in pixels and of each pixel in the dump"),
+                )
+                .arg(
+                    Arg::with_name("display-base")
+                        .long("display-base")
+                        .takes_value(true)
+                        .value_name("ADDRESS")
+                        .default_value("0x10010000")
+                        .help("Address of the top left pixel of the bitmap display"),
+                )
+                .arg(
+                    Arg::with_name("display-dump")
+                        .long("display-dump")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help(
+                            "Write the bitmap display to FILE once the program stops, as PNG if \
+                             FILE ends in .png and as PPM otherwise",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("asm")
+                .about("Assembles a program and writes its segments to image files")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Assembly source file to assemble")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .takes_value(true)
+                        .possible_values(&["bin", "ihex", "logisim"])
+                        .default_value("bin")
+                        .help("Format of the image files"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("PREFIX")
+                        .help("Write images to PREFIX.text.<ext> and PREFIX.data.<ext>"),
+                )
+                .arg(
+                    Arg::with_name("listing")
+                        .long("listing")
+                        .help("Print the address, machine word and source of each line"),
+                )
+                .arg(
+                    Arg::with_name("symbols")
+                        .long("symbols")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write the symbol table to FILE"),
+                )
+                .arg(
+                    Arg::with_name("symbols-format")
+                        .long("symbols-format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Format of the symbol table"),
+                ),
+        )
+}
+
+/// Runs the subcommand selected on the command line
+///
+/// # Returns
+/// The exit code of the process or `None` if no subcommand was given and the editor should be
+/// launched instead
+pub fn run_subcommand(matches: &ArgMatches) -> Option<i32> {
+    let result = match matches.subcommand() {
+        ("run", Some(matches)) => run::run(matches),
+        ("asm", Some(matches)) => asm::asm(matches),
+        _ => return None,
+    };
+
+    Some(result.unwrap_or_else(|e| {
+        eprintln!("ERROR: {:#}", e);
+        1
+    }))
+}
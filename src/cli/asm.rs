@@ -0,0 +1,170 @@
+use std::{fmt::Write, fs, path::Path};
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+
+use crate::{
+    assembler,
+    parser::model::{LabelTable, Segment},
+    Memory,
+};
+
+/// Assembles a script and writes each segment to an image file
+///
+/// Images are written to `<output>.<segment>.<ext>` where output defaults to the path of the
+/// script without its extension
+pub fn asm(matches: &ArgMatches) -> Result<i32> {
+    let path = matches.value_of("FILE").unwrap();
+    let format = matches.value_of("format").unwrap();
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => Path::new(path).with_extension("").display().to_string(),
+    };
+
+    let script = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let (mem, syms) = assembler(&script)?;
+
+    for seg in Segment::ALL.iter() {
+        let range = syms.segment_range(*seg);
+        if range.is_empty() {
+            continue;
+        }
+        let bytes = range
+            .clone()
+            .map(|addr| mem.get_byte(addr))
+            .collect::<Result<Vec<u8>>>()?;
+
+        let (image, ext) = match format {
+            "ihex" => (intel_hex(range.start, &bytes).into_bytes(), "hex"),
+            "logisim" => (logisim(&bytes).into_bytes(), "txt"),
+            _ => (bytes, "bin"),
+        };
+
+        // strip the leading '.' from the segment directive
+        let file = format!("{}.{}.{}", output, &seg.name()[1..], ext);
+        fs::write(&file, image).with_context(|| format!("Failed to write {}", file))?;
+    }
+
+    if let Some(file) = matches.value_of("symbols") {
+        let table = match matches.value_of("symbols-format") {
+            Some("json") => syms.to_json(),
+            _ => syms.to_text(),
+        };
+        fs::write(file, table).with_context(|| format!("Failed to write {}", file))?;
+    }
+
+    if matches.is_present("listing") {
+        print!("{}", listing(&script, &mem, &syms)?);
+    }
+    Ok(0)
+}
+
+/// Encodes a single Intel HEX record
+fn ihex_record(ty: u8, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (addr >> 8) as u8, addr as u8, ty];
+    bytes.extend_from_slice(data);
+    let checksum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    bytes.push(checksum.wrapping_neg());
+
+    let mut record = String::from(":");
+    for b in bytes {
+        let _ = write!(record, "{:02X}", b);
+    }
+    record.push('\n');
+    record
+}
+
+/// Encodes `bytes` starting at `base` as an Intel HEX file
+fn intel_hex(base: u32, bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let mut upper = None;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let addr = base + offset as u32;
+
+        // emit an extended linear address record whenever the upper 16 bits change
+        if upper != Some(addr >> 16) {
+            upper = Some(addr >> 16);
+            out.push_str(&ihex_record(0x04, 0, &((addr >> 16) as u16).to_be_bytes()));
+        }
+
+        // records may not cross a 64KiB boundary
+        let len = 16
+            .min(bytes.len() - offset)
+            .min(0x10000 - (addr & 0xFFFF) as usize);
+        out.push_str(&ihex_record(
+            0x00,
+            addr as u16,
+            &bytes[offset..offset + len],
+        ));
+        offset += len;
+    }
+    out.push_str(&ihex_record(0x01, 0, &[]));
+    out
+}
+
+/// Encodes `bytes` as a Logisim memory image of 32-bit words
+fn logisim(bytes: &[u8]) -> String {
+    let words: Vec<String> = bytes
+        .chunks(4)
+        .map(|word| {
+            let mut data = [0; 4];
+            data[..word.len()].copy_from_slice(word);
+            format!("{:08x}", u32::from_le_bytes(data))
+        })
+        .collect();
+
+    let mut out = String::from("v2.0 raw\n");
+    for row in words.chunks(8) {
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Builds an address, machine word and source line listing of an assembled script
+fn listing(script: &str, mem: &Memory, syms: &LabelTable) -> Result<String> {
+    let source: Vec<&str> = script.lines().collect();
+    let lines: Vec<(usize, u32)> = syms.lines().collect();
+
+    let mut out = String::new();
+    for (i, (line, pc)) in lines.iter().enumerate() {
+        let seg_end = Segment::ALL
+            .iter()
+            .map(|seg| syms.segment_range(*seg))
+            .find(|range| range.contains(pc))
+            .map(|range| range.end)
+            .unwrap_or(*pc);
+        // a line ends where the next one begins unless the next line is in another segment
+        let end = match lines.get(i + 1) {
+            Some((_, next)) if *next <= seg_end => *next,
+            _ => seg_end,
+        };
+
+        let mut text = source.get(*line).map(|s| s.trim()).unwrap_or("");
+        let mut addr = *pc;
+        while addr < end {
+            let len = (end - addr).min(4);
+            let mut data = [0; 4];
+            for (j, byte) in data.iter_mut().take(len as usize).enumerate() {
+                *byte = mem.get_byte(addr + j as u32)?;
+            }
+            let word = u32::from_le_bytes(data);
+            let width = len as usize * 2;
+            let row = format!(
+                "0x{:08X}  0x{:0width$X}{:pad$}  {}",
+                addr,
+                word,
+                "",
+                text,
+                width = width,
+                pad = 8 - width
+            );
+            out.push_str(row.trim_end());
+            out.push('\n');
+            text = "";
+            addr += len;
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,340 @@
+use std::{
+    fmt::Write as _,
+    fs::{read_to_string, write},
+    io::{self, BufRead, Write},
+    ops::ControlFlow,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+
+use crate::{
+    assembler, syscall::Syscall, BranchStage, CacheConfig, CacheStats, DisplayConfig, Machine,
+    PipelineConfig, Predictor, Replacement, SandboxFs, WritePolicy,
+};
+
+/// Assembles a script and runs it until it exits
+///
+/// Syscall output is written to stdout and input is read from stdin, so is the output and input of
+/// the memory mapped terminal.
+///
+/// # Returns
+/// The exit code of the program, 0 if it quit normally and 1 if it exited with an error
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let path = matches.value_of("FILE").unwrap();
+    let max_cycles = match matches.value_of("max-cycles") {
+        Some(n) => Some(
+            n.parse::<u64>()
+                .with_context(|| format!("Invalid cycle count '{}'", n))?,
+        ),
+        None => None,
+    };
+
+    let script = read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let (mem, syms) = assembler(&script)?;
+
+    let mut machine = Machine::default();
+    machine.set_config(PipelineConfig {
+        single_cycle: matches.is_present("single-cycle"),
+        forwarding: !matches.is_present("no-forwarding"),
+        branch_stage: match matches.value_of("branch-stage") {
+            Some("id") => BranchStage::Decode,
+            Some("ex") => BranchStage::Execute,
+            _ => BranchStage::Memory,
+        },
+        delay_slot: matches.is_present("delay-slot"),
+        predictor: match matches.value_of("predictor") {
+            Some("taken") => Predictor::Taken,
+            Some("btfnt") => Predictor::Btfnt,
+            Some("1-bit") => Predictor::OneBit,
+            Some("2-bit") => Predictor::TwoBit,
+            _ => Predictor::NotTaken,
+        },
+        bht_entries: table_size(matches, "bht")?,
+        btb_entries: table_size(matches, "btb")?,
+        icache: cache_config(matches, "icache")?,
+        dcache: cache_config(matches, "dcache")?,
+        mmu: matches.is_present("mmu"),
+        tlb_entries: match table_size(matches, "tlb")? {
+            0 => bail!("The TLB needs at least one entry"),
+            n => n,
+        },
+    });
+    machine.set_display_config(display_config(matches)?);
+    // files only touch the disk when a directory has been given for them
+    if let Some(dir) = matches.value_of("sandbox") {
+        machine.set_file_system(Box::new(SandboxFs::new(dir)));
+    }
+    machine.reset();
+    machine.flash(mem, syms);
+
+    let result = execute(&mut machine, max_cycles);
+    // the summary goes to stderr so it is never mixed into the output of the program
+    if matches.is_present("stats") {
+        eprint!("\n{}", summary(&machine));
+    }
+    if let Some(file) = matches.value_of("timeline") {
+        let timeline = machine.timeline();
+        let diagram = if file.ends_with(".csv") {
+            timeline.to_csv()
+        } else {
+            timeline.to_text()
+        };
+        write(file, diagram).with_context(|| format!("Failed to write {}", file))?;
+    }
+    if let Some(file) = matches.value_of("display-dump") {
+        let frame = machine.display_frame();
+        let scale = machine.display_config().pixel_size;
+        let image = if file.ends_with(".png") {
+            frame.to_png(scale)
+        } else {
+            frame.to_ppm(scale)
+        };
+        write(file, image).with_context(|| format!("Failed to write {}", file))?;
+    }
+    result
+}
+
+/// Lines typed on stdin that are shared by the input syscalls and the memory mapped keyboard
+struct Input<R> {
+    reader: R,
+    /// Input that has been read but not consumed yet
+    buffer: String,
+    /// The reader has reached the end of its input
+    ended: bool,
+}
+
+impl<R: BufRead> Input<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            ended: false,
+        }
+    }
+
+    /// Reads the next line if everything read so far has been consumed
+    fn fill(&mut self) -> Result<()> {
+        if self.buffer.is_empty() && !self.ended {
+            self.ended = self.reader.read_line(&mut self.buffer)? == 0;
+        }
+        Ok(())
+    }
+
+    /// Takes everything that has been read but not consumed yet
+    fn take(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// Drops the first `len` bytes that a syscall has consumed
+    fn consume(&mut self, len: usize) {
+        self.buffer.drain(..len);
+    }
+}
+
+/// Cycles the machine until the program exits
+fn execute(machine: &mut Machine, max_cycles: Option<u64>) -> Result<i32> {
+    let stdin = io::stdin();
+    let mut input = Input::new(stdin.lock());
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    let mut cycles = 0;
+    loop {
+        if max_cycles.map(|max| cycles >= max).unwrap_or(false) {
+            bail!("Program did not exit within {} cycles", cycles);
+        }
+        machine.cycle()?;
+        cycles += 1;
+
+        let mut exit_code = None;
+        let mut print = String::new();
+        let mut needs_input = false;
+        let mut eof_allowed = false;
+        machine.handle_syscall(|syscall| {
+            match syscall {
+                Syscall::Print(out) => print.push_str(out),
+                Syscall::Error(out) => {
+                    eprintln!("ERROR: {}", out);
+                    exit_code = Some(1);
+                }
+                Syscall::Quit => exit_code = Some(0),
+                Syscall::ReadInt
+                | Syscall::ReadFloat
+                | Syscall::ReadDouble
+                | Syscall::ReadString { .. }
+                | Syscall::ReadChar => {
+                    // input is read once the closure has released the machine
+                    needs_input = true;
+                    return ControlFlow::Continue(());
+                }
+                Syscall::ReadConsole { .. } => {
+                    // reading file descriptor 0 returns 0 bytes at the end of input
+                    needs_input = true;
+                    eof_allowed = true;
+                    return ControlFlow::Continue(());
+                }
+                // files are opened, read, written and closed by the machine itself
+                Syscall::OpenFile { .. }
+                | Syscall::ReadFile { .. }
+                | Syscall::WriteFile { .. }
+                | Syscall::CloseFile(_) => {}
+            }
+            ControlFlow::Break(())
+        });
+
+        print.push_str(&machine.terminal_mut().take_output());
+        if !print.is_empty() {
+            stdout.write_all(print.as_bytes())?;
+        }
+
+        // at most one line is read each cycle and it goes to whichever consumer is waiting on it,
+        // a syscall blocks the pipeline so it is served first. Running out of input is an error
+        // for a syscall unless it reads the console like a file, while a keyboard that has been
+        // starved just stays empty
+        if needs_input {
+            // make sure any prompt is visible before blocking on input
+            stdout.flush()?;
+            input.fill()?;
+            if input.buffer.is_empty() && !eof_allowed {
+                bail!("Reached end of input while waiting for a syscall");
+            }
+            let used = machine.resolve_input(&input.buffer)?;
+            input.consume(used);
+        } else if machine.terminal().starved() && !input.ended {
+            stdout.flush()?;
+            input.fill()?;
+            machine.terminal_mut().type_keys(&input.take());
+        }
+
+        if let Some(code) = exit_code {
+            stdout.flush()?;
+            return Ok(code);
+        }
+    }
+}
+
+/// Formats the performance counters and the prediction statistics of every branch
+fn summary(machine: &Machine) -> String {
+    let mut out = machine.stats().report();
+    for (name, cache) in [("I-CACHE", machine.icache()), ("D-CACHE", machine.dcache())] {
+        if cache.config().enabled {
+            let _ = write!(out, "\n{}\n{}", name, cache_report(cache.stats()));
+        }
+    }
+    if machine.mmu().enabled() {
+        let stats = machine.mmu().stats();
+        let _ = writeln!(out, "\nTLB");
+        let _ = writeln!(out, "Lookups             {}", stats.lookups);
+        let _ = writeln!(out, "Misses              {}", stats.misses);
+        let _ = writeln!(out, "Write faults        {}", stats.write_faults);
+        let _ = writeln!(out, "Refills             {}", stats.refills);
+        let _ = writeln!(out, "Hit rate            {:.1}%", stats.hit_rate() * 100.0);
+    }
+    out.push_str("\nBRANCHES\n");
+    for (pc, stats) in machine.branch_stats() {
+        let line = machine
+            .source_line(pc)
+            .map(|line| format!("line {}", line + 1))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "0x{:08X}  {:<10}{:>8} executed {:>8} taken  {:5.1}% predicted",
+            pc,
+            line,
+            stats.executed,
+            stats.taken,
+            stats.accuracy() * 100.0
+        );
+    }
+    out
+}
+
+/// Parses the number of entries in a branch prediction table
+fn table_size(matches: &ArgMatches, name: &str) -> Result<u32> {
+    let n = matches.value_of(name).unwrap();
+    n.parse::<u32>()
+        .with_context(|| format!("Invalid table size '{}'", n))
+}
+
+/// Parses the size and position of the bitmap display
+fn display_config(matches: &ArgMatches) -> Result<DisplayConfig> {
+    let spec = matches.value_of("display").unwrap();
+    let dims = spec
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .ok()
+        .filter(|dims| dims.len() == 3)
+        .with_context(|| format!("Invalid display '{}', expected WIDTH,HEIGHT,SIZE", spec))?;
+    let base = matches.value_of("display-base").unwrap();
+    let parsed = match base.strip_prefix("0x").or_else(|| base.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => base.parse::<u32>(),
+    };
+
+    let config = DisplayConfig {
+        base: parsed.with_context(|| format!("Invalid display address '{}'", base))?,
+        width: dims[0],
+        height: dims[1],
+        pixel_size: dims[2],
+    };
+    if let Err(e) = config.validate() {
+        bail!("{}", e);
+    }
+    Ok(config)
+}
+
+/// Parses the geometry of a cache along with the policies shared by both caches
+fn cache_config(matches: &ArgMatches, name: &str) -> Result<CacheConfig> {
+    let spec = match matches.value_of(name) {
+        Some(spec) => spec,
+        None => return Ok(CacheConfig::default()),
+    };
+    let dims = spec
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .ok()
+        .filter(|dims| dims.len() == 3)
+        .with_context(|| format!("Invalid cache '{}', expected SIZE,BLOCK,WAYS", spec))?;
+    let penalty = matches.value_of("miss-penalty").unwrap();
+
+    let config = CacheConfig {
+        enabled: true,
+        size: dims[0],
+        block_size: dims[1],
+        associativity: dims[2],
+        replacement: match matches.value_of("replacement") {
+            Some("fifo") => Replacement::Fifo,
+            Some("random") => Replacement::Random,
+            _ => Replacement::Lru,
+        },
+        write_policy: if matches.is_present("write-through") {
+            WritePolicy::WriteThrough
+        } else {
+            WritePolicy::WriteBack
+        },
+        write_allocate: !matches.is_present("no-write-allocate"),
+        miss_penalty: penalty
+            .parse::<u32>()
+            .with_context(|| format!("Invalid miss penalty '{}'", penalty))?,
+    };
+    if let Err(e) = config.validate() {
+        bail!("{}", e);
+    }
+    Ok(config)
+}
+
+/// Formats the counters of a cache
+fn cache_report(stats: &CacheStats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Reads               {}", stats.reads);
+    let _ = writeln!(out, "Read misses         {}", stats.read_misses);
+    let _ = writeln!(out, "Writes              {}", stats.writes);
+    let _ = writeln!(out, "Write misses        {}", stats.write_misses);
+    let _ = writeln!(out, "Writebacks          {}", stats.writebacks);
+    let _ = writeln!(out, "Hit rate            {:.1}%", stats.hit_rate() * 100.0);
+    let _ = writeln!(out, "Stall cycles        {}", stats.stall_cycles);
+    out
+}
@@ -0,0 +1,170 @@
+//! Devices that are accessed through loads and stores to the memory mapped I/O region
+//!
+//! The terminal follows the keyboard and display of MARS, each register is a word:
+//!
+//! | Address      | Register                                                   |
+//! |--------------|------------------------------------------------------------|
+//! | `0xFFFF0000` | Receiver control, bit 0 ready and bit 1 interrupt enable    |
+//! | `0xFFFF0004` | Receiver data, reading it takes the key and clears ready    |
+//! | `0xFFFF0008` | Transmitter control, bit 0 ready and bit 1 interrupt enable |
+//! | `0xFFFF000C` | Transmitter data, writing it displays a character           |
+
+use std::collections::VecDeque;
+
+use crate::parser::model::MMIO_BASE;
+
+pub const RECEIVER_CONTROL: u32 = MMIO_BASE;
+pub const RECEIVER_DATA: u32 = MMIO_BASE + 4;
+pub const TRANSMITTER_CONTROL: u32 = MMIO_BASE + 8;
+pub const TRANSMITTER_DATA: u32 = MMIO_BASE + 12;
+
+/// Control register bit set when the device is ready
+const READY: u32 = 0x1;
+/// Control register bit set by the program to be interrupted when the device is ready
+const INTERRUPT_ENABLE: u32 = 0x2;
+
+/// Cause register bits the receiver and transmitter raise their interrupts on
+pub const RECEIVER_INTERRUPT: u32 = 0x100;
+pub const TRANSMITTER_INTERRUPT: u32 = 0x200;
+
+/// Cycles the transmitter stays busy after a character is written
+const TRANSMIT_DELAY: u32 = 5;
+
+/// A keyboard and display pair
+#[derive(Debug, Clone, Default)]
+pub struct Terminal {
+    /// Keys typed that the receiver hasn't taken yet
+    keys: VecDeque<u8>,
+    receiver_data: u8,
+    receiver_ready: bool,
+    receiver_interrupt: bool,
+    /// Cycles until the transmitter is ready again
+    transmitter_busy: u32,
+    transmitter_interrupt: bool,
+    /// Characters displayed that the host hasn't shown yet
+    output: Vec<u8>,
+    /// The program has accessed the receiver
+    connected: bool,
+    /// The program polled the receiver while no key was waiting
+    starved: bool,
+}
+
+impl Terminal {
+    /// Queues keys typed by the user
+    pub fn type_keys(&mut self, keys: &str) {
+        self.keys.extend(keys.bytes());
+        self.starved = false;
+    }
+
+    /// Takes the characters the program has displayed since the last call
+    pub fn take_output(&mut self) -> String {
+        let output = String::from_utf8_lossy(&self.output).into_owned();
+        self.output.clear();
+        output
+    }
+
+    /// Checks if the program has accessed the receiver so keys should be sent to it
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Checks if the program is waiting for a key that hasn't been typed yet, either by polling
+    /// the receiver or by waiting for its interrupt
+    pub fn starved(&self) -> bool {
+        self.keys.is_empty() && !self.receiver_ready && (self.starved || self.receiver_interrupt)
+    }
+
+    fn read(&mut self, address: u32) -> u32 {
+        match address {
+            RECEIVER_CONTROL => {
+                self.connected = true;
+                self.starved |= !self.receiver_ready && self.keys.is_empty();
+                control(self.receiver_ready, self.receiver_interrupt)
+            }
+            RECEIVER_DATA => {
+                self.connected = true;
+                self.receiver_ready = false;
+                self.receiver_data as u32
+            }
+            TRANSMITTER_CONTROL => control(self.transmitter_busy == 0, self.transmitter_interrupt),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, address: u32, val: u32) {
+        match address {
+            RECEIVER_CONTROL => {
+                self.connected = true;
+                self.receiver_interrupt = val & INTERRUPT_ENABLE != 0;
+            }
+            TRANSMITTER_CONTROL => self.transmitter_interrupt = val & INTERRUPT_ENABLE != 0,
+            // characters written while the transmitter is busy are lost
+            TRANSMITTER_DATA if self.transmitter_busy == 0 => {
+                self.output.push(val as u8);
+                self.transmitter_busy = TRANSMIT_DELAY;
+            }
+            _ => {}
+        }
+    }
+
+    /// Advances the terminal by a cycle
+    ///
+    /// # Returns
+    /// The Cause bits of the interrupts that are being requested
+    fn tick(&mut self) -> u32 {
+        if !self.receiver_ready {
+            if let Some(key) = self.keys.pop_front() {
+                self.receiver_data = key;
+                self.receiver_ready = true;
+            }
+        }
+        self.transmitter_busy = self.transmitter_busy.saturating_sub(1);
+
+        let mut pending = 0;
+        if self.receiver_ready && self.receiver_interrupt {
+            pending |= RECEIVER_INTERRUPT;
+        }
+        if self.transmitter_busy == 0 && self.transmitter_interrupt {
+            pending |= TRANSMITTER_INTERRUPT;
+        }
+        pending
+    }
+}
+
+/// Formats a control register
+fn control(ready: bool, interrupt: bool) -> u32 {
+    let mut val = 0;
+    if ready {
+        val |= READY;
+    }
+    if interrupt {
+        val |= INTERRUPT_ENABLE;
+    }
+    val
+}
+
+/// Routes accesses to the memory mapped I/O region to the device at that address
+#[derive(Debug, Clone, Default)]
+pub struct Devices {
+    pub terminal: Terminal,
+}
+
+impl Devices {
+    /// Reads the word aligned register at `address`, reading may change the state of a device
+    pub(crate) fn read(&mut self, address: u32) -> u32 {
+        self.terminal.read(address)
+    }
+
+    /// Writes the word aligned register at `address`
+    pub(crate) fn write(&mut self, address: u32, val: u32) {
+        self.terminal.write(address, val);
+    }
+
+    /// Advances every device by a cycle
+    ///
+    /// # Returns
+    /// The Cause bits of the interrupts that are being requested
+    pub(crate) fn tick(&mut self) -> u32 {
+        self.terminal.tick()
+    }
+}
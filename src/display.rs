@@ -0,0 +1,181 @@
+//! Bitmap display that shows a framebuffer held in memory
+//!
+//! Every pixel is a word holding its colour as `0x00RRGGBB`, rows are laid out one after another
+//! from the base address. The display only ever reads memory so a program draws by storing to it.
+
+use crate::{
+    parser::model::{DATA_BASE, MMIO_BASE},
+    Memory,
+};
+
+/// Settings of the bitmap display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayConfig {
+    /// Address of the top left pixel
+    pub base: u32,
+    /// Pixels in each row
+    pub width: u32,
+    /// Pixels in each column
+    pub height: u32,
+    /// Length of the side of a pixel when drawn on screen
+    pub pixel_size: u32,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            base: DATA_BASE,
+            width: 64,
+            height: 64,
+            pixel_size: 8,
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// Bytes of memory the framebuffer covers
+    pub fn bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 * 4
+    }
+
+    /// Checks that the framebuffer is word aligned and lies below the memory mapped devices
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 || self.pixel_size == 0 {
+            return Err("The display and its pixels must be at least 1 wide and high".to_string());
+        }
+        if self.base & 3 != 0 {
+            return Err(format!(
+                "The display must start on a word boundary, got 0x{:08X}",
+                self.base
+            ));
+        }
+        if self.base as u64 + self.bytes() > MMIO_BASE as u64 {
+            return Err(format!(
+                "A {}x{} display at 0x{:08X} runs into the memory mapped devices",
+                self.width, self.height, self.base
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The pixels shown by the display at a point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// Colours as `0x00RRGGBB` a row at a time from the top left
+    pub pixels: Vec<u32>,
+}
+
+impl Frame {
+    /// Reads the framebuffer described by `config` out of memory
+    pub fn capture(config: &DisplayConfig, mem: &Memory) -> Self {
+        let pixels = (0..config.width * config.height)
+            .map(|i| mem.get(config.base.wrapping_add(i * 4)).unwrap_or(0) & 0x00FF_FFFF)
+            .collect();
+        Self {
+            width: config.width,
+            height: config.height,
+            pixels,
+        }
+    }
+
+    /// Gets the red, green and blue bytes of every pixel with each pixel repeated into a square
+    /// of `scale` points
+    pub fn to_rgb(&self, scale: u32) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.pixels.len() * (scale * scale * 3) as usize);
+        for row in self.pixels.chunks(self.width as usize) {
+            for _ in 0..scale {
+                for pixel in row {
+                    for _ in 0..scale {
+                        rgb.extend_from_slice(&pixel.to_be_bytes()[1..]);
+                    }
+                }
+            }
+        }
+        rgb
+    }
+
+    /// Encodes the frame as a binary PPM image with each pixel `scale` points wide
+    pub fn to_ppm(&self, scale: u32) -> Vec<u8> {
+        let mut ppm =
+            format!("P6\n{} {}\n255\n", self.width * scale, self.height * scale).into_bytes();
+        ppm.extend(self.to_rgb(scale));
+        ppm
+    }
+
+    /// Encodes the frame as a PNG image with each pixel `scale` points wide
+    ///
+    /// The image data is stored without compression so no encoder is needed
+    pub fn to_png(&self, scale: u32) -> Vec<u8> {
+        let (width, height) = (self.width * scale, self.height * scale);
+        let rgb = self.to_rgb(scale);
+
+        // every row starts with the filter type, 0 leaves the row as it is
+        let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+        for row in rgb.chunks(width as usize * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        // a zlib stream of stored deflate blocks, each holding at most 65535 bytes
+        let mut zlib = vec![0x78, 0x01];
+        let blocks = raw.chunks(0xFFFF);
+        let last = blocks.len() - 1;
+        for (i, block) in blocks.enumerate() {
+            let len = block.len() as u16;
+            zlib.push((i == last) as u8);
+            zlib.extend_from_slice(&len.to_le_bytes());
+            zlib.extend_from_slice(&(!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = vec![];
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering and no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib);
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Appends a PNG chunk with its length and checksum
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
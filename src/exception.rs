@@ -0,0 +1,108 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{
+    parser::disassemble,
+    protection::{Access, Violation},
+};
+
+/// Exceptions that can be raised by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Exception {
+    /// A device requested an interrupt, taken before the instruction at the faulting pc
+    #[error("Interrupt")]
+    Interrupt,
+    #[error("TLB modification storing to 0x{0:08X}")]
+    TlbModified(u32),
+    #[error("TLB miss loading from 0x{0:08X}")]
+    TlbLoad(u32),
+    #[error("TLB miss storing to 0x{0:08X}")]
+    TlbStore(u32),
+    #[error("Address error loading from 0x{0:08X}")]
+    AddressLoad(u32),
+    #[error("Address error storing to 0x{0:08X}")]
+    AddressStore(u32),
+    #[error("{0}")]
+    Protection(Violation),
+    #[error("Reserved instruction")]
+    ReservedInstruction,
+    #[error("Arithmetic overflow")]
+    Overflow,
+}
+
+impl Exception {
+    /// The exception code used by MIPS to identify this exception
+    pub fn code(&self) -> u32 {
+        match self {
+            Exception::Interrupt => 0,
+            Exception::TlbModified(_) => 1,
+            Exception::TlbLoad(_) => 2,
+            Exception::TlbStore(_) => 3,
+            Exception::AddressLoad(_) => 4,
+            Exception::AddressStore(_) => 5,
+            // protection faults are address errors that name the region of the address
+            Exception::Protection(v) if v.access == Access::Write => 5,
+            Exception::Protection(_) => 4,
+            Exception::ReservedInstruction => 10,
+            Exception::Overflow => 12,
+        }
+    }
+
+    /// The address that caused an address error or TLB exception
+    pub fn bad_address(&self) -> Option<u32> {
+        match self {
+            Exception::TlbModified(addr)
+            | Exception::TlbLoad(addr)
+            | Exception::TlbStore(addr)
+            | Exception::AddressLoad(addr)
+            | Exception::AddressStore(addr) => Some(*addr),
+            Exception::Protection(violation) => Some(violation.address),
+            _ => None,
+        }
+    }
+
+    /// Checks if this exception was raised by the TLB
+    pub fn is_tlb(&self) -> bool {
+        matches!(
+            self,
+            Exception::TlbModified(_) | Exception::TlbLoad(_) | Exception::TlbStore(_)
+        )
+    }
+}
+
+/// An exception raised by the instruction at `pc`
+///
+/// Every instruction older than the faulting one has completed and every younger one has been
+/// discarded
+#[derive(Debug, Clone, Copy)]
+pub struct Fault {
+    pub exception: Exception,
+    pub pc: u32,
+    /// The faulting instruction, `None` if it couldn't be fetched or for an interrupt
+    pub instruction: Option<u32>,
+}
+
+impl Fault {
+    /// Creates the fault raised by an instruction that has reached the end of the pipeline
+    pub(crate) fn new(exception: Exception, pc: u32, instruction: u32) -> Self {
+        let fetched = exception.bad_address() != Some(pc) && exception != Exception::Interrupt;
+        Self {
+            exception,
+            pc,
+            instruction: Some(instruction).filter(|_| fetched),
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:08X}", self.exception, self.pc)?;
+        if let Some(instruction) = self.instruction {
+            write!(f, " ({})", disassemble(instruction, self.pc))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Fault {}
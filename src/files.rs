@@ -0,0 +1,447 @@
+//! Files a program can open through the file syscalls
+//!
+//! Files live in a [`FileSystem`], either a directory on the host that the program can't escape or
+//! a set of files held in memory. File descriptors 0, 1 and 2 are the console and never reach the
+//! file system.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind},
+};
+
+/// Where the files opened by a program are kept
+pub trait FileSystem {
+    /// Reads the whole contents of a file
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Replaces the contents of a file, creating it if it doesn't exist
+    fn write(&mut self, path: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Adds to the end of a file, creating it if it doesn't exist
+    fn append(&mut self, path: &str, data: &[u8]) -> io::Result<()>;
+
+    fn remove(&mut self, path: &str) -> io::Result<()>;
+
+    /// Lists the paths of every file sorted by name
+    fn list(&self) -> Vec<String>;
+}
+
+/// Files held in memory, used in the browser and by the editor
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryFs {
+    /// Paths are compared as they are apart from a leading `./`
+    fn key(path: &str) -> &str {
+        path.strip_prefix("./").unwrap_or(path)
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.files
+            .get(Self::key(path))
+            .cloned()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("No file named {}", path)))
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.files
+            .insert(Self::key(path).to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn append(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.files
+            .entry(Self::key(path).to_string())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        self.files
+            .remove(Self::key(path))
+            .map(|_| ())
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("No file named {}", path)))
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.files.keys().cloned().collect()
+    }
+}
+
+/// A directory on the host, paths are relative to it and may not leave it
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct SandboxFs {
+    root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SandboxFs {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Finds a file in the sandbox refusing absolute paths, `..` and links that lead out of it
+    fn resolve(&self, path: &str) -> io::Result<std::path::PathBuf> {
+        use std::path::{Component, Path};
+
+        let outside = || {
+            io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("{} is outside of {}", path, self.root.display()),
+            )
+        };
+        let relative = Path::new(path);
+        let contained = !path.is_empty()
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !contained {
+            return Err(outside());
+        }
+
+        // links are followed before checking where the file is, a file that doesn't exist yet is
+        // found through the directory it will be created in
+        let joined = self.root.join(relative);
+        let resolved = match joined.canonicalize() {
+            Ok(resolved) => resolved,
+            // a link that leads nowhere could still be followed out when the file is created
+            Err(_) if joined.symlink_metadata().is_ok() => return Err(outside()),
+            Err(_) => {
+                let name = joined.file_name().ok_or_else(outside)?;
+                joined
+                    .parent()
+                    .ok_or_else(outside)?
+                    .canonicalize()?
+                    .join(name)
+            }
+        };
+        if !resolved.starts_with(self.root.canonicalize()?) {
+            return Err(outside());
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSystem for SandboxFs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.resolve(path)?)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        std::fs::write(self.resolve(path)?, data)
+    }
+
+    fn append(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        use std::io::Write;
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.resolve(path)?)?
+            .write_all(data)
+    }
+
+    fn remove(&mut self, path: &str) -> io::Result<()> {
+        std::fs::remove_file(self.resolve(path)?)
+    }
+
+    fn list(&self) -> Vec<String> {
+        let mut files: Vec<String> = std::fs::read_dir(&self.root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+/// Flags of the open syscall
+pub mod open_flags {
+    pub const READ: u32 = 0;
+    /// Creates the file or empties it if it exists
+    pub const WRITE: u32 = 1;
+    /// Creates the file or writes after its contents if it exists
+    pub const APPEND: u32 = 9;
+}
+use open_flags::*;
+
+/// First descriptor handed out, lower ones are the console
+const FIRST_FD: u32 = 3;
+
+/// A file opened by the program
+#[derive(Debug, Clone)]
+enum OpenFile {
+    /// Files opened for reading are read whole when they are opened
+    Read { data: Vec<u8>, pos: usize },
+    /// Writes go straight to the file system so nothing is lost if the file is never closed
+    Write { path: String },
+}
+
+/// The file system of a machine along with the files the program has open
+pub struct Files {
+    fs: Box<dyn FileSystem>,
+    open: BTreeMap<u32, OpenFile>,
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self::new(Box::new(MemoryFs::default()))
+    }
+}
+
+impl Files {
+    pub fn new(fs: Box<dyn FileSystem>) -> Self {
+        Self {
+            fs,
+            open: BTreeMap::new(),
+        }
+    }
+
+    pub fn fs(&self) -> &dyn FileSystem {
+        self.fs.as_ref()
+    }
+
+    pub fn fs_mut(&mut self) -> &mut dyn FileSystem {
+        self.fs.as_mut()
+    }
+
+    /// Closes every file the program left open
+    pub fn close_all(&mut self) {
+        self.open.clear();
+    }
+
+    /// Opens a file
+    ///
+    /// # Returns
+    /// The new file descriptor
+    pub fn open(&mut self, path: &str, flags: u32) -> io::Result<u32> {
+        let file = match flags {
+            READ => OpenFile::Read {
+                data: self.fs.read(path)?,
+                pos: 0,
+            },
+            WRITE | APPEND => {
+                if flags == WRITE {
+                    self.fs.write(path, &[])?;
+                } else {
+                    self.fs.append(path, &[])?;
+                }
+                OpenFile::Write {
+                    path: path.to_string(),
+                }
+            }
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unsupported open flags {}", flags),
+                ))
+            }
+        };
+        // reuse the lowest descriptor that has been closed
+        let fd = (FIRST_FD..).find(|fd| !self.open.contains_key(fd)).unwrap();
+        self.open.insert(fd, file);
+        Ok(fd)
+    }
+
+    /// Gets the next `max_len` bytes of a file opened for reading without reading them
+    ///
+    /// # Returns
+    /// The bytes that would be read, empty at the end of the file
+    pub fn peek(&self, fd: u32, max_len: usize) -> io::Result<&[u8]> {
+        match self.open.get(&fd) {
+            Some(OpenFile::Read { data, pos }) => {
+                let end = pos.saturating_add(max_len).min(data.len());
+                Ok(&data[*pos..end])
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("File descriptor {} is not open for reading", fd),
+            )),
+        }
+    }
+
+    /// Reads at most `max_len` bytes from a file opened for reading
+    ///
+    /// # Returns
+    /// The bytes read, empty at the end of the file
+    pub fn read(&mut self, fd: u32, max_len: usize) -> io::Result<Vec<u8>> {
+        let read = self.peek(fd, max_len)?.to_vec();
+        if let Some(OpenFile::Read { pos, .. }) = self.open.get_mut(&fd) {
+            *pos += read.len();
+        }
+        Ok(read)
+    }
+
+    /// Writes to the end of a file opened for writing
+    pub fn write(&mut self, fd: u32, data: &[u8]) -> io::Result<()> {
+        match self.open.get(&fd) {
+            Some(OpenFile::Write { path }) => self.fs.append(path, data),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("File descriptor {} is not open for writing", fd),
+            )),
+        }
+    }
+
+    pub fn close(&mut self, fd: u32) {
+        self.open.remove(&fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Files {
+        let mut fs = MemoryFs::default();
+        fs.write("in.txt", b"hello world").unwrap();
+        Files::new(Box::new(fs))
+    }
+
+    #[test]
+    fn open_reuses_the_lowest_closed_descriptor() {
+        let mut files = files();
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD);
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD + 1);
+        assert_eq!(files.open("out.txt", WRITE).unwrap(), FIRST_FD + 2);
+        files.close(FIRST_FD + 1);
+        files.close(FIRST_FD);
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD);
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD + 1);
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD + 3);
+    }
+
+    #[test]
+    fn failed_open_uses_no_descriptor() {
+        let mut files = files();
+        assert!(files.open("missing.txt", READ).is_err());
+        assert!(files.open("in.txt", 2).is_err());
+        assert_eq!(files.open("in.txt", READ).unwrap(), FIRST_FD);
+    }
+
+    #[test]
+    fn reads_continue_where_the_last_one_stopped() {
+        let mut files = files();
+        let fd = files.open("in.txt", READ).unwrap();
+        assert_eq!(files.peek(fd, 5).unwrap(), b"hello");
+        assert_eq!(files.read(fd, 5).unwrap(), b"hello");
+        assert_eq!(files.read(fd, 100).unwrap(), b" world");
+        assert!(files.read(fd, 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn descriptors_are_read_separately() {
+        let mut files = files();
+        let first = files.open("in.txt", READ).unwrap();
+        let second = files.open("in.txt", READ).unwrap();
+        files.read(first, 6).unwrap();
+        assert_eq!(files.read(second, 5).unwrap(), b"hello");
+        assert_eq!(files.read(first, 5).unwrap(), b"world");
+    }
+
+    #[test]
+    fn reads_and_writes_need_the_right_mode() {
+        let mut files = files();
+        let read = files.open("in.txt", READ).unwrap();
+        let write = files.open("out.txt", WRITE).unwrap();
+        assert!(files.write(read, b"x").is_err());
+        assert!(files.read(write, 1).is_err());
+        files.close(read);
+        assert!(files.read(read, 1).is_err());
+    }
+
+    #[test]
+    fn write_truncates_and_append_keeps_the_contents() {
+        let mut files = files();
+        let fd = files.open("in.txt", APPEND).unwrap();
+        files.write(fd, b"!").unwrap();
+        assert_eq!(files.fs().read("in.txt").unwrap(), b"hello world!");
+
+        let fd = files.open("in.txt", WRITE).unwrap();
+        files.write(fd, b"bye").unwrap();
+        files.write(fd, b" now").unwrap();
+        assert_eq!(files.fs().read("./in.txt").unwrap(), b"bye now");
+    }
+
+    /// An empty directory for a sandbox that is only used by one test
+    #[cfg(not(target_arch = "wasm32"))]
+    fn sandbox(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("sandbox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("inner")).unwrap();
+        dir
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn sandbox_refuses_paths_out_of_it() {
+        let dir = sandbox("paths");
+        let mut fs = SandboxFs::new(&dir);
+        for path in [
+            "",
+            "/etc/passwd",
+            "../outside.txt",
+            "inner/../../outside.txt",
+        ] {
+            let err = fs.write(path, b"x").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::PermissionDenied, "{}", path);
+        }
+        assert!(!dir.parent().unwrap().join("outside.txt").exists());
+
+        fs.write("inner/ok.txt", b"ok").unwrap();
+        assert_eq!(fs.read("./inner/ok.txt").unwrap(), b"ok");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sandbox_refuses_links_out_of_it() {
+        use std::os::unix::fs::symlink;
+
+        let dir = sandbox("links");
+        let outside = sandbox("links-outside");
+        std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        symlink(&outside, dir.join("dir")).unwrap();
+        symlink(outside.join("secret.txt"), dir.join("file")).unwrap();
+        symlink(outside.join("new.txt"), dir.join("dangling")).unwrap();
+        symlink(dir.join("inner"), dir.join("inside")).unwrap();
+
+        let mut fs = SandboxFs::new(&dir);
+        for path in ["dir/secret.txt", "file", "dir/new.txt", "dangling"] {
+            assert_eq!(
+                fs.read(path).unwrap_err().kind(),
+                ErrorKind::PermissionDenied,
+                "{}",
+                path
+            );
+            assert_eq!(
+                fs.append(path, b"x").unwrap_err().kind(),
+                ErrorKind::PermissionDenied,
+                "{}",
+                path
+            );
+        }
+        assert!(!outside.join("new.txt").exists());
+        assert_eq!(
+            std::fs::read(outside.join("secret.txt")).unwrap(),
+            b"secret"
+        );
+
+        // links that stay in the sandbox are followed
+        fs.write("inside/ok.txt", b"ok").unwrap();
+        assert_eq!(fs.read("inner/ok.txt").unwrap(), b"ok");
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+//! Hazard detection unit that decides when an instruction has to wait in decode
+
+use std::fmt;
+
+use crate::{
+    mmu::{tlb_ctrl::*, TLB_REGISTERS},
+    pipeline::{BranchStage, PipelineConfig, PipelineState},
+    stages::{
+        execute::{branch_ctrl::*, IdEx},
+        memory::ExMem,
+    },
+    Register, ZERO,
+};
+
+/// A value an instruction reads before it is ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hazard {
+    Register(Register),
+    FpRegister(Register),
+    HiLo,
+    Fcc,
+    Cp0(Register),
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hazard::Register(reg) => write!(f, "${}", reg.name()),
+            Hazard::FpRegister(reg) => write!(f, "$f{}", reg.value()),
+            Hazard::HiLo => write!(f, "hi/lo"),
+            Hazard::Fcc => write!(f, "fcc"),
+            Hazard::Cp0(reg) => write!(f, "${} (cp0)", reg.value()),
+        }
+    }
+}
+
+/// Reasons the front of the pipeline can be held in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stall {
+    /// An instruction reads the result of the load ahead of it
+    LoadUse(Hazard),
+    /// An instruction reads a result that can't be forwarded to it yet
+    Data(Hazard),
+    /// A syscall is draining through the pipeline
+    Syscall,
+    /// Fetch is waiting for a block to arrive in the instruction cache
+    InstructionMiss,
+    /// The memory stage is waiting for a block to arrive in the data cache
+    DataMiss,
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stall::LoadUse(hazard) => write!(f, "load-use on {}", hazard),
+            Stall::Data(hazard) => write!(f, "data hazard on {}", hazard),
+            Stall::Syscall => write!(f, "syscall"),
+            Stall::InstructionMiss => write!(f, "i-cache miss"),
+            Stall::DataMiss => write!(f, "d-cache miss"),
+        }
+    }
+}
+
+/// Why a pipeline latch holds a bubble instead of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bubble {
+    /// Inserted while an instruction waited in decode
+    Stall(Stall),
+    /// An instruction fetched after a mispredicted branch or jump was squashed
+    Flush,
+}
+
+impl fmt::Display for Bubble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bubble::Stall(stall) => write!(f, "stall: {}", stall),
+            Bubble::Flush => write!(f, "flush"),
+        }
+    }
+}
+
+/// Values an instruction that is still in flight is going to write
+#[derive(Clone, Copy)]
+struct Writes {
+    reg: bool,
+    hi_lo: bool,
+    fp: bool,
+    fp_double: bool,
+    fcc: bool,
+    cp0: bool,
+    /// `tlbr` and `tlbp` write coprocessor 0 registers in writeback
+    tlb: bool,
+    dest: Register,
+}
+
+impl From<&IdEx> for Writes {
+    fn from(ins: &IdEx) -> Self {
+        Self {
+            reg: ins.reg_write,
+            hi_lo: ins.hi_lo_write,
+            fp: ins.fp_write,
+            fp_double: ins.fp_double,
+            fcc: ins.fcc_write,
+            cp0: ins.cp0_write,
+            tlb: ins.tlb_op == TLB_READ || ins.tlb_op == TLB_PROBE,
+            dest: if ins.reg_dst { ins.rd } else { ins.rt },
+        }
+    }
+}
+
+impl From<&ExMem> for Writes {
+    fn from(ins: &ExMem) -> Self {
+        Self {
+            reg: ins.reg_write,
+            hi_lo: ins.hi_lo_write,
+            fp: ins.fp_write,
+            fp_double: ins.fp_double,
+            fcc: ins.fcc_write,
+            cp0: ins.cp0_write,
+            tlb: ins.tlb_op == TLB_READ || ins.tlb_op == TLB_PROBE,
+            dest: ins.write_register,
+        }
+    }
+}
+
+impl Writes {
+    /// Finds a value read by a decoded instruction that this instruction writes
+    ///
+    /// Stores read the value they write to memory a stage later than every other source, that
+    /// value is only checked if `late` is set
+    fn conflict(&self, ins: &IdEx, late: bool) -> Option<Hazard> {
+        // writes to $zero are discarded so nothing ever waits on them
+        if self.reg && self.dest != ZERO {
+            let rt_read = ins.rt_read && (late || !ins.mem_write);
+            if (ins.rs_read && ins.rs == self.dest) || (rt_read && ins.rt == self.dest) {
+                return Some(Hazard::Register(self.dest));
+            }
+        }
+
+        // floating point values may be register pairs so check for any overlap
+        if self.fp {
+            let first = self.dest.value();
+            let last = first + self.fp_double as u32;
+            let overlaps = |reg: Register| {
+                reg.value() <= last && first <= reg.value() + ins.fp_read_double as u32
+            };
+            let ft_read = ins.ft_read && (late || !ins.mem_write);
+            if ins.fs_read && overlaps(ins.fs) {
+                return Some(Hazard::FpRegister(ins.fs));
+            }
+            if ft_read && overlaps(ins.ft) {
+                return Some(Hazard::FpRegister(ins.ft));
+            }
+        }
+
+        if self.hi_lo && ins.hi_lo_read {
+            return Some(Hazard::HiLo);
+        }
+
+        let fcc_read =
+            ins.branch && (ins.branch_cond == BRANCH_FP_TRUE || ins.branch_cond == BRANCH_FP_FALSE);
+        if self.fcc && fcc_read {
+            return Some(Hazard::Fcc);
+        }
+
+        if self.cp0 && (ins.cp0_read || ins.eret) && self.dest == ins.rd {
+            return Some(Hazard::Cp0(self.dest));
+        }
+        None
+    }
+
+    /// Finds a coprocessor 0 register read by a decoded instruction that a TLB instruction writes
+    fn tlb_conflict(&self, ins: &IdEx) -> Option<Hazard> {
+        if self.tlb && ins.cp0_read && TLB_REGISTERS.contains(&ins.rd) {
+            Some(Hazard::Cp0(ins.rd))
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks if a decoded instruction has to wait in decode because a value it reads is not ready
+pub(crate) fn detect(state: &PipelineState, id_ex: &IdEx, config: PipelineConfig) -> Option<Stall> {
+    let in_ex = Writes::from(&state.id_ex);
+    let in_mem = Writes::from(&state.ex_mem);
+
+    // TLB instructions write coprocessor 0 in writeback so their results are never forwarded
+    if let Some(hazard) = in_ex
+        .tlb_conflict(id_ex)
+        .or_else(|| in_mem.tlb_conflict(id_ex))
+    {
+        return Some(Stall::Data(hazard));
+    }
+
+    // branches resolved in decode need their operands a stage earlier than everything else, only
+    // ALU results in the memory stage can be forwarded to them
+    if config.branch_stage == BranchStage::Decode && (id_ex.branch || id_ex.jump) {
+        if let Some(hazard) = in_ex.conflict(id_ex, false) {
+            return Some(if state.id_ex.mem_read {
+                Stall::LoadUse(hazard)
+            } else {
+                Stall::Data(hazard)
+            });
+        }
+        return match in_mem.conflict(id_ex, false) {
+            Some(hazard) if state.ex_mem.read => Some(Stall::LoadUse(hazard)),
+            Some(hazard) if !config.forwarding => Some(Stall::Data(hazard)),
+            _ => None,
+        };
+    }
+
+    // without forwarding results can only be read once they have been written back
+    if !config.forwarding {
+        return in_ex
+            .conflict(id_ex, true)
+            .or_else(|| in_mem.conflict(id_ex, true))
+            .map(Stall::Data);
+    }
+
+    // loads only have their data at the end of the memory stage, stores read their data in the
+    // memory stage so it is forwarded to them from writeback instead
+    if state.id_ex.mem_read {
+        return in_ex.conflict(id_ex, false).map(Stall::LoadUse);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{T0, T1};
+
+    /// `lw $t0, 0($t1)` in execute
+    fn load_in_execute() -> PipelineState {
+        PipelineState {
+            id_ex: IdEx {
+                mem_read: true,
+                mem_to_reg: true,
+                reg_write: true,
+                rs: T1,
+                rt: T0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// An instruction in decode that reads `rs` and `rt`
+    fn reads(rs: Register, rt: Register) -> IdEx {
+        IdEx {
+            rs_read: true,
+            rt_read: true,
+            rs,
+            rt,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn load_use_stalls() {
+        let config = PipelineConfig::default();
+        let stall = detect(&load_in_execute(), &reads(T0, T1), config);
+        assert_eq!(stall, Some(Stall::LoadUse(Hazard::Register(T0))));
+        let stall = detect(&load_in_execute(), &reads(T1, T0), config);
+        assert_eq!(stall, Some(Stall::LoadUse(Hazard::Register(T0))));
+    }
+
+    #[test]
+    fn unrelated_registers_do_not_stall() {
+        let stall = detect(
+            &load_in_execute(),
+            &reads(T1, T1),
+            PipelineConfig::default(),
+        );
+        assert_eq!(stall, None);
+    }
+
+    #[test]
+    fn load_in_memory_stage_is_forwarded() {
+        let state = PipelineState {
+            ex_mem: ExMem {
+                read: true,
+                mem_to_reg: true,
+                reg_write: true,
+                write_register: T0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            detect(&state, &reads(T0, T1), PipelineConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn stored_value_is_forwarded_from_writeback() {
+        // sw $t0, 0($t1) reads $t0 a stage later so the load doesn't hold it up
+        let store = IdEx {
+            mem_write: true,
+            ..reads(T1, T0)
+        };
+        assert_eq!(
+            detect(&load_in_execute(), &store, PipelineConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn loads_to_zero_do_not_stall() {
+        let mut state = load_in_execute();
+        state.id_ex.rt = ZERO;
+        let stall = detect(&state, &reads(ZERO, ZERO), PipelineConfig::default());
+        assert_eq!(stall, None);
+    }
+
+    #[test]
+    fn without_forwarding_every_result_is_waited_on() {
+        let config = PipelineConfig {
+            forwarding: false,
+            ..Default::default()
+        };
+        let stall = detect(&load_in_execute(), &reads(T0, T1), config);
+        assert_eq!(stall, Some(Stall::Data(Hazard::Register(T0))));
+    }
+}
@@ -1,10 +1,23 @@
 mod app;
+mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+mod devices;
+mod display;
+mod exception;
+mod files;
+mod hazard;
 mod machine;
 mod memory;
+mod mmu;
 mod parser;
 mod pipeline;
+mod predictor;
+mod protection;
 mod register;
+mod stats;
 mod syscall;
+mod timeline;
 
 pub mod stages {
     pub mod writeback;
@@ -31,9 +44,23 @@ pub mod stages {
 }
 
 pub use app::App;
+pub use cache::{CacheConfig, CacheStats, Replacement, WritePolicy};
+pub use devices::{Devices, Terminal};
+pub use display::{DisplayConfig, Frame};
+pub use exception::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use files::SandboxFs;
+pub use files::{FileSystem, Files, MemoryFs};
+pub use hazard::{Bubble, Hazard, Stall};
 pub use machine::*;
 pub use memory::*;
+pub use mmu::{Mmu, Page, TlbEntry, TlbStats, PAGE_SIZE};
+pub use pipeline::{BranchStage, PipelineConfig};
+pub use predictor::{BranchStats, Predictor};
+pub use protection::{Access, MemoryMap, Permissions, Region, Violation};
 pub use register::*;
+pub use stats::Stats;
+pub use timeline::Timeline;
 
 // ----------------------------------------------------------------------------
 // When compiling for web:
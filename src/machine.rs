@@ -1,15 +1,21 @@
 use std::ops::ControlFlow;
 
 use crate::{
+    cache::Cache,
+    files::{FileSystem, Files},
     parser::{
         self, compute_labels,
-        model::{LabelTable, Line, Segment, Segments, STACK_BASE, TEXT_BASE},
+        model::{LabelTable, Line, Segment, Segments, KTEXT_BASE, STACK_BASE, TEXT_BASE},
     },
-    pipeline::{self, PipelineState},
-    syscall::{resolve_syscall, Syscall},
-    Memory, Register, RegisterFile, SP,
+    pipeline::{self, has_delay_slot, Hardware, PipelineConfig, PipelineState},
+    predictor::BranchStats,
+    stats::Stats,
+    syscall::{file_syscall, resolve_syscall, Syscall},
+    timeline::Timeline,
+    Devices, DisplayConfig, Exception, Fault, Frame, Memory, Mmu, Register, RegisterFile, Terminal,
+    ENTRY_HI, ENTRY_LO, SP,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 /// Represents an instance of a simulated MIPS computer.
 #[derive(Default)]
@@ -20,9 +26,65 @@ pub struct Machine {
     mem: Memory,
     syms: LabelTable,
     pending_syscall: Option<Syscall>,
+    config: PipelineConfig,
+    hw: Hardware,
+    stats: Stats,
+    timeline: Timeline,
+    display: DisplayConfig,
+    files: Files,
 }
 
 impl Machine {
+    /// Fetch the options the pipeline is running with
+    pub fn config(&self) -> PipelineConfig {
+        self.config
+    }
+
+    /// Change the options the pipeline runs with
+    ///
+    /// The pipeline is flushed so no instruction is in flight across the change
+    pub fn set_config(&mut self, config: PipelineConfig) {
+        if self.config != config {
+            self.config = config;
+            self.reset();
+        }
+    }
+
+    /// Get the performance counters of the program that is running
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Get the history of which instruction was in each pipeline stage during recent cycles
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    /// Get the L1 instruction cache
+    pub fn icache(&self) -> &Cache {
+        &self.hw.caches.instruction
+    }
+
+    /// Get the L1 data cache
+    pub fn dcache(&self) -> &Cache {
+        &self.hw.caches.data
+    }
+
+    /// Get the MMU holding the TLB and page table
+    pub fn mmu(&self) -> &Mmu {
+        &self.hw.mmu
+    }
+
+    /// Get the prediction statistics of every branch and jump that has executed sorted by address
+    pub fn branch_stats(&self) -> Vec<(u32, BranchStats)> {
+        self.hw.predictor.stats()
+    }
+
+    /// Get the counters of the branch history table
+    pub fn branch_history(&self) -> &[u8] {
+        self.hw.predictor.history()
+    }
+
     /// Fetch a readonly view of this machines registers
     pub fn register(&self, reg: Register) -> u32 {
         self.regs.read_register(reg)
@@ -32,6 +94,75 @@ impl Machine {
         self.regs.get_mut(reg)
     }
 
+    /// Fetch the contents of the HI and LO registers
+    pub fn hi_lo(&self) -> (u32, u32) {
+        (self.regs.hi(), self.regs.lo())
+    }
+
+    /// Fetch the contents of a floating point register
+    pub fn fp_register(&self, reg: Register) -> u32 {
+        self.regs.read_fpr(reg)
+    }
+
+    /// Fetch the floating point control and status register
+    pub fn fcsr(&self) -> u32 {
+        self.regs.fcsr()
+    }
+
+    /// Fetch the contents of a coprocessor 0 register
+    pub fn cp0_register(&self, reg: Register) -> u32 {
+        self.regs.read_cp0(reg)
+    }
+
+    /// Checks if the loaded program has an exception handler in `.ktext`
+    pub fn has_exception_handler(&self) -> bool {
+        !self.syms.segment_range(Segment::KText).is_empty()
+    }
+
+    /// Get the program break, the end of the heap that sbrk moves
+    pub fn heap_break(&self) -> u32 {
+        self.hw.mmu.map().heap_end()
+    }
+
+    /// Get the files the file syscalls work on
+    pub fn file_system(&self) -> &dyn FileSystem {
+        self.files.fs()
+    }
+
+    pub fn file_system_mut(&mut self) -> &mut dyn FileSystem {
+        self.files.fs_mut()
+    }
+
+    /// Replace the files the file syscalls work on, every open file is closed
+    pub fn set_file_system(&mut self, fs: Box<dyn FileSystem>) {
+        self.files = Files::new(fs);
+    }
+
+    /// Get the keyboard and display mapped at `0xFFFF0000`
+    pub fn terminal(&self) -> &Terminal {
+        &self.mem.devices().terminal
+    }
+
+    pub fn terminal_mut(&mut self) -> &mut Terminal {
+        &mut self.mem.devices_mut().terminal
+    }
+
+    /// Get the settings of the bitmap display
+    pub fn display_config(&self) -> DisplayConfig {
+        self.display
+    }
+
+    /// Change the settings of the bitmap display, unlike the pipeline options the program keeps
+    /// running
+    pub fn set_display_config(&mut self, config: DisplayConfig) {
+        self.display = config;
+    }
+
+    /// Reads what the bitmap display is currently showing out of memory
+    pub fn display_frame(&self) -> Frame {
+        Frame::capture(&self.display, &self.mem)
+    }
+
     pub fn read_word(&self, addr: u32) -> Result<u32> {
         self.mem.get(addr)
     }
@@ -44,11 +175,17 @@ impl Machine {
     /// Reset this machine so it can be ran again
     ///
     /// Note that this will not reset the contents of memory or registers for that see
-    /// [`hard_reset`]
+    /// [`hard_reset`]. The memory mapped devices are reset along with the processor and every file
+    /// the program left open is closed
     pub fn reset(&mut self) {
         self.pc = TEXT_BASE;
+        *self.mem.devices_mut() = Devices::default();
+        self.files.close_all();
         self.state = PipelineState::default();
         self.regs = RegisterFile::default();
+        self.hw = Hardware::new(self.config);
+        self.stats = Stats::default();
+        self.timeline = Timeline::default();
     }
 
     /// Fully resets this machine including memory contents and registers
@@ -75,6 +212,11 @@ impl Machine {
         ]
     }
 
+    /// Gets the source code line an address was assembled from
+    pub fn source_line(&self, pc: u32) -> Option<usize> {
+        self.syms.get_line(pc)
+    }
+
     /// Gets the current pipeline stages
     pub fn pipeline(&self) -> &PipelineState {
         &self.state
@@ -91,12 +233,40 @@ impl Machine {
         stack
     }
 
-    pub fn resolve_input(&mut self, input: &str) -> Result<()> {
-        if let Some(syscall) = &self.pending_syscall {
-            resolve_syscall(&mut self.regs, syscall, input)?;
+    /// Completes the pending syscall with the input the user entered
+    ///
+    /// A buffer the program may not write to faults like a store by the syscall instruction
+    ///
+    /// # Returns
+    /// The number of bytes of `input` that were consumed, the rest is left for the next read
+    pub fn resolve_input(&mut self, input: &str) -> Result<usize> {
+        while let Some(syscall) = &self.pending_syscall {
+            let resolved = resolve_syscall(
+                &mut self.regs,
+                &mut self.mem,
+                &mut self.hw.mmu,
+                syscall,
+                input,
+            );
+            let exception = match resolved {
+                Ok(used) => {
+                    self.pending_syscall = None;
+                    return Ok(used);
+                }
+                Err(e) => e.downcast::<Exception>()?,
+            };
+            // without a handler the TLB is refilled and the buffer written again rather than
+            // asking for the input a second time
+            if let Exception::TlbStore(addr) = exception {
+                if !self.has_exception_handler() && self.hw.mmu.refill(addr) {
+                    continue;
+                }
+            }
+            // the input wasn't stored anywhere so none of it is consumed
             self.pending_syscall = None;
+            return self.syscall_fault(exception).map(|_| 0);
         }
-        Ok(())
+        Ok(0)
     }
 
     /// Handle a syscall in the application
@@ -128,19 +298,106 @@ impl Machine {
     pub fn cycle(&mut self) -> Result<()> {
         // do not cycle if we are waiting on a syscall
         if self.pending_syscall.is_none() {
-            let (new_state, syscall) = pipeline::pipe_cycle(
+            let interrupts = self.mem.tick();
+            self.regs.request_interrupts(interrupts);
+            let step = if self.config.single_cycle {
+                pipeline::single_cycle
+            } else {
+                pipeline::pipe_cycle
+            };
+            let (new_state, syscall) = match step(
                 &mut self.pc,
                 &mut self.regs,
                 &mut self.mem,
                 self.state.clone(),
-            )?;
+                self.config,
+                &mut self.hw,
+                &mut self.stats,
+            ) {
+                Ok(cycle) => cycle,
+                Err(e) => return self.handle_fault(e),
+            };
             self.state = new_state;
+            self.timeline.record(&self.state, self.config.single_cycle);
             if let Some(syscall) = syscall {
-                self.pending_syscall = Some(syscall);
+                // file syscalls are carried out right away apart from reading the console
+                let pending = file_syscall(
+                    &mut self.files,
+                    &mut self.regs,
+                    &mut self.mem,
+                    &mut self.hw.mmu,
+                    syscall,
+                );
+                match pending {
+                    Ok(pending) => self.pending_syscall = pending,
+                    Err(exception) => return self.syscall_fault(exception),
+                }
             }
         }
         Ok(())
     }
+
+    /// Takes the exception an instruction raised, vectoring to the handler if there is one
+    ///
+    /// # Returns
+    /// The error stopping the program if the fault can't be handled
+    fn handle_fault(&mut self, e: anyhow::Error) -> Result<()> {
+        if let Some(fault) = e.downcast_ref::<Fault>().copied() {
+            // nothing younger than the faulting instruction may execute
+            self.state = PipelineState::default();
+
+            let delay_slot = self.config.delay_slot
+                && self
+                    .mem
+                    .get(fault.pc.wrapping_sub(4))
+                    .is_ok_and(has_delay_slot);
+
+            // without a handler to refill the TLB it is refilled from the page table and the
+            // faulting instruction is fetched again, from its branch if it is in a delay slot
+            if let Exception::TlbLoad(addr) | Exception::TlbStore(addr) = fault.exception {
+                if !self.has_exception_handler() && self.hw.mmu.refill(addr) {
+                    self.pc = if delay_slot {
+                        fault.pc.wrapping_sub(4)
+                    } else {
+                        fault.pc
+                    };
+                    self.timeline.record(&self.state, self.config.single_cycle);
+                    return Ok(());
+                }
+            }
+
+            // vector to the exception handler, an exception raised while already handling one
+            // can't be recovered from
+            if self.has_exception_handler() && !self.regs.in_exception() {
+                self.regs
+                    .enter_exception(fault.exception, fault.pc, delay_slot);
+                // the entry the page table holds for the page is loaded ready for the handler to
+                // write it to the TLB
+                match fault.exception.bad_address() {
+                    Some(addr) if fault.exception.is_tlb() => {
+                        let (entry_hi, entry_lo) = self.hw.mmu.page_entry(addr);
+                        self.regs.write_cp0(ENTRY_HI, entry_hi);
+                        self.regs.write_cp0(ENTRY_LO, entry_lo);
+                    }
+                    _ => {}
+                }
+                self.pc = KTEXT_BASE;
+                self.timeline.record(&self.state, self.config.single_cycle);
+                return Ok(());
+            }
+            if let Some(line) = self.syms.get_line(fault.pc) {
+                return Err(e.context(format!("On line {}", line + 1)));
+            }
+        }
+        Err(e)
+    }
+
+    /// Raises an exception on the syscall instruction that has just left the pipeline
+    fn syscall_fault(&mut self, exception: Exception) -> Result<()> {
+        let out = &self.state.pipe_out;
+        let fault = Fault::new(exception, out.pc, out.instruction);
+        self.handle_fault(fault.into())
+    }
 }
 
 /// Method that create a memory instance from a script file
@@ -148,13 +405,24 @@ pub fn assembler(script: &str) -> Result<(Memory, LabelTable)> {
     // parse assembly
     let lines = parser::parse_string(script)?;
     let labels = compute_labels(&lines);
+    for seg in Segment::ALL {
+        let end = labels.segment_range(seg).end;
+        if end > seg.limit() {
+            bail!(
+                "The {} segment ends at 0x{:08X} and runs past 0x{:08X}",
+                seg.name(),
+                end,
+                seg.limit()
+            );
+        }
+    }
 
     // for each line in the parsed assembly assemble that line and add the result to a vec
     let mut memory = Memory::new();
     let mut segments = Segments::default();
     // current segement pc
     let mut pc = segments.switch(Segment::Text);
-    for line in &lines {
+    for (_, line) in &lines {
         match line {
             Line::Instruction(ins) => {
                 for word in ins {
@@ -172,8 +440,990 @@ pub fn assembler(script: &str) -> Result<(Memory, LabelTable)> {
     }
     // insert guard instruction that causes the program to crash if it is encountered
     pc = segments.switch(Segment::Text);
-    *memory.get_mut(*pc)? = 0x3402DEAD;
+    *memory.get_mut(*pc)? = 0x2402DEAD;
     *memory.get_mut(*pc + 4)? = 0xC;
 
     Ok((memory, labels))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{predictor::Predictor, S0, S1, S2, S3, S4, S5, S6, S7, T1};
+
+    /// Assembles and runs a script until it quits
+    fn run(script: &str) -> Machine {
+        run_with(script, PipelineConfig::default())
+    }
+
+    /// Assembles and runs a script on a pipeline with the given options until it quits
+    fn run_with(script: &str, config: PipelineConfig) -> Machine {
+        let (mem, syms) = assembler(script).unwrap();
+        let mut machine = Machine::default();
+        machine.set_config(config);
+        machine.reset();
+        machine.flash(mem, syms);
+        for _ in 0..10_000 {
+            machine.cycle().unwrap();
+            let mut quit = false;
+            machine.handle_syscall(|syscall| {
+                quit = matches!(syscall, Syscall::Quit);
+                ControlFlow::Break(())
+            });
+            if quit {
+                return machine;
+            }
+        }
+        panic!("program did not quit");
+    }
+
+    #[test]
+    fn jal_links_and_jr_returns() {
+        let machine = run("
+main:
+    jal add_one
+    addi $s1, $s1, 10
+    jal add_one
+    addi $s1, $s1, 10
+    li $v0, 10
+    syscall
+add_one:
+    addi $s0, $s0, 1
+    jr $ra
+");
+        assert_eq!(machine.register(S0), 2);
+        assert_eq!(machine.register(S1), 20);
+    }
+
+    #[test]
+    fn jalr_links_to_ra_or_rd() {
+        let machine = run("
+main:
+    la $t0, add_one
+    jalr $t0
+    addi $s1, $s1, 10
+    la $t0, add_two
+    jalr $s2, $t0
+    addi $s1, $s1, 10
+    li $v0, 10
+    syscall
+add_one:
+    addi $s0, $s0, 1
+    jr $ra
+add_two:
+    addi $s0, $s0, 2
+    jr $s2
+");
+        assert_eq!(machine.register(S0), 3);
+        assert_eq!(machine.register(S1), 20);
+        assert_ne!(machine.register(S2), 0);
+    }
+
+    #[test]
+    fn mult_and_div_write_hi_lo() {
+        let machine = run("
+main:
+    li $t0, -6
+    li $t1, 4
+    mult $t0, $t1
+    mflo $s0
+    mfhi $s1
+    multu $t0, $t1
+    mfhi $s2
+    div $t0, $t1
+    mflo $s3
+    mfhi $s4
+    divu $t0, $t1
+    mflo $s5
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), -24i32 as u32);
+        assert_eq!(machine.register(S1), u32::MAX);
+        assert_eq!(machine.register(S2), 3);
+        assert_eq!(machine.register(S3), -1i32 as u32);
+        assert_eq!(machine.register(S4), -2i32 as u32);
+        assert_eq!(machine.register(S5), (-6i32 as u32) / 4);
+    }
+
+    #[test]
+    fn divide_by_zero_leaves_hi_lo() {
+        let machine = run("
+main:
+    li $t0, 7
+    mthi $t0
+    li $t0, 9
+    mtlo $t0
+    div $t0, $zero
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.hi_lo(), (7, 9));
+    }
+
+    #[test]
+    fn mul_and_rem_pseudo_instructions() {
+        let machine = run("
+main:
+    li $t0, 17
+    li $t1, 5
+    mul $s0, $t0, $t1
+    rem $s1, $t0, $t1
+    li $t2, -17
+    remu $s2, $t2, $t1
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 85);
+        assert_eq!(machine.register(S1), 2);
+        assert_eq!(machine.register(S2), (-17i32 as u32) % 5);
+    }
+
+    #[test]
+    fn byte_and_half_loads_extend() {
+        let machine = run("
+main:
+    li $t0, 0x80817FFF
+    sw $t0, -4($sp)
+    lb $s0, -4($sp)
+    lbu $s1, -4($sp)
+    lh $s2, -2($sp)
+    lhu $s3, -2($sp)
+    lb $s4, -1($sp)
+    lh $s5, -4($sp)
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 0xFFFFFFFF);
+        assert_eq!(machine.register(S1), 0xFF);
+        assert_eq!(machine.register(S2), 0xFFFF8081);
+        assert_eq!(machine.register(S3), 0x8081);
+        assert_eq!(machine.register(S4), 0xFFFFFF80);
+        assert_eq!(machine.register(S5), 0x7FFF);
+    }
+
+    #[test]
+    fn byte_and_half_stores_keep_neighbours() {
+        let machine = run("
+main:
+    li $t0, -1
+    sw $t0, -4($sp)
+    sb $zero, -3($sp)
+    li $t1, 0x1234
+    sh $t1, -2($sp)
+    lw $s0, -4($sp)
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 0x123400FF);
+    }
+
+    #[test]
+    fn set_on_less_than_signed_and_unsigned() {
+        let machine = run("
+main:
+    li $t0, -1
+    li $t1, 1
+    slt $s0, $t0, $t1
+    sltu $s1, $t0, $t1
+    slti $s2, $t0, 0
+    sltiu $s3, $t1, -1
+    sltiu $s4, $t0, 5
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 1);
+        assert_eq!(machine.register(S1), 0);
+        assert_eq!(machine.register(S2), 1);
+        // the immediate is sign extended before the unsigned compare
+        assert_eq!(machine.register(S3), 1);
+        assert_eq!(machine.register(S4), 0);
+    }
+
+    #[test]
+    fn logical_immediates_zero_extend() {
+        let machine = run("
+main:
+    li $s0, 0x8081F0FF
+    li $t0, -1
+    andi $s1, $t0, 0x8000
+    xori $s2, $t0, 0xFFFF
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 0x8081F0FF);
+        assert_eq!(machine.register(S1), 0x8000);
+        assert_eq!(machine.register(S2), 0xFFFF0000);
+    }
+
+    #[test]
+    fn variable_shifts_and_unsigned_arithmetic() {
+        let machine = run("
+main:
+    li $t0, 0x80000010
+    li $t1, 36
+    sllv $s0, $t0, $t1
+    srlv $s1, $t0, $t1
+    srav $s2, $t0, $t1
+    li $t2, 0x7FFFFFFF
+    addiu $s3, $t2, 1
+    addu $s4, $t2, $t2
+    subu $s5, $zero, $t1
+    li $v0, 10
+    syscall
+");
+        // only the low five bits of the shift amount are used
+        assert_eq!(machine.register(S0), 0x00000100);
+        assert_eq!(machine.register(S1), 0x08000001);
+        assert_eq!(machine.register(S2), 0xF8000001);
+        assert_eq!(machine.register(S3), 0x80000000);
+        assert_eq!(machine.register(S4), 0xFFFFFFFE);
+        assert_eq!(machine.register(S5), -36i32 as u32);
+    }
+
+    #[test]
+    fn zero_branches() {
+        // every branch that falls through sets its bit in $s0
+        let machine = run("
+main:
+    li $t0, -1
+    bltz $t0, l1
+    ori $s0, $s0, 0x1
+l1: bgez $t0, l2
+    ori $s0, $s0, 0x2
+l2: blez $zero, l3
+    ori $s0, $s0, 0x4
+l3: bgtz $zero, l4
+    ori $s0, $s0, 0x8
+l4: beqz $zero, l5
+    ori $s0, $s0, 0x10
+l5: bnez $zero, l6
+    ori $s0, $s0, 0x20
+l6: b l7
+    ori $s0, $s0, 0x40
+l7: li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 0x2 | 0x8 | 0x20);
+    }
+
+    #[test]
+    fn compare_branches_signed_and_unsigned() {
+        let machine = run("
+main:
+    li $t0, -1
+    li $t1, 1
+    blt $t0, $t1, l1
+    ori $s0, $s0, 0x1
+l1: bltu $t0, $t1, l2
+    ori $s0, $s0, 0x2
+l2: bgt $t0, $t1, l3
+    ori $s0, $s0, 0x4
+l3: bgtu $t0, $t1, l4
+    ori $s0, $s0, 0x8
+l4: ble $t1, $t1, l5
+    ori $s0, $s0, 0x10
+l5: bleu $t0, $t1, l6
+    ori $s0, $s0, 0x20
+l6: bge $t1, $t1, l7
+    ori $s0, $s0, 0x40
+l7: bgeu $t1, $t0, l8
+    ori $s0, $s0, 0x80
+l8: li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 0x2 | 0x4 | 0x20 | 0x80);
+    }
+
+    #[test]
+    fn branch_and_link_returns_after_the_branch() {
+        let machine = run("
+main:
+    li $t0, -1
+    bgezal $t0, func
+    bltzal $t0, func
+    addi $s1, $s1, 10
+    bal func
+    addi $s1, $s1, 10
+    li $v0, 10
+    syscall
+func:
+    addi $s0, $s0, 1
+    jr $ra
+");
+        assert_eq!(machine.register(S0), 2);
+        assert_eq!(machine.register(S1), 20);
+    }
+
+    #[test]
+    fn single_precision_arithmetic_and_compare() {
+        let machine = run("
+.data
+x: .float 1.5
+.text
+main:
+    la $t0, x
+    l.s $f0, 0($t0)
+    add.s $f2, $f0, $f0
+    mul.s $f4, $f0, $f0
+    mfc1 $s0, $f2
+    mfc1 $s1, $f4
+    cvt.w.s $f6, $f2
+    mfc1 $s2, $f6
+    c.lt.s $f0, $f2
+    bc1t taken
+    li $s3, 1
+taken:
+    c.eq.s $f0, $f2
+    bc1f not_equal
+    li $s4, 1
+not_equal:
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S0), 3.0f32.to_bits());
+        assert_eq!(machine.register(S1), 2.25f32.to_bits());
+        assert_eq!(machine.register(S2), 3);
+        assert_eq!(machine.register(S3), 0);
+        assert_eq!(machine.register(S4), 0);
+    }
+
+    #[test]
+    fn double_precision_loads_stores_and_conversion() {
+        let machine = run("
+.data
+x: .double 2.25, 0.0
+.text
+main:
+    la $t0, x
+    l.d $f0, 0($t0)
+    add.d $f2, $f0, $f0
+    s.d $f2, 8($t0)
+    lw $s0, 8($t0)
+    lw $s1, 12($t0)
+    cvt.s.d $f4, $f2
+    mfc1 $s2, $f4
+    li $v0, 10
+    syscall
+");
+        let bits = 4.5f64.to_bits();
+        assert_eq!(machine.register(S0), bits as u32);
+        assert_eq!(machine.register(S1), (bits >> 32) as u32);
+        assert_eq!(machine.register(S2), 4.5f32.to_bits());
+    }
+
+    #[test]
+    fn float_and_double_literals_are_aligned() {
+        let (mem, syms) = assembler(
+            "
+.data
+a: .byte 1
+f: .float 1.5
+b: .byte 2
+d: .double 2.25, -1.0
+g: .float 3.0
+",
+        )
+        .unwrap();
+        let label = |name| syms.get_label(name).unwrap();
+        assert_eq!(label("f") - label("a"), 4);
+        assert_eq!(label("d") % 8, 0);
+        assert_eq!(label("d") - label("b"), 8 - label("b") % 8);
+        assert_eq!(label("g"), label("d") + 16);
+
+        assert_eq!(mem.get(label("f")).unwrap(), 1.5f32.to_bits());
+        let double =
+            |addr| mem.get(addr).unwrap() as u64 | (mem.get(addr + 4).unwrap() as u64) << 32;
+        assert_eq!(double(label("d")), 2.25f64.to_bits());
+        assert_eq!(double(label("d") + 8), (-1.0f64).to_bits());
+        assert_eq!(mem.get(label("g")).unwrap(), 3.0f32.to_bits());
+    }
+
+    /// Kernel handler that skips the instruction that raised the exception and counts how many
+    /// were raised in $s7, the cause code is left in $s6 and the bad address in $s5
+    const SKIP_HANDLER: &str = "
+.ktext
+    addi $s7, $s7, 1
+    mfc0 $k0, $13
+    srl $s6, $k0, 2
+    andi $s6, $s6, 0x1F
+    mfc0 $s5, $8
+    mfc0 $k0, $14
+    addiu $k0, $k0, 4
+    mtc0 $k0, $14
+    eret
+";
+
+    #[test]
+    fn exceptions_vector_to_the_handler_and_resume() {
+        let machine = run(&format!(
+            "
+main:
+    li $t0, 0x7FFFFFFF
+    addi $t1, $t0, 1
+    li $s0, 1
+    lw $t2, 2($zero)
+    li $s1, 2
+    li $v0, 10
+    syscall
+{}",
+            SKIP_HANDLER
+        ));
+        assert_eq!(machine.register(S0), 1);
+        assert_eq!(machine.register(S1), 2);
+        // the overflowing add never writes its destination
+        assert_eq!(machine.register(T1), 0);
+        assert_eq!(machine.register(S7), 2);
+        assert_eq!(machine.register(S6), 4);
+        assert_eq!(machine.register(S5), 2);
+    }
+
+    #[test]
+    fn reserved_instruction_raises_exception() {
+        let machine = run(&format!(
+            "
+main:
+    .word 0xFC000000
+    add.d $f1, $f2, $f4
+    li $v0, 10
+    syscall
+{}",
+            SKIP_HANDLER
+        ));
+        assert_eq!(machine.register(S7), 2);
+        assert_eq!(machine.register(S6), 10);
+    }
+
+    #[test]
+    fn exception_without_handler_is_an_error() {
+        let (mem, syms) = assembler("main:\n    lw $t0, 1($zero)\n").unwrap();
+        let mut machine = Machine::default();
+        machine.reset();
+        machine.flash(mem, syms);
+        let err = (0..100).find_map(|_| machine.cycle().err()).unwrap();
+        assert!(format!("{:#}", err).contains("On line 2"));
+    }
+
+    #[test]
+    fn fetch_past_the_end_of_memory_faults() {
+        // the pc wraps instead of overflowing, the fetch from the unmapped address is what faults
+        let machine = run("
+main:
+    li $t0, 0xFFFFFFFC
+    jr $t0
+.ktext
+    mfc0 $k0, $13
+    srl $s6, $k0, 2
+    andi $s6, $s6, 0x1F
+    mfc0 $s5, $14
+    li $v0, 10
+    syscall
+");
+        assert_eq!(machine.register(S6), 4);
+        assert_eq!(machine.register(S5), 0xFFFFFFFC);
+    }
+
+    fn delay_slot() -> PipelineConfig {
+        PipelineConfig {
+            delay_slot: true,
+            ..Default::default()
+        }
+    }
+
+    const SQUASH: &str = "
+main:
+    beq $zero, $zero, target
+    addi $s0, $s0, 1
+    addi $s0, $s0, 1
+target:
+    j done
+    addi $s1, $s1, 1
+    addi $s1, $s1, 1
+done:
+    li $v0, 10
+    syscall
+";
+
+    #[test]
+    fn taken_branches_squash_the_wrong_path() {
+        let machine = run(SQUASH);
+        assert_eq!(machine.register(S0), 0);
+        assert_eq!(machine.register(S1), 0);
+    }
+
+    #[test]
+    fn delay_slot_runs_the_next_instruction() {
+        let machine = run_with(SQUASH, delay_slot());
+        assert_eq!(machine.register(S0), 1);
+        assert_eq!(machine.register(S1), 1);
+    }
+
+    /// Handler that records Cause in $s6 and EPC in $s5 and quits
+    const QUIT_HANDLER: &str = "
+.ktext
+    mfc0 $s6, $13
+    mfc0 $s5, $14
+    li $v0, 10
+    syscall
+";
+
+    #[test]
+    fn exception_in_delay_slot_points_epc_at_the_branch() {
+        let script = format!(
+            "
+main:
+    nop
+    j main
+    lw $t0, 1($zero)
+{}",
+            QUIT_HANDLER
+        );
+        let machine = run_with(&script, delay_slot());
+        assert_eq!(machine.register(S5), TEXT_BASE + 4);
+        assert_eq!(machine.register(S6) >> 31, 1);
+        assert_eq!((machine.register(S6) >> 2) & 0x1F, 4);
+
+        // anywhere else EPC is the faulting instruction
+        let machine = run_with(
+            &format!(
+                "
+main:
+    nop
+    nop
+    lw $t0, 1($zero)
+{}",
+                QUIT_HANDLER
+            ),
+            delay_slot(),
+        );
+        assert_eq!(machine.register(S5), TEXT_BASE + 8);
+        assert_eq!(machine.register(S6) >> 31, 0);
+    }
+
+    /// Every configuration of the pipeline that doesn't change what a program computes
+    fn configurations() -> Vec<PipelineConfig> {
+        let mut configs = vec![PipelineConfig {
+            single_cycle: true,
+            ..Default::default()
+        }];
+        for forwarding in [true, false] {
+            for branch_stage in pipeline::BranchStage::ALL {
+                configs.push(PipelineConfig {
+                    forwarding,
+                    branch_stage,
+                    ..Default::default()
+                });
+            }
+        }
+        configs
+    }
+
+    #[test]
+    fn every_configuration_computes_the_same_result() {
+        let script = "
+.data
+vals: .word 3, 4, 5
+.text
+main:
+    la $t0, vals
+    lw $t1, 0($t0)
+    add $s0, $t1, $t1
+    lw $t2, 4($t0)
+    lw $t3, 8($t0)
+    li $t4, 3
+loop:
+    add $s1, $s1, $t2
+    addi $t4, $t4, -1
+    bne $t4, $zero, loop
+    jal double
+    add $s2, $v0, $zero
+    sw $s2, 0($t0)
+    lw $s3, 0($t0)
+    li $v0, 10
+    syscall
+double:
+    add $v0, $t3, $t3
+    jr $ra
+";
+        for config in configurations() {
+            let machine = run_with(script, config);
+            let regs = [S0, S1, S2, S3].map(|reg| machine.register(reg));
+            assert_eq!(regs, [6, 12, 10, 10], "{:?}", config);
+
+            let machine = run_with(SQUASH, config);
+            assert_eq!(machine.register(S0), 0, "{:?}", config);
+            assert_eq!(machine.register(S1), 0, "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn single_cycle_runs_the_delay_slot() {
+        let machine = run_with(
+            SQUASH,
+            PipelineConfig {
+                single_cycle: true,
+                delay_slot: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(machine.register(S0), 1);
+        assert_eq!(machine.register(S1), 1);
+    }
+
+    const LOOP: &str = "
+main:
+    li $t0, 10
+loop:
+    addi $s0, $s0, 3
+    addi $t0, $t0, -1
+    bnez $t0, loop
+    li $v0, 10
+    syscall
+";
+
+    #[test]
+    fn predictors_do_not_change_results() {
+        for predictor in Predictor::ALL {
+            for btb_entries in [0, 8] {
+                let config = PipelineConfig {
+                    predictor,
+                    btb_entries,
+                    ..Default::default()
+                };
+                let machine = run_with(LOOP, config);
+                assert_eq!(machine.register(S0), 30, "{:?}", config);
+            }
+        }
+    }
+
+    #[test]
+    fn two_bit_predictor_learns_a_loop() {
+        let config = PipelineConfig {
+            predictor: Predictor::TwoBit,
+            ..Default::default()
+        };
+        let machine = run_with(LOOP, config);
+        let stats = machine.branch_stats();
+        assert_eq!(stats.len(), 1);
+        let (_, stats) = stats[0];
+        assert_eq!((stats.executed, stats.taken), (10, 9));
+        // it mispredicts while warming up and when the loop exits
+        assert_eq!(stats.correct, 7);
+    }
+
+    #[test]
+    fn stats_count_the_committed_path() {
+        let machine = run(LOOP);
+        let stats = machine.stats();
+        assert_eq!(stats.instructions, 33);
+        assert_eq!((stats.branches_taken, stats.branches_not_taken), (9, 1));
+        assert_eq!(stats.mix.get("addi"), Some(&20));
+        assert!(stats.cycles > stats.instructions);
+    }
+
+    fn mmu() -> PipelineConfig {
+        PipelineConfig {
+            mmu: true,
+            ..Default::default()
+        }
+    }
+
+    const SUM: &str = "
+.data
+values: .word 1, 2, 3, 4
+.text
+main:
+    la $t0, values
+    li $t1, 4
+loop:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    sw $s0, 0($t0)
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bnez $t1, loop
+    li $v0, 10
+    syscall
+";
+
+    #[test]
+    fn tlb_is_refilled_without_a_handler() {
+        let machine = run_with(SUM, mmu());
+        assert_eq!(machine.register(S0), 10);
+        let stats = machine.mmu().stats();
+        // the text and data pages are refilled once each, instructions fetched behind a miss
+        // miss as well before they are squashed
+        assert_eq!(stats.refills, 2);
+        assert!(stats.misses >= 2);
+    }
+
+    #[test]
+    fn tlb_refill_handler() {
+        let script = format!(
+            "{}\n.ktext\n    addi $s7, $s7, 1\n    tlbwr\n    eret\n",
+            SUM
+        );
+        let machine = run_with(&script, mmu());
+        assert_eq!(machine.register(S0), 10);
+        assert_eq!(machine.register(S7), 2);
+    }
+
+    #[test]
+    fn store_to_text_with_mmu_is_an_error() {
+        let (mem, syms) = assembler("main:\n    la $t0, main\n    sw $zero, 0($t0)\n").unwrap();
+        let mut machine = Machine::default();
+        machine.set_config(mmu());
+        machine.reset();
+        machine.flash(mem, syms);
+        let err = (0..100).find_map(|_| machine.cycle().err()).unwrap();
+        assert!(format!("{:#}", err).contains("On line 3"), "{:#}", err);
+    }
+
+    #[test]
+    fn tlb_refill_in_delay_slot_restarts_from_the_branch() {
+        let config = PipelineConfig {
+            delay_slot: true,
+            ..mmu()
+        };
+        let machine = run_with(
+            "
+.data
+x: .word 0
+.text
+main:
+    la $t0, x
+    li $t1, 5
+    j done
+    sw $t1, 0($t0)
+    li $s1, 1
+done:
+    lw $s0, 0($t0)
+    li $v0, 10
+    syscall
+",
+            config,
+        );
+        assert_eq!(machine.register(S0), 5);
+        assert_eq!(machine.register(S1), 0);
+    }
+
+    #[test]
+    fn protection_faults_vector_to_the_handler() {
+        let machine = run(&format!(
+            "
+main:
+    la $t0, main
+    sw $zero, 0($t0)
+    lw $s0, 0($t0)
+    li $v0, 10
+    syscall
+{}",
+            SKIP_HANDLER
+        ));
+        // the store is refused but .text can still be read
+        assert_ne!(machine.register(S0), 0);
+        assert_eq!(machine.register(S7), 1);
+        assert_eq!(machine.register(S6), 5);
+        assert_eq!(machine.register(S5), TEXT_BASE);
+
+        let machine = run(&format!(
+            "
+main:
+    lw $s0, 16($zero)
+    li $v0, 10
+    syscall
+{}",
+            SKIP_HANDLER
+        ));
+        assert_eq!(machine.register(S7), 1);
+        assert_eq!(machine.register(S6), 4);
+        assert_eq!(machine.register(S5), 16);
+    }
+
+    #[test]
+    fn protection_fault_without_handler_is_an_error() {
+        let (mem, syms) = assembler("main:\n    li $t0, 0x10\n    lw $t1, 0($t0)\n").unwrap();
+        let mut machine = Machine::default();
+        machine.reset();
+        machine.flash(mem, syms);
+        let err = (0..100).find_map(|_| machine.cycle().err()).unwrap();
+        let err = format!("{:#}", err);
+        assert!(err.contains("On line 3"), "{}", err);
+        assert!(err.contains("unmapped address 0x00000010"), "{}", err);
+    }
+
+    /// Runs a script that reads `input` through syscalls and returns what it printed
+    fn run_with_input(script: &str, mut input: &str) -> (Machine, String) {
+        let (mem, syms) = assembler(script).unwrap();
+        let mut machine = Machine::default();
+        machine.reset();
+        machine.flash(mem, syms);
+        let mut output = String::new();
+        for _ in 0..10_000 {
+            machine.cycle().unwrap();
+            let mut quit = false;
+            machine.handle_syscall(|syscall| match syscall {
+                Syscall::Print(out) => {
+                    output.push_str(out);
+                    ControlFlow::Break(())
+                }
+                Syscall::Quit => {
+                    quit = true;
+                    ControlFlow::Break(())
+                }
+                _ => ControlFlow::Continue(()),
+            });
+            if quit {
+                return (machine, output);
+            }
+            if machine.pending_syscall() {
+                let used = machine.resolve_input(input).unwrap();
+                input = &input[used..];
+            }
+        }
+        panic!("program did not quit");
+    }
+
+    #[test]
+    fn input_syscalls_leave_the_rest_of_the_line() {
+        let (_, output) = run_with_input(
+            "
+.data
+buf: .space 16
+.text
+main:
+    li $v0, 12
+    syscall
+    move $a0, $v0
+    li $v0, 11
+    syscall
+    la $a0, buf
+    li $a1, 16
+    li $v0, 8
+    syscall
+    li $v0, 4
+    syscall
+    li $v0, 5
+    syscall
+    move $a0, $v0
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+",
+            "abc\n42 \n",
+        );
+        assert_eq!(output, "abc\n42");
+    }
+
+    #[test]
+    fn read_string_into_code_vectors_to_the_handler() {
+        let (machine, _) = run_with_input(
+            &format!(
+                "
+main:
+    la $a0, main
+    li $a1, 8
+    li $v0, 8
+    syscall
+    lw $s0, 0($a0)
+    li $v0, 10
+    syscall
+{}",
+                SKIP_HANDLER
+            ),
+            "hello\n",
+        );
+        assert_eq!(machine.register(S7), 1);
+        assert_eq!(machine.register(S6), 5);
+        assert_eq!(machine.register(S5), TEXT_BASE);
+        // the buffer is left untouched
+        assert_ne!(machine.register(S0) & 0xFF, b'h' as u32);
+    }
+
+    #[test]
+    fn file_syscalls_keep_files_in_memory() {
+        let (_, output) = run_with_input(
+            "
+.data
+path: .asciiz \"out.txt\"
+text: .ascii \"hello\"
+buf: .space 8
+.text
+main:
+    la $a0, path
+    li $a1, 1
+    li $v0, 13
+    syscall
+    move $a0, $v0
+    la $a1, text
+    li $a2, 5
+    li $v0, 15
+    syscall
+    li $v0, 16
+    syscall
+    la $a0, path
+    li $a1, 0
+    li $v0, 13
+    syscall
+    move $a0, $v0
+    la $a1, buf
+    li $a2, 8
+    li $v0, 14
+    syscall
+    move $a0, $v0
+    li $v0, 1
+    syscall
+    la $a0, buf
+    li $v0, 4
+    syscall
+    li $v0, 10
+    syscall
+",
+            "",
+        );
+        assert_eq!(output, "5hello");
+    }
+
+    #[test]
+    fn syscall_strings_and_buffers_fault_like_loads() {
+        let machine = run(&format!(
+            "
+main:
+    li $a0, 16
+    li $v0, 4
+    syscall
+    li $a0, 1
+    li $a1, 32
+    li $a2, 4
+    li $v0, 15
+    syscall
+    li $v0, 10
+    syscall
+{}",
+            SKIP_HANDLER
+        ));
+        assert_eq!(machine.register(S7), 2);
+        assert_eq!(machine.register(S6), 4);
+        assert_eq!(machine.register(S5), 32);
+    }
+
+    #[test]
+    fn syscall_strings_are_translated_by_the_mmu() {
+        let machine = run_with(
+            "
+.data
+msg: .asciiz \"hi\"
+.text
+main:
+    la $a0, msg
+    li $v0, 4
+    syscall
+    li $v0, 10
+    syscall
+",
+            mmu(),
+        );
+        assert!(machine.mmu().stats().refills >= 2);
+    }
+}
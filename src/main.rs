@@ -1,8 +1,12 @@
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    // if let Err(e) = run() {
-    //     eprintln!("ERROR: {}", e);
-    // }
+    use simulator::cli;
+
+    // run headless if a subcommand was given
+    let matches = cli::app().get_matches();
+    if let Some(code) = cli::run_subcommand(&matches) {
+        std::process::exit(code);
+    }
 
     use eframe::egui::Visuals;
     let app = simulator::App::default();
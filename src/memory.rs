@@ -2,15 +2,49 @@ use std::collections::HashMap;
 
 use anyhow::{bail, Result};
 
+use crate::{devices::Devices, parser::model::MMIO_BASE};
+
+/// Size of a memory access
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Half,
+    #[default]
+    Word,
+}
+
+impl Width {
+    /// Number of bytes accessed
+    pub fn bytes(&self) -> u32 {
+        match self {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
+        }
+    }
+
+    /// Mask covering the bits of an access of this width
+    fn mask(&self) -> u32 {
+        match self {
+            Width::Byte => 0xFF,
+            Width::Half => 0xFFFF,
+            Width::Word => 0xFFFFFFFF,
+        }
+    }
+}
+
 /// Handles memory
 ///
 /// Memory is allocated in pages of words
 ///
 /// Unaligned memory access is undefined
+///
+/// Loads and stores from `0xFFFF0000` up go to the memory mapped devices instead
 #[derive(Debug)]
 pub struct Memory {
     data: HashMap<u32, Vec<u32>>,
     page_size: usize,
+    devices: Devices,
 }
 
 impl Default for Memory {
@@ -25,9 +59,26 @@ impl Memory {
         Self {
             data: HashMap::new(),
             page_size: 1024,
+            devices: Devices::default(),
         }
     }
 
+    pub fn devices(&self) -> &Devices {
+        &self.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut Devices {
+        &mut self.devices
+    }
+
+    /// Advances the memory mapped devices by a cycle
+    ///
+    /// # Returns
+    /// The Cause bits of the interrupts the devices are requesting
+    pub fn tick(&mut self) -> u32 {
+        self.devices.tick()
+    }
+
     /// Sets a single byte
     pub fn set_byte(&mut self, address: u32, val: u8) -> Result<()> {
         let aligned_address = address / 4;
@@ -54,7 +105,7 @@ impl Memory {
     }
 
     /// Get a single byte
-    pub fn get_byte(&mut self, address: u32) -> Result<u8> {
+    pub fn get_byte(&self, address: u32) -> Result<u8> {
         let aligned_address = address / 4;
         let align_offset = address % 4;
         let page_num = aligned_address / self.page_size as u32;
@@ -96,7 +147,7 @@ impl Memory {
         let page_num = aligned_address / self.page_size as u32;
         let page_offset = aligned_address - (self.page_size as u32 * page_num);
 
-        if address % 4 != 0 {
+        if !address.is_multiple_of(4) {
             bail!(format!(
                 "Unaligned memory access: {address:08X} expected to be aligned to 4 bytes"
             ));
@@ -105,4 +156,45 @@ impl Memory {
         let page = self.data.entry(page_num).or_insert(vec![0; self.page_size]);
         Ok(&mut page[page_offset as usize])
     }
+
+    /// Reads a value of the given width, the result is zero extended
+    ///
+    /// The address must be aligned to the width of the access. Memory that has never been written
+    /// reads as zero, reading a device register may change the state of the device
+    pub fn load(&mut self, address: u32, width: Width) -> Result<u32> {
+        if !address.is_multiple_of(width.bytes()) {
+            bail!(format!(
+                "Unaligned memory access: {address:08X} expected to be aligned to {} bytes",
+                width.bytes()
+            ));
+        }
+        let shift = (address % 4) * 8;
+        let word = if address >= MMIO_BASE {
+            self.devices.read(address & !3)
+        } else {
+            self.get(address & !3).unwrap_or(0)
+        };
+        Ok((word >> shift) & width.mask())
+    }
+
+    /// Writes the low bytes of `val` leaving the rest of the word untouched
+    ///
+    /// The address must be aligned to the width of the access
+    pub fn store(&mut self, address: u32, width: Width, val: u32) -> Result<()> {
+        if !address.is_multiple_of(width.bytes()) {
+            bail!(format!(
+                "Unaligned memory access: {address:08X} expected to be aligned to {} bytes",
+                width.bytes()
+            ));
+        }
+        let shift = (address % 4) * 8;
+        let mask = width.mask() << shift;
+        if address >= MMIO_BASE {
+            self.devices.write(address & !3, (val << shift) & mask);
+            return Ok(());
+        }
+        let word = self.get_mut(address & !3)?;
+        *word = (*word & !mask) | ((val << shift) & mask);
+        Ok(())
+    }
 }
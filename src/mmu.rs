@@ -0,0 +1,433 @@
+//! Memory management unit that checks every access against the memory map and optionally
+//! translates addresses through a software managed TLB
+//!
+//! User addresses are translated a page at a time through the TLB, kernel addresses from
+//! `0x80000000` up are never mapped. A miss raises an exception and it is up to the handler in
+//! `.ktext` to write the missing entry with `tlbwr` or `tlbwi`. Programs without a handler have
+//! their TLB refilled straight from the page table as if by a minimal handler.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    parser::model::{DATA_BASE, KERNEL_BASE, TEXT_BASE},
+    protection::{Access, MemoryMap},
+    Exception, PipelineConfig, Register, RegisterFile, ENTRY_HI, ENTRY_LO, INDEX,
+};
+
+/// Bytes in a page, the same as the pages [`Memory`](crate::Memory) is organised into
+pub const PAGE_SIZE: u32 = 4096;
+
+/// EntryLo bit set if stores to the page are allowed, called the dirty bit by MIPS
+const ENTRY_LO_DIRTY: u32 = 0x400;
+/// EntryLo bit set if the entry maps its page
+const ENTRY_LO_VALID: u32 = 0x200;
+/// Index bit set by `tlbp` when no entry matches
+const INDEX_PROBE_FAILED: u32 = 0x8000_0000;
+
+/// TLB instructions executed in writeback
+pub mod tlb_ctrl {
+    pub const TLB_NONE: u8 = 0;
+    /// `tlbr` loads EntryHi and EntryLo from the entry selected by Index
+    pub const TLB_READ: u8 = 1;
+    /// `tlbwi` writes EntryHi and EntryLo to the entry selected by Index
+    pub const TLB_WRITE_INDEXED: u8 = 2;
+    /// `tlbwr` writes EntryHi and EntryLo to a random entry
+    pub const TLB_WRITE_RANDOM: u8 = 3;
+    /// `tlbp` sets Index to the entry that matches EntryHi
+    pub const TLB_PROBE: u8 = 4;
+}
+use tlb_ctrl::*;
+
+/// Coprocessor 0 registers written by `tlbr` and `tlbp`
+pub(crate) const TLB_REGISTERS: [Register; 3] = [INDEX, ENTRY_HI, ENTRY_LO];
+
+/// A translation held by the TLB
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TlbEntry {
+    /// Virtual page number
+    pub vpn: u32,
+    /// Physical page number
+    pub frame: u32,
+    pub valid: bool,
+    /// Stores through the entry are allowed, the D bit of EntryLo
+    pub writable: bool,
+}
+
+impl TlbEntry {
+    /// Builds an entry from the EntryHi and EntryLo registers
+    pub fn from_cp0(entry_hi: u32, entry_lo: u32) -> Self {
+        Self {
+            vpn: entry_hi / PAGE_SIZE,
+            frame: entry_lo / PAGE_SIZE,
+            valid: entry_lo & ENTRY_LO_VALID != 0,
+            writable: entry_lo & ENTRY_LO_DIRTY != 0,
+        }
+    }
+
+    pub fn entry_hi(&self) -> u32 {
+        self.vpn * PAGE_SIZE
+    }
+
+    pub fn entry_lo(&self) -> u32 {
+        let mut entry_lo = self.frame * PAGE_SIZE;
+        if self.valid {
+            entry_lo |= ENTRY_LO_VALID;
+        }
+        if self.writable {
+            entry_lo |= ENTRY_LO_DIRTY;
+        }
+        entry_lo
+    }
+}
+
+/// Status bits of a page of the user address space
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    /// Physical page number, pages are mapped onto the frame with the same number
+    pub frame: u32,
+    /// The page may be accessed, pages below `.text` are left unmapped to catch null pointers
+    pub valid: bool,
+    /// The page has been stored to since it was first touched
+    pub dirty: bool,
+    /// The page may be stored to, `.text` is read only
+    pub writable: bool,
+}
+
+impl Page {
+    fn new(vpn: u32) -> Self {
+        let addr = vpn * PAGE_SIZE;
+        Self {
+            frame: vpn,
+            valid: addr >= TEXT_BASE,
+            dirty: false,
+            writable: addr >= DATA_BASE,
+        }
+    }
+
+    /// The TLB entry that maps this page
+    pub fn entry(&self, vpn: u32) -> TlbEntry {
+        TlbEntry {
+            vpn,
+            frame: self.frame,
+            valid: self.valid,
+            writable: self.writable,
+        }
+    }
+}
+
+/// Lookup counters of the TLB
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TlbStats {
+    pub lookups: u64,
+    /// Lookups that found no entry or an invalid one
+    pub misses: u64,
+    /// Stores refused because the page is not writable
+    pub write_faults: u64,
+    /// Entries written by `tlbwi` and `tlbwr` or by an automatic refill
+    pub refills: u64,
+}
+
+impl TlbStats {
+    /// Fraction of lookups that hit
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            1.0 - self.misses as f64 / self.lookups as f64
+        }
+    }
+}
+
+/// Checks accesses against the memory map and translates user addresses through a TLB when
+/// enabled
+#[derive(Debug, Clone)]
+pub struct Mmu {
+    map: MemoryMap,
+    enabled: bool,
+    entries: Vec<Option<TlbEntry>>,
+    /// Every page that has been looked up keyed by its virtual page number
+    pages: BTreeMap<u32, Page>,
+    stats: TlbStats,
+    /// State of the xorshift generator that picks the entry `tlbwr` replaces
+    seed: u32,
+}
+
+impl Default for Mmu {
+    fn default() -> Self {
+        Self::new(PipelineConfig::default())
+    }
+}
+
+impl Mmu {
+    /// Creates an MMU with an empty TLB and page table
+    pub fn new(config: PipelineConfig) -> Self {
+        Self {
+            map: MemoryMap::default(),
+            enabled: config.mmu,
+            entries: vec![None; config.tlb_entries.max(1) as usize],
+            pages: BTreeMap::new(),
+            stats: TlbStats::default(),
+            seed: 0x2545_F491,
+        }
+    }
+
+    /// Gets the regions of the address space accesses are checked against
+    pub fn map(&self) -> &MemoryMap {
+        &self.map
+    }
+
+    pub(crate) fn map_mut(&mut self) -> &mut MemoryMap {
+        &mut self.map
+    }
+
+    /// Checks if the TLB is in use
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Gets every TLB entry, empty entries have never been written
+    pub fn entries(&self) -> &[Option<TlbEntry>] {
+        &self.entries
+    }
+
+    /// Gets the page table sorted by virtual page number
+    pub fn pages(&self) -> &BTreeMap<u32, Page> {
+        &self.pages
+    }
+
+    pub fn stats(&self) -> &TlbStats {
+        &self.stats
+    }
+
+    /// Translates a virtual address into a physical one
+    ///
+    /// Accesses outside every region or that their region doesn't allow raise a protection fault.
+    /// Stores mark their page dirty. Addresses are passed through unchanged when the TLB is
+    /// disabled
+    pub fn translate(&mut self, address: u32, access: Access) -> Result<u32, Exception> {
+        self.map
+            .check(address, access)
+            .map_err(Exception::Protection)?;
+        if !self.enabled || address >= KERNEL_BASE {
+            return Ok(address);
+        }
+        let write = access == Access::Write;
+        self.stats.lookups += 1;
+        let vpn = address / PAGE_SIZE;
+        let entry = self
+            .entries
+            .iter()
+            .flatten()
+            .find(|e| e.vpn == vpn)
+            .filter(|e| e.valid);
+
+        let entry = match entry {
+            Some(entry) => *entry,
+            None => {
+                self.stats.misses += 1;
+                return Err(if write {
+                    Exception::TlbStore(address)
+                } else {
+                    Exception::TlbLoad(address)
+                });
+            }
+        };
+        if write {
+            if !entry.writable {
+                self.stats.write_faults += 1;
+                return Err(Exception::TlbModified(address));
+            }
+            self.page(vpn).dirty = true;
+        }
+        Ok(entry.frame * PAGE_SIZE + address % PAGE_SIZE)
+    }
+
+    /// Gets the page table entry of the page an address is in as EntryHi and EntryLo values
+    ///
+    /// This is loaded into coprocessor 0 when a TLB exception is raised so a handler can refill
+    /// the TLB without walking the page table itself
+    pub fn page_entry(&mut self, address: u32) -> (u32, u32) {
+        let vpn = address / PAGE_SIZE;
+        let entry = self.page(vpn).entry(vpn);
+        (entry.entry_hi(), entry.entry_lo())
+    }
+
+    /// Refills the TLB from the page table after a miss on a program without a handler
+    ///
+    /// # Returns
+    /// False if the page is not valid so retrying the access would miss again
+    pub fn refill(&mut self, address: u32) -> bool {
+        let vpn = address / PAGE_SIZE;
+        let page = *self.page(vpn);
+        if !page.valid {
+            return false;
+        }
+        // an invalid entry for the page is replaced rather than duplicated
+        let index = match self.find(vpn) {
+            Some(index) => index,
+            None => self.random(),
+        };
+        self.write(index, page.entry(vpn));
+        true
+    }
+
+    /// Executes a TLB instruction reading and writing its operands in coprocessor 0
+    pub(crate) fn execute(&mut self, op: u8, regs: &mut RegisterFile) {
+        let index = regs.read_cp0(INDEX) as usize % self.entries.len();
+        match op {
+            TLB_READ => {
+                let entry = self.entries[index].unwrap_or_default();
+                regs.write_cp0(ENTRY_HI, entry.entry_hi());
+                regs.write_cp0(ENTRY_LO, entry.entry_lo());
+            }
+            TLB_WRITE_INDEXED | TLB_WRITE_RANDOM => {
+                let entry = TlbEntry::from_cp0(regs.read_cp0(ENTRY_HI), regs.read_cp0(ENTRY_LO));
+                let index = if op == TLB_WRITE_RANDOM {
+                    self.random()
+                } else {
+                    index
+                };
+                self.write(index, entry);
+            }
+            TLB_PROBE => {
+                let vpn = regs.read_cp0(ENTRY_HI) / PAGE_SIZE;
+                let found = self.find(vpn);
+                regs.write_cp0(INDEX, found.map_or(INDEX_PROBE_FAILED, |i| i as u32));
+            }
+            _ => {}
+        }
+    }
+
+    /// Finds the entry that maps a page whether or not it is valid
+    fn find(&self, vpn: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.map(|e| e.vpn) == Some(vpn))
+    }
+
+    fn write(&mut self, index: usize, entry: TlbEntry) {
+        self.stats.refills += 1;
+        self.entries[index] = Some(entry);
+    }
+
+    /// Gets a page table entry creating it the first time a page is touched
+    fn page(&mut self, vpn: u32) -> &mut Page {
+        self.pages.entry(vpn).or_insert_with(|| Page::new(vpn))
+    }
+
+    /// Picks the entry replaced by `tlbwr`
+    fn random(&mut self) -> usize {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as usize % self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mmu() -> Mmu {
+        Mmu::new(PipelineConfig {
+            mmu: true,
+            tlb_entries: 4,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn disabled_mmu_and_kernel_addresses_are_not_translated() {
+        let mut mmu = Mmu::default();
+        assert_eq!(
+            mmu.translate(DATA_BASE + 4, Access::Write),
+            Ok(DATA_BASE + 4)
+        );
+        let mut mmu = self::mmu();
+        assert_eq!(mmu.translate(0x8000_0180, Access::Execute), Ok(0x8000_0180));
+        assert_eq!(mmu.stats().lookups, 0);
+    }
+
+    #[test]
+    fn miss_is_refilled_from_the_page_table() {
+        let mut mmu = mmu();
+        let addr = DATA_BASE + 0x10;
+        assert_eq!(
+            mmu.translate(addr, Access::Read),
+            Err(Exception::TlbLoad(addr))
+        );
+        assert_eq!(
+            mmu.translate(addr, Access::Write),
+            Err(Exception::TlbStore(addr))
+        );
+        assert!(mmu.refill(addr));
+        assert_eq!(mmu.translate(addr, Access::Write), Ok(addr));
+        assert!(mmu.pages()[&(addr / PAGE_SIZE)].dirty);
+
+        let stats = *mmu.stats();
+        assert_eq!((stats.lookups, stats.misses, stats.refills), (3, 2, 1));
+    }
+
+    #[test]
+    fn protection_is_checked_before_the_tlb() {
+        let mut mmu = mmu();
+        assert!(matches!(
+            mmu.translate(TEXT_BASE, Access::Write),
+            Err(Exception::Protection(_))
+        ));
+        assert!(matches!(
+            mmu.translate(0, Access::Read),
+            Err(Exception::Protection(_))
+        ));
+        assert_eq!(mmu.stats().lookups, 0);
+        assert!(!mmu.refill(0));
+    }
+
+    #[test]
+    fn read_only_entry_raises_tlb_modified() {
+        let mut mmu = mmu();
+        let mut regs = RegisterFile::default();
+        let (entry_hi, entry_lo) = mmu.page_entry(DATA_BASE);
+        regs.write_cp0(ENTRY_HI, entry_hi);
+        regs.write_cp0(ENTRY_LO, entry_lo & !ENTRY_LO_DIRTY);
+        mmu.execute(TLB_WRITE_RANDOM, &mut regs);
+        assert_eq!(mmu.translate(DATA_BASE, Access::Read), Ok(DATA_BASE));
+        assert_eq!(
+            mmu.translate(DATA_BASE, Access::Write),
+            Err(Exception::TlbModified(DATA_BASE))
+        );
+        assert_eq!(mmu.stats().write_faults, 1);
+    }
+
+    #[test]
+    fn tlb_instructions_go_through_coprocessor_0() {
+        let mut mmu = mmu();
+        let mut regs = RegisterFile::default();
+        let (entry_hi, entry_lo) = mmu.page_entry(DATA_BASE);
+        regs.write_cp0(INDEX, 2);
+        regs.write_cp0(ENTRY_HI, entry_hi);
+        regs.write_cp0(ENTRY_LO, entry_lo);
+        mmu.execute(TLB_WRITE_INDEXED, &mut regs);
+        assert_eq!(
+            mmu.entries()[2],
+            Some(TlbEntry::from_cp0(entry_hi, entry_lo))
+        );
+        assert_eq!(
+            mmu.translate(DATA_BASE + 4, Access::Write),
+            Ok(DATA_BASE + 4)
+        );
+
+        regs.write_cp0(INDEX, 0);
+        mmu.execute(TLB_PROBE, &mut regs);
+        assert_eq!(regs.read_cp0(INDEX), 2);
+        regs.write_cp0(ENTRY_HI, 0);
+        regs.write_cp0(ENTRY_LO, 0);
+        mmu.execute(TLB_READ, &mut regs);
+        assert_eq!(regs.read_cp0(ENTRY_HI), entry_hi);
+        assert_eq!(regs.read_cp0(ENTRY_LO), entry_lo);
+
+        regs.write_cp0(ENTRY_HI, TEXT_BASE);
+        mmu.execute(TLB_PROBE, &mut regs);
+        assert_eq!(regs.read_cp0(INDEX), INDEX_PROBE_FAILED);
+    }
+}
@@ -11,7 +11,7 @@ use nom::{
     error::{context, VerboseError, VerboseErrorKind},
     multi::many_till,
     sequence::{delimited, preceded, terminated},
-    Finish, IResult,
+    Finish, IResult, Offset,
 };
 
 mod directives;
@@ -26,8 +26,8 @@ pub use instruction::instruction;
 pub use label::label;
 pub use numbers::*;
 pub use opcode::opcode;
-pub use opcode::opcode_name;
-pub use register::register;
+pub use opcode::{disassemble, opcode_name};
+pub use register::{cop0_register, fp_register, register};
 
 use model::{LabelTable, Line};
 
@@ -92,47 +92,84 @@ pub fn parse_line(input: &str) -> IResult<&str, Line, VerboseError<&str>> {
     )(input)
 }
 
-pub fn parse_string(input: &str) -> Result<Vec<Line>> {
-    let (_, (output, _)) = many_till(
-        alt((
+/// Parses a single line and records the byte offset in `source` it started at
+fn source_line<'a>(
+    source: &'a str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (usize, Line), VerboseError<&'a str>> {
+    move |input| {
+        let start = source.offset(input);
+        let (input, line) = alt((
             comment,
             blank,
             terminated(label, preceded(space0, opt(tag("\n")))),
             parse_line,
-        )),
-        eof,
-    )(input)
-    .finish()
-    .map_err(|e| anyhow!("{}", convert_error(input, e)))?;
-    Ok(output)
+        ))(input)?;
+        Ok((input, (start, line)))
+    }
 }
 
-pub fn compute_labels(input: &[Line]) -> LabelTable {
+/// Parses a script
+///
+/// Each parsed line is paired with the source line it came from, a label sharing a line with an
+/// instruction produces two lines with the same line number
+pub fn parse_string(input: &str) -> Result<Vec<(usize, Line)>> {
+    let (_, (output, _)) = many_till(source_line(input), eof)(input)
+        .finish()
+        .map_err(|e| anyhow!("{}", convert_error(input, e)))?;
+
+    let mut line_num = 0;
+    let mut last = 0;
+    Ok(output
+        .into_iter()
+        .map(|(start, line)| {
+            line_num += input[last..start].matches('\n').count();
+            last = start;
+            (line_num, line)
+        })
+        .collect())
+}
+
+pub fn compute_labels(input: &[(usize, Line)]) -> LabelTable {
     let mut labels = LabelTable::default();
     let mut segments = Segments::default();
     let mut pc = segments.switch(Segment::Text);
 
-    for (i, line) in input.iter().enumerate() {
+    // labels that have not been followed by an instruction yet
+    let mut pending = vec![];
+    for (i, line) in input {
         match line {
             Line::Label(name) => {
                 labels.insert_label(name.clone(), *pc);
+                pending.push(name.clone());
             }
             Line::Instruction(ins) => {
-                labels.insert_line(i, *pc);
+                // labels in front of aligned data point at the data instead of the padding
+                if let Some(model::Instruction::Align { bytes }) = ins.first() {
+                    let addr = *pc + model::padding(*pc, *bytes);
+                    for name in pending.drain(..) {
+                        labels.insert_label(name, addr);
+                    }
+                }
+                pending.clear();
+                labels.insert_line(*i, *pc);
                 let mut len = 0;
                 for inst in ins {
                     len += match inst {
-                        model::Instruction::Literal { data } => data.len(),
+                        model::Instruction::Literal { data } => data.len() as u32,
+                        model::Instruction::Align { bytes } => model::padding(*pc + len, *bytes),
                         _ => 4,
                     };
                 }
-                *pc += len as u32;
+                *pc += len;
+            }
+            Line::Segment(seg) => {
+                pending.clear();
+                pc = segments.switch(*seg);
             }
-            Line::Segment(seg) => pc = segments.switch(*seg),
             _ => {}
         }
     }
 
-    std::fs::write("labels.txt", format!("{:#?}", input)).unwrap();
+    labels.set_segment_ends(segments);
     labels
 }
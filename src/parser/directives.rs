@@ -4,6 +4,7 @@ use nom::{
     combinator::{map, opt},
     error::context,
     multi::many1,
+    number::complete::double,
     sequence::delimited,
 };
 
@@ -117,6 +118,38 @@ pub fn byte_lit(input: &str) -> ParserOutput {
     )(input)
 }
 
+/// Parses `.float` values, the first one is aligned to 4 bytes
+pub fn float_lit(input: &str) -> ParserOutput<'_> {
+    map(
+        many1(map(
+            delimited(multispace0, double, opt(tag(","))),
+            |f: f64| Instruction::Literal {
+                data: (f as f32).to_bits().to_le_bytes().to_vec(),
+            },
+        )),
+        |mut x| {
+            x.insert(0, Instruction::Align { bytes: 4 });
+            Line::Instruction(x)
+        },
+    )(input)
+}
+
+/// Parses `.double` values, the first one is aligned to 8 bytes
+pub fn double_lit(input: &str) -> ParserOutput<'_> {
+    map(
+        many1(map(
+            delimited(multispace0, double, opt(tag(","))),
+            |f: f64| Instruction::Literal {
+                data: f.to_bits().to_le_bytes().to_vec(),
+            },
+        )),
+        |mut x| {
+            x.insert(0, Instruction::Align { bytes: 8 });
+            Line::Instruction(x)
+        },
+    )(input)
+}
+
 pub fn space(input: &str) -> ParserOutput {
     map(
         context("Expected amount to space", parser::int),
@@ -2,7 +2,7 @@ use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{space0, space1},
-    combinator::{map, peek},
+    combinator::{eof, map, map_res, opt, peek},
     error::{context, VerboseError},
     sequence::{delimited, preceded, terminated, tuple},
     IResult,
@@ -10,7 +10,7 @@ use nom::{
 
 use crate::{
     parser::{self, model::Opcode},
-    AT, ZERO,
+    Register, AT, RA, ZERO,
 };
 
 use super::{
@@ -43,24 +43,17 @@ fn symbol(input: &str) -> IResult<&str, Symbol, VerboseError<&str>> {
     )(input)
 }
 
+/// Opcodes shared by every coprocessor instruction
+const COP0: Opcode = Opcode::Op(0x10);
+const COP1: Opcode = Opcode::Op(0x11);
+
 pub(crate) type ParserOutput<'a> = IResult<&'a str, Line, VerboseError<&'a str>>;
 
 /// Parse jump instructions
 /// <OP> <label>
 pub fn j_type(input: &str, op: Opcode) -> ParserOutput {
     let (input, addr) = context("Expected label", symbol)(input)?;
-    Ok((
-        input,
-        Line::Instruction(vec![
-            Instruction::J { op, addr },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-        ]),
-    ))
+    Ok((input, Line::Instruction(vec![Instruction::J { op, addr }])))
 }
 
 /// Parse JR instruction
@@ -68,39 +61,149 @@ pub fn jr_type(input: &str, op: Opcode) -> ParserOutput {
     let (input, rs) = context("Expected register", parser::register)(input)?;
     let rt = ZERO;
     let rd = ZERO;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::R {
+            op,
+            rd,
+            rs,
+            rt,
+            shamt: 0,
+        }]),
+    ))
+}
+
+/// Parse JALR instruction
+/// `<OP> <rs>` or `<OP> <rd>, <rs>` where rd defaults to $ra
+pub fn jalr_type(input: &str, op: Opcode) -> ParserOutput<'_> {
+    let (input, first) = context("Expected register", parser::register)(input)?;
+    let (input, second) = opt(preceded(separator, parser::register))(input)?;
+    let (rd, rs) = match second {
+        Some(rs) => (first, rs),
+        None => (RA, first),
+    };
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::R {
+            op,
+            rd,
+            rs,
+            rt: ZERO,
+            shamt: 0,
+        }]),
+    ))
+}
+
+/// Parses simple R-type instructions using the format
+/// `<OP> <rd>, <rs>, <rt>`
+pub fn r_type(input: &str, op: Opcode) -> ParserOutput {
+    let (input, rd) = context("Destination Register", parser::register)(input)?;
+    let (input, rs) = context("Source Register", preceded(separator, parser::register))(input)?;
+    let (input, rt) = context("Target Register", preceded(separator, parser::register))(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::R {
+            op,
+            rd,
+            rs,
+            rt,
+            shamt: 0,
+        }]),
+    ))
+}
+
+/// Parses multiply/divide instructions
+/// `<OP> <rs>, <rt>`
+///
+/// Also accepts the pseudo instruction form `<OP> <rd>, <rs>, <rt>` which moves the result from
+/// `result` (either mflo or mfhi) into rd
+pub fn muldiv_type(input: &str, op: Opcode, result: Opcode) -> ParserOutput<'_> {
+    let (input, first) = context("Expected first register", parser::register)(input)?;
+    let (input, second) = context(
+        "Expected second register",
+        preceded(separator, parser::register),
+    )(input)?;
+    let (input, third) = opt(preceded(separator, parser::register))(input)?;
+
+    let (rs, rt) = match third {
+        Some(rt) => (second, rt),
+        None => (first, second),
+    };
+    let mut ins = vec![Instruction::R {
+        op,
+        rd: ZERO,
+        rs,
+        rt,
+        shamt: 0,
+    }];
+    if third.is_some() {
+        ins.push(Instruction::R {
+            op: result,
+            rd: first,
+            rs: ZERO,
+            rt: ZERO,
+            shamt: 0,
+        });
+    }
+    Ok((input, Line::Instruction(ins)))
+}
+
+/// Parses three register multiply/divide pseudo instructions such as mul and rem
+/// `<OP> <rd>, <rs>, <rt>`
+///
+/// Expands to `op` followed by a move from `result` (either mflo or mfhi) into rd
+pub fn mul_pseudo(input: &str, op: Opcode, result: Opcode) -> ParserOutput<'_> {
+    let (input, rd) = context("Destination Register", parser::register)(input)?;
+    let (input, rs) = context("Source Register", preceded(separator, parser::register))(input)?;
+    let (input, rt) = context("Target Register", preceded(separator, parser::register))(input)?;
     Ok((
         input,
         Line::Instruction(vec![
             Instruction::R {
                 op,
-                rd,
+                rd: ZERO,
                 rs,
                 rt,
                 shamt: 0,
             },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
+            Instruction::R {
+                op: result,
+                rd,
+                rs: ZERO,
+                rt: ZERO,
+                shamt: 0,
             },
         ]),
     ))
 }
 
-/// Parses simple R-type instructions using the format
-/// `<OP> <rd>, <rs>, <rt>`
-pub fn r_type(input: &str, op: Opcode) -> ParserOutput {
-    let (input, rd) = context("Destination Register", parser::register)(input)?;
-    let (input, rs) = context("Source Register", preceded(separator, parser::register))(input)?;
-    let (input, rt) = context("Target Register", preceded(separator, parser::register))(input)?;
+/// Parses moves from HI/LO
+/// `<OP> <rd>`
+pub fn move_from_type(input: &str, op: Opcode) -> ParserOutput<'_> {
+    let (input, rd) = context("Expected destination register", parser::register)(input)?;
     Ok((
         input,
         Line::Instruction(vec![Instruction::R {
             op,
             rd,
+            rs: ZERO,
+            rt: ZERO,
+            shamt: 0,
+        }]),
+    ))
+}
+
+/// Parses moves to HI/LO
+/// `<OP> <rs>`
+pub fn move_to_type(input: &str, op: Opcode) -> ParserOutput<'_> {
+    let (input, rs) = context("Expected source register", parser::register)(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::R {
+            op,
+            rd: ZERO,
             rs,
-            rt,
+            rt: ZERO,
             shamt: 0,
         }]),
     ))
@@ -129,6 +232,24 @@ pub fn shift_type(input: &str, op: Opcode) -> ParserOutput {
     ))
 }
 
+/// Parses variable shift instructions
+/// `<OP> <rd>, <rt>, <rs>`
+pub fn var_shift_type(input: &str, op: Opcode) -> ParserOutput<'_> {
+    let (input, rd) = context("Destination Register", parser::register)(input)?;
+    let (input, rt) = context("Target Register", preceded(separator, parser::register))(input)?;
+    let (input, rs) = context("Shift Register", preceded(separator, parser::register))(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::R {
+            op,
+            rd,
+            rs,
+            rt,
+            shamt: 0,
+        }]),
+    ))
+}
+
 /// Parses simple immediate mode instructions using the format
 /// `<OP> <rt> <rs> <imm>`
 pub fn i_type(input: &str, op: Opcode) -> ParserOutput {
@@ -175,6 +296,172 @@ pub fn load_type(input: &str, op: Opcode) -> ParserOutput {
     ))
 }
 
+/// Parses floating point load and store instructions
+/// `<OP> <ft> <imm>(<rs>)`
+pub fn fp_load_type(input: &str, op: Opcode) -> ParserOutput<'_> {
+    let (input, rt) = context("Expected target register", parser::fp_register)(input)?;
+    let (input, imm) = context("Expected offset value", preceded(separator, immediate))(input)?;
+    let (input, rs) = context(
+        "Expected source value",
+        delimited(tag("("), parser::register, tag(")")),
+    )(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::I { op, rt, rs, imm }]),
+    ))
+}
+
+/// Parses moves between general purpose and floating point registers
+/// `<OP> <rt>, <fs>`
+pub fn fp_move_type(input: &str, fmt: u32) -> ParserOutput<'_> {
+    let (input, ft) = context("Expected general purpose register", parser::register)(input)?;
+    let (input, fs) = context(
+        "Expected floating point register",
+        preceded(separator, parser::fp_register),
+    )(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP1,
+            fmt,
+            ft,
+            fs,
+            fd: ZERO,
+            funct: 0,
+        }]),
+    ))
+}
+
+/// Parses moves between general purpose and coprocessor 0 registers
+/// `<OP> <rt>, <rd>`
+pub fn cop0_move_type(input: &str, fmt: u32) -> ParserOutput<'_> {
+    let (input, rt) = context("Expected general purpose register", parser::register)(input)?;
+    let (input, rd) = context(
+        "Expected coprocessor 0 register",
+        preceded(separator, parser::cop0_register),
+    )(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP0,
+            fmt,
+            ft: rt,
+            fs: rd,
+            fd: ZERO,
+            funct: 0,
+        }]),
+    ))
+}
+
+/// Parses coprocessor 0 instructions without operands such as eret and the TLB instructions
+pub fn cop0_function(input: &str, funct: u32) -> ParserOutput<'_> {
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP0,
+            fmt: 0x10,
+            ft: ZERO,
+            fs: ZERO,
+            fd: ZERO,
+            funct,
+        }]),
+    ))
+}
+
+/// Parses three register floating point instructions
+/// `<OP> <fd>, <fs>, <ft>`
+pub fn fp_r_type(input: &str, op: Opcode, fmt: u32) -> ParserOutput<'_> {
+    let (input, fd) = context("Destination Register", parser::fp_register)(input)?;
+    let (input, fs) = context("Source Register", preceded(separator, parser::fp_register))(input)?;
+    let (input, ft) = context("Target Register", preceded(separator, parser::fp_register))(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP1,
+            fmt,
+            ft,
+            fs,
+            fd,
+            funct: op.value(),
+        }]),
+    ))
+}
+
+/// Parses two register floating point instructions such as conversions
+/// `<OP> <fd>, <fs>`
+pub fn fp_unary_type(input: &str, op: Opcode, fmt: u32) -> ParserOutput<'_> {
+    let (input, fd) = context("Destination Register", parser::fp_register)(input)?;
+    let (input, fs) = context("Source Register", preceded(separator, parser::fp_register))(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP1,
+            fmt,
+            ft: ZERO,
+            fs,
+            fd,
+            funct: op.value(),
+        }]),
+    ))
+}
+
+/// Parses an optional floating point condition flag followed by a separator
+fn condition_flag(input: &str) -> IResult<&str, u32, VerboseError<&str>> {
+    map(
+        opt(terminated(
+            map_res(int, |cc: u32| if cc < 8 { Ok(cc) } else { Err(()) }),
+            separator,
+        )),
+        |cc| cc.unwrap_or(0),
+    )(input)
+}
+
+/// Parses floating point comparisons
+/// `<OP> [cc,] <fs>, <ft>` where the condition flag defaults to 0
+pub fn fp_compare_type(input: &str, op: Opcode, fmt: u32) -> ParserOutput<'_> {
+    let (input, cc) = condition_flag(input)?;
+    let (input, fs) = context("Expected first register", parser::fp_register)(input)?;
+    let (input, ft) = context(
+        "Expected second register",
+        preceded(separator, parser::fp_register),
+    )(input)?;
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::FR {
+            op: COP1,
+            fmt,
+            ft,
+            fs,
+            // the condition flag is held in the top 3 bits of fd
+            fd: Register::from(cc << 2),
+            funct: op.value(),
+        }]),
+    ))
+}
+
+/// Parses floating point branches
+/// `<OP> [cc,] <label>` where the condition flag defaults to 0
+///
+/// `sense` selects whether the branch is taken when the flag is true (1) or false (0)
+pub fn fp_branch_type(input: &str, op: Opcode, sense: u32) -> ParserOutput<'_> {
+    let (input, cc) = condition_flag(input)?;
+    let (input, mut imm) = context("Expected label", immediate)(input)?;
+
+    // if we got a label make it pc relative
+    if let Imm::Label(label) = imm {
+        imm = Imm::PcRelative(label);
+    }
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::I {
+            op,
+            rt: Register::from((cc << 2) | sense),
+            rs: Register::from(0x08), // BC
+            imm,
+        }]),
+    ))
+}
+
 /// Parses branch instructions
 /// `<OP> <rt> <rs> <label>`
 pub fn branch_type(input: &str, op: Opcode) -> ParserOutput {
@@ -191,20 +478,50 @@ pub fn branch_type(input: &str, op: Opcode) -> ParserOutput {
     }
     Ok((
         input,
-        Line::Instruction(vec![
-            Instruction::I { op, rt, rs, imm },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-        ]),
+        Line::Instruction(vec![Instruction::I { op, rt, rs, imm }]),
+    ))
+}
+
+/// Parses a branch that compares a single register to zero
+///
+/// `rt` is a fixed value since REGIMM instructions use it to select the comparison
+pub fn zero_branch_type(input: &str, op: Opcode, rt: Register) -> ParserOutput<'_> {
+    let (input, rs) = context("Expected register", parser::register)(input)?;
+    let (input, mut imm) = context("Expected label", preceded(separator, immediate))(input)?;
+
+    // if we got a label make it pc relative
+    if let Imm::Label(label) = imm {
+        imm = Imm::PcRelative(label);
+    }
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::I { op, rt, rs, imm }]),
+    ))
+}
+
+/// Parses an unconditional branch pseudo instruction that compares $zero to itself
+pub fn always_branch(input: &str, op: Opcode, rt: Register) -> ParserOutput<'_> {
+    let (input, mut imm) = context("Expected label", immediate)(input)?;
+
+    // if we got a label make it pc relative
+    if let Imm::Label(label) = imm {
+        imm = Imm::PcRelative(label);
+    }
+    Ok((
+        input,
+        Line::Instruction(vec![Instruction::I {
+            op,
+            rt,
+            rs: ZERO,
+            imm,
+        }]),
     ))
 }
 
 /// Parses branch pseudo instructions
-pub fn multi_branch(input: &str, less_than: bool, equal: bool) -> ParserOutput {
+///
+/// If `unsigned` is set the registers are compared with sltu instead of slt
+pub fn multi_branch(input: &str, less_than: bool, equal: bool, unsigned: bool) -> ParserOutput {
     let (input, rt) = context("Expected first register", parser::register)(input)?;
     let (input, rs) = context(
         "Expected second register",
@@ -221,7 +538,11 @@ pub fn multi_branch(input: &str, less_than: bool, equal: bool) -> ParserOutput {
         input,
         Line::Instruction(vec![
             Instruction::R {
-                op: Opcode::Funct(0x2a), //slt
+                op: if unsigned {
+                    Opcode::Funct(0x2b) // sltu
+                } else {
+                    Opcode::Funct(0x2a) // slt
+                },
                 rd: AT,
                 rs: if less_than != equal { rt } else { rs }, // != is used as an XOR
                 rt: if less_than != equal { rs } else { rt },
@@ -237,12 +558,6 @@ pub fn multi_branch(input: &str, less_than: bool, equal: bool) -> ParserOutput {
                 rt: ZERO,
                 imm,
             },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
-            Instruction::Literal {
-                data: vec![0, 0, 0, 0],
-            },
         ]),
     ))
 }
@@ -257,7 +572,7 @@ pub fn move_ins(input: &str) -> ParserOutput {
     Ok((
         input,
         Line::Instruction(vec![Instruction::R {
-            op: Opcode::Funct(0x20), // add
+            op: Opcode::Funct(0x21), // addu
             rd,
             rs,
             rt: ZERO,
@@ -295,13 +610,27 @@ pub fn li_ins(input: &str) -> ParserOutput {
                     imm,
                 }],
                 Imm::LowHWord(_) => vec![Instruction::I {
-                    op: Opcode::Op(0x08), //addi
+                    op: Opcode::Op(0x09), // addiu
                     rt: reg,
                     rs: ZERO,
                     imm,
                 }],
                 Imm::Value(value) => {
-                    if value > u16::MAX as i64 {
+                    if value >= i16::MIN as i64 && value <= i16::MAX as i64 {
+                        vec![Instruction::I {
+                            op: Opcode::Op(0x09), // addiu
+                            rt: reg,
+                            rs: ZERO,
+                            imm,
+                        }]
+                    } else if value >= 0 && value <= u16::MAX as i64 {
+                        vec![Instruction::I {
+                            op: Opcode::Op(0x0d), // ori
+                            rt: reg,
+                            rs: ZERO,
+                            imm,
+                        }]
+                    } else {
                         vec![
                             Instruction::I {
                                 op: Opcode::Op(0x0f),
@@ -316,13 +645,6 @@ pub fn li_ins(input: &str) -> ParserOutput {
                                 imm: Imm::Value(value & 0xFFFF),
                             },
                         ]
-                    } else {
-                        vec![Instruction::I {
-                            op: Opcode::Op(0x08), // addi
-                            rt: reg,
-                            rs: ZERO,
-                            imm,
-                        }]
                     }
                 }
                 Imm::PcRelative(_) => todo!(),
@@ -358,7 +680,7 @@ pub fn instruction(input: &str) -> IResult<&str, Line, VerboseError<&str>> {
     // grab the opcode
     let (input, parser) = preceded(
         space0,
-        terminated(parser::opcode, alt((space1, peek(tag("\n"))))),
+        terminated(parser::opcode, alt((space1, peek(tag("\n")), eof))),
     )(input)?;
     parser.parse(input)
 }
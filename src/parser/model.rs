@@ -1,22 +1,69 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
 
 mod instruction;
 mod opcode;
+mod symbols;
 
 pub use instruction::*;
 pub use opcode::Opcode;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Segment {
     Text,
     Data,
+    KText,
+    KData,
+}
+
+impl Segment {
+    /// Every segment in the order they are laid out in memory
+    pub const ALL: [Segment; 4] = [Segment::Text, Segment::Data, Segment::KText, Segment::KData];
+
+    /// Address the segment begins at
+    pub fn base(&self) -> u32 {
+        match self {
+            Segment::Text => TEXT_BASE,
+            Segment::Data => DATA_BASE,
+            Segment::KText => KTEXT_BASE,
+            Segment::KData => KDATA_BASE,
+        }
+    }
+
+    /// Address the segment must end before so it doesn't run into the region after it
+    pub fn limit(&self) -> u32 {
+        match self {
+            Segment::Text => DATA_BASE,
+            Segment::Data => HEAP_BASE,
+            Segment::KText => KDATA_BASE,
+            Segment::KData => MMIO_BASE,
+        }
+    }
+
+    /// The directive used to switch to this segment
+    pub fn name(&self) -> &'static str {
+        match self {
+            Segment::Text => ".text",
+            Segment::Data => ".data",
+            Segment::KText => ".ktext",
+            Segment::KData => ".kdata",
+        }
+    }
 }
 
 pub const TEXT_BASE: u32 = 0x00400000;
 pub const DATA_BASE: u32 = 0x10010000;
+pub const HEAP_BASE: u32 = 0x10040000;
+// the stack may grow down to 8MiB below the kernel
+pub const STACK_LIMIT: u32 = 0x7f800000;
 pub const STACK_BASE: u32 = 0x7fffeffc;
+pub const KERNEL_BASE: u32 = 0x80000000;
+// kernel text begins at the exception vector so a handler can be written at the top of .ktext
+pub const KTEXT_BASE: u32 = 0x80000180;
+pub const KDATA_BASE: u32 = 0x90000000;
+pub const MMIO_BASE: u32 = 0xFFFF0000;
 
 /// Tracks the current position in each segment
+#[derive(Debug, Clone)]
 pub struct Segments {
     segments: Vec<u32>,
 }
@@ -24,7 +71,7 @@ pub struct Segments {
 impl Default for Segments {
     fn default() -> Self {
         Self {
-            segments: vec![TEXT_BASE, DATA_BASE],
+            segments: Segment::ALL.iter().map(Segment::base).collect(),
         }
     }
 }
@@ -34,6 +81,18 @@ impl Segments {
         match seg {
             Segment::Text => &mut self.segments[0],
             Segment::Data => &mut self.segments[1],
+            Segment::KText => &mut self.segments[2],
+            Segment::KData => &mut self.segments[3],
+        }
+    }
+
+    /// Gets the current position in a segment without switching to it
+    pub fn get(&self, seg: Segment) -> u32 {
+        match seg {
+            Segment::Text => self.segments[0],
+            Segment::Data => self.segments[1],
+            Segment::KText => self.segments[2],
+            Segment::KData => self.segments[3],
         }
     }
 }
@@ -54,6 +113,9 @@ pub struct LabelTable {
 
     // Is kept sorted by PC value
     lines: Vec<(usize, u32)>,
+
+    // Position each segment ended at after assembling
+    segments: Segments,
 }
 
 impl LabelTable {
@@ -74,6 +136,21 @@ impl LabelTable {
         self.lines.sort_by_key(|x| x.1);
     }
 
+    /// Record where each segment ended once every line has been placed
+    pub fn set_segment_ends(&mut self, segments: Segments) {
+        self.segments = segments;
+    }
+
+    /// Gets the addresses that were assembled into a segment
+    pub fn segment_range(&self, seg: Segment) -> Range<u32> {
+        seg.base()..self.segments.get(seg)
+    }
+
+    /// Iterates over the source lines and their PCs in order of PC
+    pub fn lines(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.lines.iter().copied()
+    }
+
     pub fn get_label(&self, key: &str) -> Option<u32> {
         self.labels.get(key).map(|x| *x)
     }
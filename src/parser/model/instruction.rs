@@ -60,15 +60,33 @@ pub enum Instruction {
         op: Opcode,
         addr: Symbol,
     },
+    /// Coprocessor instructions that select a format or operation in place of rs
+    FR {
+        op: Opcode,
+        fmt: u32,
+        ft: Register,
+        fs: Register,
+        fd: Register,
+        funct: u32,
+    },
     Literal {
         data: Vec<u8>,
     },
+    /// Zero padding up to the next multiple of `bytes`
+    Align {
+        bytes: u32,
+    },
 }
 
 fn field(x: u32, start: u32, width: u32) -> u32 {
     (x & (2_u32.pow(width) - 1)) << start
 }
 
+/// Number of bytes needed to move `pc` to a multiple of `bytes`
+pub fn padding(pc: u32, bytes: u32) -> u32 {
+    pc.wrapping_neg() % bytes
+}
+
 impl Instruction {
     pub fn asm(&self, labels: &LabelTable, pc: u32) -> (Vec<u8>, usize) {
         match self {
@@ -97,7 +115,29 @@ impl Instruction {
                 .to_vec(),
                 4,
             ),
+            Instruction::FR {
+                op,
+                fmt,
+                ft,
+                fs,
+                fd,
+                funct,
+            } => (
+                (field(op.value(), 26, 6)
+                    | field(*fmt, 21, 5)
+                    | field(ft.value(), 16, 5)
+                    | field(fs.value(), 11, 5)
+                    | field(fd.value(), 6, 5)
+                    | field(*funct, 0, 6))
+                .to_le_bytes()
+                .to_vec(),
+                4,
+            ),
             Instruction::Literal { data } => (data.clone(), data.len()),
+            Instruction::Align { bytes } => {
+                let len = padding(pc, *bytes) as usize;
+                (vec![0; len], len)
+            }
             Instruction::J { op, addr } => (
                 (field(op.value(), 26, 6) | field(addr.asm(labels), 0, 26))
                     .to_le_bytes()
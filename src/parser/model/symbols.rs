@@ -0,0 +1,133 @@
+//! Exports the contents of a [`LabelTable`] so it can be consumed by other tools
+
+use std::fmt::Write;
+
+use super::{LabelTable, Segment};
+
+/// A label and the region of memory it names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelEntry {
+    pub name: String,
+    pub address: u32,
+    pub segment: Option<Segment>,
+    /// Number of bytes until the next label or the end of the segment
+    pub size: u32,
+}
+
+impl LabelTable {
+    /// Gets the segment an address was assembled into
+    ///
+    /// An address at the very end of a segment is considered part of it so labels placed after
+    /// the last line of a segment are still found
+    pub fn segment_of(&self, addr: u32) -> Option<Segment> {
+        Segment::ALL.iter().copied().find(|seg| {
+            let range = self.segment_range(*seg);
+            range.start <= addr && addr <= range.end
+        })
+    }
+
+    /// Lists every label sorted by address
+    pub fn symbols(&self) -> Vec<LabelEntry> {
+        let mut labels: Vec<(&String, u32)> = self.labels.iter().map(|(k, v)| (k, *v)).collect();
+        labels.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+
+        labels
+            .iter()
+            .map(|(name, address)| {
+                let segment = self.segment_of(*address);
+                let end = segment
+                    .map(|seg| self.segment_range(seg).end)
+                    .unwrap_or(*address);
+                // labels sharing an address name the same bytes so look for the next address
+                let next = labels
+                    .iter()
+                    .map(|(_, addr)| *addr)
+                    .find(|addr| addr > address && *addr <= end)
+                    .unwrap_or(end);
+                LabelEntry {
+                    name: name.to_string(),
+                    address: *address,
+                    segment,
+                    size: next - address,
+                }
+            })
+            .collect()
+    }
+
+    /// Formats the symbol table as human readable text
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("SEGMENTS\n");
+        for seg in Segment::ALL.iter() {
+            let range = self.segment_range(*seg);
+            let _ = writeln!(
+                out,
+                "{:<8}0x{:08X}  {} bytes",
+                seg.name(),
+                range.start,
+                range.len()
+            );
+        }
+
+        out.push_str("\nLABELS\n");
+        for label in self.symbols() {
+            let seg = label.segment.map(|seg| seg.name()).unwrap_or("");
+            let _ = writeln!(
+                out,
+                "0x{:08X}  {:<8}{:>6} bytes  {}",
+                label.address, seg, label.size, label.name
+            );
+        }
+
+        out.push_str("\nLINES\n");
+        for (line, pc) in self.lines() {
+            let _ = writeln!(out, "0x{:08X}  {}", pc, line + 1);
+        }
+        out
+    }
+
+    /// Formats the symbol table as JSON
+    ///
+    /// Source lines are numbered from 1
+    pub fn to_json(&self) -> String {
+        let segments: Vec<String> = Segment::ALL
+            .iter()
+            .map(|seg| {
+                let range = self.segment_range(*seg);
+                format!(
+                    r#"{{"name":"{}","base":{},"size":{}}}"#,
+                    seg.name(),
+                    range.start,
+                    range.len()
+                )
+            })
+            .collect();
+
+        // label names are identifiers so they never need to be escaped
+        let labels: Vec<String> = self
+            .symbols()
+            .iter()
+            .map(|label| {
+                let seg = match label.segment {
+                    Some(seg) => format!(r#""{}""#, seg.name()),
+                    None => "null".to_string(),
+                };
+                format!(
+                    r#"{{"name":"{}","address":{},"segment":{},"size":{}}}"#,
+                    label.name, label.address, seg, label.size
+                )
+            })
+            .collect();
+
+        let lines: Vec<String> = self
+            .lines()
+            .map(|(line, pc)| format!(r#"{{"line":{},"pc":{}}}"#, line + 1, pc))
+            .collect();
+
+        format!(
+            r#"{{"segments":[{}],"labels":[{}],"lines":[{}]}}"#,
+            segments.join(","),
+            labels.join(","),
+            lines.join(",")
+        )
+    }
+}
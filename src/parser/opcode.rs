@@ -1,17 +1,35 @@
-use super::directives::{ascii_lit, asciiz_lit, byte_lit, half_lit, segment, space, word_lit};
+use super::directives::{
+    ascii_lit, asciiz_lit, byte_lit, double_lit, float_lit, half_lit, segment, space, word_lit,
+};
 use super::instruction::{
-    branch_type, i_type, j_type, jr_type, li_ins, load_type, lui, move_ins, multi_branch, nop,
-    r_type, shift_type, syscall,
+    always_branch, branch_type, cop0_function, cop0_move_type, fp_branch_type, fp_compare_type,
+    fp_load_type, fp_move_type, fp_r_type, fp_unary_type, i_type, j_type, jalr_type, jr_type,
+    li_ins, load_type, lui, move_from_type, move_ins, move_to_type, mul_pseudo, muldiv_type,
+    multi_branch, nop, r_type, shift_type, syscall, var_shift_type, zero_branch_type,
 };
 use super::model::{Line, Opcode, Segment};
+use crate::{Register, ZERO};
 
-use nom::combinator::fail;
 use nom::error::{context, VerboseError};
 use nom::{bytes::complete::take_till, combinator::map_res, IResult};
 
-type InsParser = fn(&str, Opcode) -> IResult<&str, Line, VerboseError<&str>>;
+const MFHI: Opcode = Opcode::Funct(0x10);
+const MFLO: Opcode = Opcode::Funct(0x12);
+const BEQ: Opcode = Opcode::Op(0x04);
+const BNE: Opcode = Opcode::Op(0x05);
+const REGIMM: Opcode = Opcode::Op(0x01);
+const COP1: Opcode = Opcode::Op(0x11);
+
+// coprocessor 1 formats
+const FMT_S: u32 = 0x10;
+const FMT_D: u32 = 0x11;
+const FMT_W: u32 = 0x14;
 
-const NO_PARSER: InsParser = |input, _| context("No parser for instruction", fail)(input);
+/// Floating point compare conditions in the order of their encoding
+const FP_CONDITIONS: [&str; 16] = [
+    "f", "un", "eq", "ueq", "olt", "ult", "ole", "ule", "sf", "ngle", "seq", "ngl", "lt", "nge",
+    "le", "ngt",
+];
 
 /// Holds a parsed opcode and a nom parser that can parse its arguments and produce an Instruction
 /// object
@@ -49,6 +67,109 @@ impl InstructionParser {
     }
 }
 
+/// Creates a parser for floating point arithmetic, conversion and compare mnemonics which are
+/// made up of an operation and a format suffix such as `add.s` or `cvt.d.w`
+fn fp_opcode(word: &str) -> Option<InstructionParser> {
+    let (name, fmt) = word.rsplit_once('.')?;
+    let fmt = match fmt {
+        "s" => FMT_S,
+        "d" => FMT_D,
+        "w" => FMT_W,
+        _ => return None,
+    };
+
+    // compares encode their condition in the low bits of funct
+    if let Some(cond) = name.strip_prefix("c.") {
+        let funct = FP_CONDITIONS.iter().position(|c| *c == cond)? as u8 | 0x30;
+        if fmt == FMT_W {
+            return None;
+        }
+        return Some(InstructionParser::new(
+            Opcode::Funct(funct),
+            move |i, op| fp_compare_type(i, op, fmt),
+        ));
+    }
+
+    let (funct, unary) = match name {
+        "add" => (0x00, false),
+        "sub" => (0x01, false),
+        "mul" => (0x02, false),
+        "div" => (0x03, false),
+        "sqrt" => (0x04, true),
+        "abs" => (0x05, true),
+        "mov" => (0x06, true),
+        "neg" => (0x07, true),
+        "round.w" => (0x0c, true),
+        "trunc.w" => (0x0d, true),
+        "ceil.w" => (0x0e, true),
+        "floor.w" => (0x0f, true),
+        "cvt.s" if fmt != FMT_S => (0x20, true),
+        "cvt.d" if fmt != FMT_D => (0x21, true),
+        "cvt.w" if fmt != FMT_W => (0x24, true),
+        _ => return None,
+    };
+    // words can only be converted
+    if fmt == FMT_W && funct < 0x20 {
+        return None;
+    }
+    Some(if unary {
+        InstructionParser::new(Opcode::Funct(funct), move |i, op| fp_unary_type(i, op, fmt))
+    } else {
+        InstructionParser::new(Opcode::Funct(funct), move |i, op| fp_r_type(i, op, fmt))
+    })
+}
+
+/// Names a coprocessor 1 instruction
+fn cop1_name(fmt: u32, rt: u32, funct: u32) -> Option<&'static str> {
+    const SINGLE: [&str; 16] = [
+        "c.f.s", "c.un.s", "c.eq.s", "c.ueq.s", "c.olt.s", "c.ult.s", "c.ole.s", "c.ule.s",
+        "c.sf.s", "c.ngle.s", "c.seq.s", "c.ngl.s", "c.lt.s", "c.nge.s", "c.le.s", "c.ngt.s",
+    ];
+    const DOUBLE: [&str; 16] = [
+        "c.f.d", "c.un.d", "c.eq.d", "c.ueq.d", "c.olt.d", "c.ult.d", "c.ole.d", "c.ule.d",
+        "c.sf.d", "c.ngle.d", "c.seq.d", "c.ngl.d", "c.lt.d", "c.nge.d", "c.le.d", "c.ngt.d",
+    ];
+    match (fmt, funct) {
+        (0x00, _) => Some("mfc1"),
+        (0x04, _) => Some("mtc1"),
+        (0x08, _) if rt & 1 == 1 => Some("bc1t"),
+        (0x08, _) => Some("bc1f"),
+        (FMT_S, 0x30..=0x3f) => Some(SINGLE[funct as usize - 0x30]),
+        (FMT_D, 0x30..=0x3f) => Some(DOUBLE[funct as usize - 0x30]),
+        (FMT_S, 0x00) => Some("add.s"),
+        (FMT_D, 0x00) => Some("add.d"),
+        (FMT_S, 0x01) => Some("sub.s"),
+        (FMT_D, 0x01) => Some("sub.d"),
+        (FMT_S, 0x02) => Some("mul.s"),
+        (FMT_D, 0x02) => Some("mul.d"),
+        (FMT_S, 0x03) => Some("div.s"),
+        (FMT_D, 0x03) => Some("div.d"),
+        (FMT_S, 0x04) => Some("sqrt.s"),
+        (FMT_D, 0x04) => Some("sqrt.d"),
+        (FMT_S, 0x05) => Some("abs.s"),
+        (FMT_D, 0x05) => Some("abs.d"),
+        (FMT_S, 0x06) => Some("mov.s"),
+        (FMT_D, 0x06) => Some("mov.d"),
+        (FMT_S, 0x07) => Some("neg.s"),
+        (FMT_D, 0x07) => Some("neg.d"),
+        (FMT_S, 0x0c) => Some("round.w.s"),
+        (FMT_D, 0x0c) => Some("round.w.d"),
+        (FMT_S, 0x0d) => Some("trunc.w.s"),
+        (FMT_D, 0x0d) => Some("trunc.w.d"),
+        (FMT_S, 0x0e) => Some("ceil.w.s"),
+        (FMT_D, 0x0e) => Some("ceil.w.d"),
+        (FMT_S, 0x0f) => Some("floor.w.s"),
+        (FMT_D, 0x0f) => Some("floor.w.d"),
+        (FMT_D, 0x20) => Some("cvt.s.d"),
+        (FMT_W, 0x20) => Some("cvt.s.w"),
+        (FMT_S, 0x21) => Some("cvt.d.s"),
+        (FMT_W, 0x21) => Some("cvt.d.w"),
+        (FMT_S, 0x24) => Some("cvt.w.s"),
+        (FMT_D, 0x24) => Some("cvt.w.d"),
+        _ => None,
+    }
+}
+
 pub fn opcode_name(input: u32) -> Option<&'static str> {
     // early return if the instruction is a nop
     if input == 0 {
@@ -58,9 +179,13 @@ pub fn opcode_name(input: u32) -> Option<&'static str> {
     // copied from src/stages/decode.rs
     let op_mask = 0b11111100000000000000000000000000;
     let fn_mask = 0b00000000000000000000000000111111;
+    let rt_mask = 0b00000000000111110000000000000000;
+    let rs_mask = 0b00000011111000000000000000000000;
 
     let funct = input & fn_mask;
     let op = (input & op_mask) >> 26;
+    let rt = (input & rt_mask) >> 16;
+    let rs = (input & rs_mask) >> 21;
 
     let opcode = if op == 0 {
         Opcode::Funct(funct as u8)
@@ -73,33 +198,81 @@ pub fn opcode_name(input: u32) -> Option<&'static str> {
             0x00 => Some("sll"),
             0x02 => Some("srl"),
             0x03 => Some("sra"),
+            0x04 => Some("sllv"),
             0x06 => Some("srlv"),
+            0x07 => Some("srav"),
             0x08 => Some("jr"),
+            0x09 => Some("jalr"),
             0x0c => Some("syscall"),
+            0x10 => Some("mfhi"),
+            0x11 => Some("mthi"),
+            0x12 => Some("mflo"),
+            0x13 => Some("mtlo"),
+            0x18 => Some("mult"),
+            0x19 => Some("multu"),
             0x1a => Some("div"),
             0x1b => Some("divu"),
             0x20 => Some("add"),
             0x21 => Some("addu"),
             0x22 => Some("sub"),
+            0x23 => Some("subu"),
             0x24 => Some("and"),
             0x25 => Some("or"),
             0x26 => Some("xor"),
             0x27 => Some("nor"),
             0x2a => Some("slt"),
+            0x2b => Some("sltu"),
             _ => None,
         },
         Opcode::Op(op) => match op {
+            // REGIMM instructions are selected by their rt field
+            0x01 => match rt {
+                0x00 => Some("bltz"),
+                0x01 => Some("bgez"),
+                0x10 => Some("bltzal"),
+                0x11 => Some("bgezal"),
+                _ => None,
+            },
             0x02 => Some("j"),
             0x03 => Some("jal"),
             0x04 => Some("beq"),
             0x05 => Some("bne"),
+            0x06 => Some("blez"),
+            0x07 => Some("bgtz"),
             0x08 => Some("addi"),
             0x09 => Some("addiu"),
+            0x0a => Some("slti"),
+            0x0b => Some("sltiu"),
             0x0c => Some("andi"),
             0x0d => Some("ori"),
+            0x0e => Some("xori"),
             0x0f => Some("lui"),
+            0x10 => match rs {
+                0x00 => Some("mfc0"),
+                0x04 => Some("mtc0"),
+                0x10 => match funct {
+                    0x01 => Some("tlbr"),
+                    0x02 => Some("tlbwi"),
+                    0x06 => Some("tlbwr"),
+                    0x08 => Some("tlbp"),
+                    0x18 => Some("eret"),
+                    _ => None,
+                },
+                _ => None,
+            },
+            0x11 => cop1_name(rs, rt, funct),
+            0x20 => Some("lb"),
+            0x21 => Some("lh"),
             0x23 => Some("lw"),
+            0x24 => Some("lbu"),
+            0x25 => Some("lhu"),
+            0x28 => Some("sb"),
+            0x29 => Some("sh"),
             0x2b => Some("sw"),
+            0x31 => Some("lwc1"),
+            0x35 => Some("ldc1"),
+            0x39 => Some("swc1"),
+            0x3d => Some("sdc1"),
             _ => None,
         },
     }
@@ -113,34 +286,140 @@ pub fn opcode(input: &str) -> IResult<&str, InstructionParser, VerboseError<&str
             |word: &str| match word.to_lowercase().trim() {
                 "add" => Ok(InstructionParser::new(Opcode::Funct(0x20), r_type)),
                 "sub" => Ok(InstructionParser::new(Opcode::Funct(0x22), r_type)),
+                "subu" => Ok(InstructionParser::new(Opcode::Funct(0x23), r_type)),
                 "addi" => Ok(InstructionParser::new(Opcode::Op(0x08), i_type)),
                 "addiu" => Ok(InstructionParser::new(Opcode::Op(0x09), i_type)),
                 "addu" => Ok(InstructionParser::new(Opcode::Funct(0x21), r_type)),
                 "and" => Ok(InstructionParser::new(Opcode::Funct(0x24), r_type)),
                 "andi" => Ok(InstructionParser::new(Opcode::Op(0x0c), i_type)),
-                "beq" => Ok(InstructionParser::new(Opcode::Op(0x04), branch_type)),
-                "bne" => Ok(InstructionParser::new(Opcode::Op(0x05), branch_type)),
-                "blt" => Ok(InstructionParser::pseudo(|i| multi_branch(i, true, false))),
-                "bgt" => Ok(InstructionParser::pseudo(|i| multi_branch(i, false, false))),
-                "ble" => Ok(InstructionParser::pseudo(|i| multi_branch(i, true, true))),
-                "bge" => Ok(InstructionParser::pseudo(|i| multi_branch(i, false, true))),
-                "div" => Ok(InstructionParser::new(Opcode::Funct(0x1a), NO_PARSER)),
-                "divu" => Ok(InstructionParser::new(Opcode::Funct(0x1b), NO_PARSER)),
+                "beq" => Ok(InstructionParser::new(BEQ, branch_type)),
+                "bne" => Ok(InstructionParser::new(BNE, branch_type)),
+                "beqz" => Ok(InstructionParser::new(BEQ, |i, op| {
+                    zero_branch_type(i, op, ZERO)
+                })),
+                "bnez" => Ok(InstructionParser::new(BNE, |i, op| {
+                    zero_branch_type(i, op, ZERO)
+                })),
+                "bltz" => Ok(InstructionParser::new(REGIMM, |i, op| {
+                    zero_branch_type(i, op, 0x00.into())
+                })),
+                "bgez" => Ok(InstructionParser::new(REGIMM, |i, op| {
+                    zero_branch_type(i, op, 0x01.into())
+                })),
+                "bltzal" => Ok(InstructionParser::new(REGIMM, |i, op| {
+                    zero_branch_type(i, op, 0x10.into())
+                })),
+                "bgezal" => Ok(InstructionParser::new(REGIMM, |i, op| {
+                    zero_branch_type(i, op, 0x11.into())
+                })),
+                "blez" => Ok(InstructionParser::new(Opcode::Op(0x06), |i, op| {
+                    zero_branch_type(i, op, ZERO)
+                })),
+                "bgtz" => Ok(InstructionParser::new(Opcode::Op(0x07), |i, op| {
+                    zero_branch_type(i, op, ZERO)
+                })),
+                "b" => Ok(InstructionParser::new(BEQ, |i, op| {
+                    always_branch(i, op, ZERO)
+                })),
+                "bal" => Ok(InstructionParser::new(REGIMM, |i, op| {
+                    always_branch(i, op, 0x11.into())
+                })),
+                "blt" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, true, false, false)
+                })),
+                "bgt" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, false, false, false)
+                })),
+                "ble" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, true, true, false)
+                })),
+                "bge" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, false, true, false)
+                })),
+                "bltu" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, true, false, true)
+                })),
+                "bgtu" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, false, false, true)
+                })),
+                "bleu" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, true, true, true)
+                })),
+                "bgeu" => Ok(InstructionParser::pseudo(|i| {
+                    multi_branch(i, false, true, true)
+                })),
+                "div" => Ok(InstructionParser::new(Opcode::Funct(0x1a), |i, op| {
+                    muldiv_type(i, op, MFLO)
+                })),
+                "divu" => Ok(InstructionParser::new(Opcode::Funct(0x1b), |i, op| {
+                    muldiv_type(i, op, MFLO)
+                })),
+                "mult" => Ok(InstructionParser::new(Opcode::Funct(0x18), |i, op| {
+                    muldiv_type(i, op, MFLO)
+                })),
+                "multu" => Ok(InstructionParser::new(Opcode::Funct(0x19), |i, op| {
+                    muldiv_type(i, op, MFLO)
+                })),
+                "mul" => Ok(InstructionParser::pseudo(|i| {
+                    mul_pseudo(i, Opcode::Funct(0x18), MFLO)
+                })),
+                "rem" => Ok(InstructionParser::pseudo(|i| {
+                    mul_pseudo(i, Opcode::Funct(0x1a), MFHI)
+                })),
+                "remu" => Ok(InstructionParser::pseudo(|i| {
+                    mul_pseudo(i, Opcode::Funct(0x1b), MFHI)
+                })),
+                "mfhi" => Ok(InstructionParser::new(MFHI, move_from_type)),
+                "mflo" => Ok(InstructionParser::new(MFLO, move_from_type)),
+                "mthi" => Ok(InstructionParser::new(Opcode::Funct(0x11), move_to_type)),
+                "mtlo" => Ok(InstructionParser::new(Opcode::Funct(0x13), move_to_type)),
                 "j" => Ok(InstructionParser::new(Opcode::Op(0x02), j_type)),
                 "jal" => Ok(InstructionParser::new(Opcode::Op(0x03), j_type)),
                 "jr" => Ok(InstructionParser::new(Opcode::Funct(0x08), jr_type)),
+                "jalr" => Ok(InstructionParser::new(Opcode::Funct(0x09), jalr_type)),
+                "lb" => Ok(InstructionParser::new(Opcode::Op(0x20), load_type)),
+                "lh" => Ok(InstructionParser::new(Opcode::Op(0x21), load_type)),
                 "lw" => Ok(InstructionParser::new(Opcode::Op(0x23), load_type)),
+                "lbu" => Ok(InstructionParser::new(Opcode::Op(0x24), load_type)),
+                "lhu" => Ok(InstructionParser::new(Opcode::Op(0x25), load_type)),
+                "sb" => Ok(InstructionParser::new(Opcode::Op(0x28), load_type)),
+                "sh" => Ok(InstructionParser::new(Opcode::Op(0x29), load_type)),
                 "sw" => Ok(InstructionParser::new(Opcode::Op(0x2b), load_type)),
                 "lui" => Ok(InstructionParser::new(Opcode::Op(0x0f), lui)),
-                "slt" => Ok(InstructionParser::new(Opcode::Funct(0x2a), NO_PARSER)),
+                "lwc1" | "l.s" => Ok(InstructionParser::new(Opcode::Op(0x31), fp_load_type)),
+                "ldc1" | "l.d" => Ok(InstructionParser::new(Opcode::Op(0x35), fp_load_type)),
+                "swc1" | "s.s" => Ok(InstructionParser::new(Opcode::Op(0x39), fp_load_type)),
+                "sdc1" | "s.d" => Ok(InstructionParser::new(Opcode::Op(0x3d), fp_load_type)),
+                "mfc0" => Ok(InstructionParser::pseudo(|i| cop0_move_type(i, 0x00))),
+                "mtc0" => Ok(InstructionParser::pseudo(|i| cop0_move_type(i, 0x04))),
+                "eret" => Ok(InstructionParser::pseudo(|i| cop0_function(i, 0x18))),
+                "tlbr" => Ok(InstructionParser::pseudo(|i| cop0_function(i, 0x01))),
+                "tlbwi" => Ok(InstructionParser::pseudo(|i| cop0_function(i, 0x02))),
+                "tlbwr" => Ok(InstructionParser::pseudo(|i| cop0_function(i, 0x06))),
+                "tlbp" => Ok(InstructionParser::pseudo(|i| cop0_function(i, 0x08))),
+                "mfc1" => Ok(InstructionParser::pseudo(|i| fp_move_type(i, 0x00))),
+                "mtc1" => Ok(InstructionParser::pseudo(|i| fp_move_type(i, 0x04))),
+                "bc1f" => Ok(InstructionParser::new(COP1, |i, op| {
+                    fp_branch_type(i, op, 0)
+                })),
+                "bc1t" => Ok(InstructionParser::new(COP1, |i, op| {
+                    fp_branch_type(i, op, 1)
+                })),
+                "slt" => Ok(InstructionParser::new(Opcode::Funct(0x2a), r_type)),
+                "sltu" => Ok(InstructionParser::new(Opcode::Funct(0x2b), r_type)),
+                "slti" => Ok(InstructionParser::new(Opcode::Op(0x0a), i_type)),
+                "sltiu" => Ok(InstructionParser::new(Opcode::Op(0x0b), i_type)),
                 "ori" => Ok(InstructionParser::new(Opcode::Op(0x0d), i_type)),
                 "or" => Ok(InstructionParser::new(Opcode::Funct(0x25), r_type)),
                 "xor" => Ok(InstructionParser::new(Opcode::Funct(0x26), r_type)),
+                "xori" => Ok(InstructionParser::new(Opcode::Op(0x0e), i_type)),
                 "nor" => Ok(InstructionParser::new(Opcode::Funct(0x27), r_type)),
                 "sll" => Ok(InstructionParser::new(Opcode::Funct(0x0), shift_type)),
                 "srl" => Ok(InstructionParser::new(Opcode::Funct(0x2), shift_type)),
                 "sra" => Ok(InstructionParser::new(Opcode::Funct(0x3), shift_type)),
-                "srlv" => Ok(InstructionParser::new(Opcode::Funct(0x6), r_type)),
+                "sllv" => Ok(InstructionParser::new(Opcode::Funct(0x4), var_shift_type)),
+                "srlv" => Ok(InstructionParser::new(Opcode::Funct(0x6), var_shift_type)),
+                "srav" => Ok(InstructionParser::new(Opcode::Funct(0x7), var_shift_type)),
                 "move" => Ok(InstructionParser::pseudo(move_ins)),
                 "li" => Ok(InstructionParser::pseudo(li_ins)),
                 "la" => Ok(InstructionParser::pseudo(li_ins)),
@@ -149,13 +428,78 @@ pub fn opcode(input: &str) -> IResult<&str, InstructionParser, VerboseError<&str
                 ".word" => Ok(InstructionParser::pseudo(word_lit)),
                 ".half" => Ok(InstructionParser::pseudo(half_lit)),
                 ".byte" => Ok(InstructionParser::pseudo(byte_lit)),
+                ".float" => Ok(InstructionParser::pseudo(float_lit)),
+                ".double" => Ok(InstructionParser::pseudo(double_lit)),
                 ".space" => Ok(InstructionParser::pseudo(space)),
                 ".ascii" => Ok(InstructionParser::pseudo(ascii_lit)),
                 ".asciiz" => Ok(InstructionParser::pseudo(asciiz_lit)),
                 ".text" => Ok(InstructionParser::pseudo(|i| segment(i, Segment::Text))),
                 ".data" => Ok(InstructionParser::pseudo(|i| segment(i, Segment::Data))),
-                _ => Err(()),
+                ".ktext" => Ok(InstructionParser::pseudo(|i| segment(i, Segment::KText))),
+                ".kdata" => Ok(InstructionParser::pseudo(|i| segment(i, Segment::KData))),
+                word => fp_opcode(word).ok_or(()),
             },
         ),
     )(input)
 }
+
+/// Formats an instruction the way it would be written in assembly
+///
+/// Branch and jump targets are computed from `pc` and shown as absolute addresses
+pub fn disassemble(input: u32, pc: u32) -> String {
+    let name = match opcode_name(input) {
+        Some(name) => name,
+        None => return format!(".word 0x{:08X}", input),
+    };
+
+    let reg = |shift: u32| format!("${}", Register::from((input >> shift) & 0x1F).name());
+    let fpr = |shift: u32| format!("$f{}", (input >> shift) & 0x1F);
+    let (rs, rt, rd) = (reg(21), reg(16), reg(11));
+    let (fs, ft, fd) = (fpr(11), fpr(16), fpr(6));
+    let imm = input as u16 as i16;
+    let shamt = (input >> 6) & 0x1F;
+    let branch = format!(
+        "0x{:X}",
+        pc.wrapping_add(4).wrapping_add((imm as i32 as u32) << 2)
+    );
+    let jump = format!(
+        "0x{:X}",
+        (pc.wrapping_add(4) & 0xF000_0000) | ((input & 0x03FF_FFFF) << 2)
+    );
+
+    let operands = match name {
+        "nop" | "syscall" | "eret" | "tlbr" | "tlbwi" | "tlbwr" | "tlbp" => String::new(),
+        "sll" | "srl" | "sra" => format!("{}, {}, {}", rd, rt, shamt),
+        "sllv" | "srlv" | "srav" => format!("{}, {}, {}", rd, rt, rs),
+        "jr" | "mthi" | "mtlo" => rs,
+        "jalr" => format!("{}, {}", rd, rs),
+        "mfhi" | "mflo" => rd,
+        "mult" | "multu" | "div" | "divu" => format!("{}, {}", rs, rt),
+        "j" | "jal" => jump,
+        "beq" | "bne" => format!("{}, {}, {}", rs, rt, branch),
+        "bltz" | "bgez" | "bltzal" | "bgezal" | "blez" | "bgtz" => format!("{}, {}", rs, branch),
+        "bc1f" | "bc1t" => branch,
+        "addi" | "addiu" | "slti" | "sltiu" => format!("{}, {}, {}", rt, rs, imm),
+        "andi" | "ori" | "xori" => format!("{}, {}, 0x{:X}", rt, rs, imm as u16),
+        "lui" => format!("{}, 0x{:X}", rt, imm as u16),
+        "lb" | "lh" | "lw" | "lbu" | "lhu" | "sb" | "sh" | "sw" => {
+            format!("{}, {}({})", rt, imm, rs)
+        }
+        "lwc1" | "ldc1" | "swc1" | "sdc1" => format!("{}, {}({})", ft, imm, rs),
+        "mfc0" | "mtc0" => format!("{}, ${}", rt, (input >> 11) & 0x1F),
+        "mfc1" | "mtc1" => format!("{}, {}", rt, fs),
+        _ if name.starts_with("c.") => format!("{}, {}", fs, ft),
+        // the remaining coprocessor 1 instructions take two operands if they only read one
+        _ if input >> 26 == 0x11 => match input & 0x3F {
+            0x00..=0x03 => format!("{}, {}, {}", fd, fs, ft),
+            _ => format!("{}, {}", fd, fs),
+        },
+        _ => format!("{}, {}, {}", rd, rs, rt),
+    };
+
+    if operands.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", name, operands)
+    }
+}
@@ -9,6 +9,8 @@ use nom::{
 
 use crate::Register;
 
+use super::dec;
+
 pub fn register_name(input: &str) -> IResult<&str, Register, VerboseError<&str>> {
     context(
         "Unknown register",
@@ -24,3 +26,36 @@ pub fn register(input: &str) -> IResult<&str, Register, VerboseError<&str>> {
     let (input, reg) = register_name(input)?;
     Ok((input, reg))
 }
+
+/// Parses a floating point register `$f0` through `$f31`
+pub fn fp_register(input: &str) -> IResult<&str, Register, VerboseError<&str>> {
+    let (input, _) = context(
+        "Expected '$f' to prepend floating point register",
+        tag("$f"),
+    )(input)?;
+    context(
+        "Unknown floating point register",
+        map_res(dec, |n: u32| {
+            if n < 32 {
+                Ok(Register::from(n))
+            } else {
+                Err(())
+            }
+        }),
+    )(input)
+}
+
+/// Parses a coprocessor 0 register by number such as `$12` for Status
+pub fn cop0_register(input: &str) -> IResult<&str, Register, VerboseError<&str>> {
+    let (input, _) = context("Expected '$' to prepend register", tag("$"))(input)?;
+    context(
+        "Unknown coprocessor 0 register",
+        map_res(dec, |n: u32| {
+            if n < 32 {
+                Ok(Register::from(n))
+            } else {
+                Err(())
+            }
+        }),
+    )(input)
+}
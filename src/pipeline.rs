@@ -1,37 +1,164 @@
+use crate::cache::{CacheConfig, Caches};
+use crate::hazard::{self, Bubble, Stall};
+use crate::mmu::Mmu;
+use crate::parser::model::MMIO_BASE;
+use crate::predictor::{BranchPredictor, Predictor};
 use crate::stages;
 use crate::stages::execute::IdEx;
 use crate::stages::inputs::*;
 use crate::stages::writeback::PipelineOutput;
+use crate::stats::Stats;
 use crate::syscall::{handle_syscall, Syscall};
-use crate::{Memory, Register, RegisterFile, ZERO};
+use crate::{Exception, Fault, Memory, Register, RegisterFile, ZERO};
 
 use anyhow::Result;
 
-/// This is a simple function to single step the CPU.
+/// Steps the CPU forward an entire instruction at a time without pipelining it
 ///
-/// Eventually this should pipeline data instead of doing an entire instruction each cycle but that
-/// can't be done until we fix all the data and control hazard issues.
-pub fn _single_cycle(pc: &mut u32, regs: &mut RegisterFile, mem: &mut Memory) -> Option<Syscall> {
-    // should never forward
-    let fwd_unit = ForwardingUnit {
-        ex_mem: (false, ZERO, 0),
-        mem_wb: (false, ZERO, 0),
-    };
+/// Every stage works on the same instruction so nothing ever needs to be forwarded or stalled and
+/// the returned state holds that instruction in every stage. With delay slots enabled the
+/// redirect of a branch or jump is held in the previous state until the next instruction has run.
+pub fn single_cycle(
+    pc: &mut u32,
+    regs: &mut RegisterFile,
+    mem: &mut Memory,
+    state: PipelineState,
+    config: PipelineConfig,
+    hw: &mut Hardware,
+    stats: &mut Stats,
+) -> Result<(PipelineState, Option<Syscall>)> {
+    stats.cycles += 1;
+    if interrupt_pending(*pc, regs, mem, config) {
+        return Err(Fault::new(Exception::Interrupt, *pc, 0).into());
+    }
+    // the caches are still looked up to count hits and misses but a miss never stalls
+    hw.caches.instruction.access(*pc, false);
+    let if_id = stages::fetch(pc, mem, &mut hw.mmu)?;
+    let id_ex = stages::decode(regs, if_id.clone())?;
+    let ex_mem = stages::execute(id_ex.clone(), ForwardingUnit::disabled(), config)?;
+    if (ex_mem.read || ex_mem.write) && ex_mem.alu_result < MMIO_BASE {
+        hw.caches.data.access(ex_mem.alu_result, ex_mem.write);
+    }
+    stats.memory(&ex_mem);
+    let mem_wb = stages::memory(mem, &mut hw.mmu, ex_mem.clone(), ForwardingUnit::disabled())?;
 
-    let if_id = stages::fetch(pc, mem);
-    let id_ex = stages::decode(regs, if_id.unwrap());
-    let ex_mem = stages::execute(id_ex.unwrap(), fwd_unit);
-    let mem_wb = stages::memory(pc, mem, ex_mem.unwrap()).unwrap();
-    let pipe_out = stages::writeback(regs, mem_wb);
+    if let Some(exception) = mem_wb.exception {
+        *pc = mem_wb.pc;
+        return Err(Fault::new(exception, mem_wb.pc, mem_wb.instruction).into());
+    }
+    let pipe_out = stages::writeback(regs, &mut hw.mmu, mem_wb.clone());
+    stats.retire(&pipe_out);
 
-    // pretend we jumped to the syscall vector
-    if pipe_out.syscall {
-        match handle_syscall(regs, mem) {
-            Ok(syscall) => Some(syscall),
-            Err(e) => Some(Syscall::Error(format!("{}", e))),
-        }
+    let redirect = if config.delay_slot {
+        state.ex_mem.redirect()
+    } else {
+        ex_mem.redirect()
+    };
+    if let Some(target) = redirect {
+        *pc = target;
+    }
+
+    let syscall = if pipe_out.syscall {
+        run_syscall(regs, mem, &mut hw.mmu, &pipe_out)?
     } else {
         None
+    };
+
+    Ok((
+        PipelineState {
+            if_id,
+            id_ex,
+            ex_mem,
+            mem_wb,
+            pipe_out,
+            ..Default::default()
+        },
+        syscall,
+    ))
+}
+
+/// Stage branches and jumps are resolved in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BranchStage {
+    Decode,
+    Execute,
+    #[default]
+    Memory,
+}
+
+impl BranchStage {
+    pub const ALL: [BranchStage; 3] = [Self::Decode, Self::Execute, Self::Memory];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Decode => "ID",
+            Self::Execute => "EX",
+            Self::Memory => "MEM",
+        }
+    }
+}
+
+/// Options that change the microarchitecture the program runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// Run every instruction to completion in a single cycle instead of pipelining them
+    pub single_cycle: bool,
+    /// Forward results to later instructions, without it every data hazard stalls until the
+    /// result has been written back
+    pub forwarding: bool,
+    /// Stage the pc is redirected at the end of, every younger instruction is squashed
+    pub branch_stage: BranchStage,
+    /// Execute the instruction after a branch or jump whether or not it is taken
+    pub delay_slot: bool,
+    /// How the direction of conditional branches is predicted when they are fetched
+    pub predictor: Predictor,
+    /// Number of entries in the branch history table used by the dynamic predictors
+    pub bht_entries: u32,
+    /// Number of entries in the branch target buffer, without one only conditional branches are
+    /// predicted and their target is decoded as they are fetched
+    pub btb_entries: u32,
+    pub icache: CacheConfig,
+    pub dcache: CacheConfig,
+    /// Translate user addresses through the TLB
+    pub mmu: bool,
+    /// Number of entries in the TLB
+    pub tlb_entries: u32,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            single_cycle: false,
+            forwarding: true,
+            branch_stage: BranchStage::default(),
+            delay_slot: false,
+            predictor: Predictor::default(),
+            bht_entries: 16,
+            btb_entries: 0,
+            icache: CacheConfig::default(),
+            dcache: CacheConfig::default(),
+            mmu: false,
+            tlb_entries: 16,
+        }
+    }
+}
+
+/// Hardware next to the pipeline that keeps state of its own across cycles
+#[derive(Debug, Clone, Default)]
+pub struct Hardware {
+    pub predictor: BranchPredictor,
+    pub caches: Caches,
+    pub mmu: Mmu,
+}
+
+impl Hardware {
+    /// Creates the hardware described by `config` with every table, cache and the TLB empty
+    pub fn new(config: PipelineConfig) -> Self {
+        Self {
+            predictor: BranchPredictor::new(config),
+            caches: Caches::new(config),
+            mmu: Mmu::new(config),
+        }
     }
 }
 
@@ -42,12 +169,110 @@ pub struct PipelineState {
     pub ex_mem: ExMem,
     pub mem_wb: MemWb,
     pub pipe_out: PipelineOutput,
+    /// Why the instruction in decode was held in place during the last cycle
+    pub stall: Option<Stall>,
+    /// Cycles left until the block missing from the instruction cache arrives
+    pub fetch_wait: Option<u32>,
+    /// Cycles left until the block the instruction in the memory stage missed on arrives
+    pub mem_wait: Option<u32>,
 }
 
 #[derive(Clone, Copy)]
 pub struct ForwardingUnit {
     pub ex_mem: (bool, Register, u32),
     pub mem_wb: (bool, Register, u32),
+    pub hi_lo_ex_mem: (bool, u32, u32),
+    pub hi_lo_mem_wb: (bool, u32, u32),
+    pub fp_ex_mem: (bool, Register, u64, bool),
+    pub fp_mem_wb: (bool, Register, u64, bool),
+    pub fcc_ex_mem: (bool, u8),
+    pub fcc_mem_wb: (bool, u8),
+    pub cp0_ex_mem: (bool, Register, u32),
+    pub cp0_mem_wb: (bool, Register, u32),
+}
+
+impl ForwardingUnit {
+    /// Builds a forwarding unit that never forwards
+    pub fn disabled() -> Self {
+        Self {
+            ex_mem: (false, ZERO, 0),
+            mem_wb: (false, ZERO, 0),
+            hi_lo_ex_mem: (false, 0, 0),
+            hi_lo_mem_wb: (false, 0, 0),
+            fp_ex_mem: (false, ZERO, 0, false),
+            fp_mem_wb: (false, ZERO, 0, false),
+            fcc_ex_mem: (false, 0),
+            fcc_mem_wb: (false, 0),
+            cp0_ex_mem: (false, ZERO, 0),
+            cp0_mem_wb: (false, ZERO, 0),
+        }
+    }
+
+    /// Builds a forwarding unit that forwards from the instructions in the memory and writeback
+    /// stages
+    pub fn new(state: &PipelineState) -> Self {
+        Self {
+            ex_mem: (
+                state.ex_mem.reg_write,
+                state.ex_mem.write_register,
+                state.ex_mem.alu_result,
+            ),
+            mem_wb: (
+                state.mem_wb.reg_write,
+                state.mem_wb.write_register,
+                if state.mem_wb.mem_to_reg {
+                    state.mem_wb.mem_data
+                } else {
+                    state.mem_wb.alu_data
+                },
+            ),
+            hi_lo_ex_mem: (state.ex_mem.hi_lo_write, state.ex_mem.hi, state.ex_mem.lo),
+            hi_lo_mem_wb: (state.mem_wb.hi_lo_write, state.mem_wb.hi, state.mem_wb.lo),
+            fp_ex_mem: (
+                state.ex_mem.fp_write,
+                state.ex_mem.write_register,
+                state.ex_mem.fp_result,
+                state.ex_mem.fp_double,
+            ),
+            fp_mem_wb: (
+                state.mem_wb.fp_write,
+                state.mem_wb.write_register,
+                state.mem_wb.fp_data,
+                state.mem_wb.fp_double,
+            ),
+            fcc_ex_mem: (state.ex_mem.fcc_write, state.ex_mem.fcc),
+            fcc_mem_wb: (state.mem_wb.fcc_write, state.mem_wb.fcc),
+            cp0_ex_mem: (
+                state.ex_mem.cp0_write,
+                state.ex_mem.write_register,
+                state.ex_mem.alu_result,
+            ),
+            cp0_mem_wb: (
+                state.mem_wb.cp0_write,
+                state.mem_wb.write_register,
+                state.mem_wb.alu_data,
+            ),
+        }
+    }
+
+    /// Builds a forwarding unit for branches resolved in decode
+    ///
+    /// Only results computed by the ALU of the instruction in the memory stage are ready, the
+    /// instruction in writeback has already written its result to the register file
+    fn decode(state: &PipelineState) -> Self {
+        let ex_mem = &state.ex_mem;
+        let ready = !ex_mem.read;
+        Self {
+            ex_mem: (
+                ex_mem.reg_write && ready,
+                ex_mem.write_register,
+                ex_mem.alu_result,
+            ),
+            fcc_ex_mem: (ex_mem.fcc_write, ex_mem.fcc),
+            cp0_ex_mem: (ex_mem.cp0_write, ex_mem.write_register, ex_mem.alu_result),
+            ..Self::disabled()
+        }
+    }
 }
 
 /// Steps the machine forward in a pipelined manner.
@@ -59,100 +284,328 @@ pub fn pipe_cycle(
     regs: &mut RegisterFile,
     mem: &mut Memory,
     state: PipelineState,
+    config: PipelineConfig,
+    hw: &mut Hardware,
+    stats: &mut Stats,
 ) -> Result<(PipelineState, Option<Syscall>)> {
-    // contruct forwarding unit
-    let fwd_unit = ForwardingUnit {
-        ex_mem: (
-            state.ex_mem.reg_write,
-            state.ex_mem.write_register,
-            state.ex_mem.alu_result,
-        ),
-        mem_wb: (
-            state.mem_wb.reg_write,
-            state.mem_wb.write_register,
-            if state.mem_wb.mem_to_reg {
-                state.mem_wb.mem_data
-            } else {
-                state.mem_wb.alu_data
-            },
-        ),
+    stats.cycles += 1;
+    let fwd_unit = if config.forwarding {
+        ForwardingUnit::new(&state)
+    } else {
+        ForwardingUnit::disabled()
     };
 
-    let pipe_out = stages::writeback(regs, state.mem_wb);
+    // take exceptions once every older instruction has written back so they are precise, every
+    // younger instruction is still in an earlier stage and is discarded by the caller
+    if let Some(exception) = state.mem_wb.exception {
+        *pc = state.mem_wb.pc;
+        return Err(Fault::new(exception, state.mem_wb.pc, state.mem_wb.instruction).into());
+    }
+
+    // a data cache miss holds every instruction in place until the block has arrived, the
+    // instruction in writeback waits as well so its result can still be forwarded. Device
+    // registers are never cached
+    if (state.ex_mem.read || state.ex_mem.write) && state.ex_mem.alu_result < MMIO_BASE {
+        let wait = match state.mem_wait {
+            Some(wait) => wait,
+            None => hw
+                .caches
+                .data
+                .access(state.ex_mem.alu_result, state.ex_mem.write),
+        };
+        if wait > 0 {
+            hw.caches.data.stall();
+            stats.stall(Stall::DataMiss);
+            return Ok((
+                PipelineState {
+                    pipe_out: PipelineOutput {
+                        bubble: Some(Bubble::Stall(Stall::DataMiss)),
+                        ..Default::default()
+                    },
+                    stall: Some(Stall::DataMiss),
+                    mem_wait: Some(wait - 1),
+                    ..state
+                },
+                None,
+            ));
+        }
+    }
+
+    let pipe_out = stages::writeback(regs, &mut hw.mmu, state.mem_wb.clone());
+    stats.retire(&pipe_out);
 
     // pretend we jumped to the syscall vector
     if pipe_out.syscall {
-        let syscall =
-            Some(handle_syscall(regs, mem).unwrap_or_else(|e| Syscall::Error(format!("{}", e))));
+        stats.stall(Stall::Syscall);
+        let syscall = run_syscall(regs, mem, &mut hw.mmu, &pipe_out)?;
         // stall in case of syscall
         // TODO: Maybe not the best solution but ¯\_(ツ)_/¯
         return Ok((
             PipelineState {
                 pipe_out,
-                mem_wb: MemWb::default(),
+                mem_wb: MemWb {
+                    bubble: Some(Bubble::Stall(Stall::Syscall)),
+                    ..Default::default()
+                },
+                stall: Some(Stall::Syscall),
                 ..state
             },
             syscall,
         ));
     }
 
-    let mem_wb = stages::memory(pc, mem, state.ex_mem.clone())?;
+    // stores read their data in the memory stage so it can still be forwarded from writeback
+    let mem_fwd = ForwardingUnit {
+        mem_wb: fwd_unit.mem_wb,
+        fp_mem_wb: fwd_unit.fp_mem_wb,
+        ..ForwardingUnit::disabled()
+    };
+    stats.memory(&state.ex_mem);
+    let mem_wb = stages::memory(mem, &mut hw.mmu, state.ex_mem.clone(), mem_fwd)?;
+    let ex_mem = stages::execute(state.id_ex.clone(), fwd_unit, config)?;
 
-    let ex_mem = stages::execute(state.id_ex.clone(), fwd_unit)?;
+    // mispredicted branches resolved in the memory or execute stage redirect the pc once that
+    // stage is done, every instruction fetched after them except the delay slot is on the wrong
+    // path
+    let resolved = match config.branch_stage {
+        BranchStage::Memory => resolve(&state.ex_mem, config, &mut hw.predictor, stats),
+        BranchStage::Execute => resolve(&ex_mem, config, &mut hw.predictor, stats),
+        BranchStage::Decode => None,
+    };
+    let on_path = |ins_pc: u32| match resolved {
+        Some((branch, _)) => config.delay_slot && ins_pc == branch.wrapping_add(4),
+        None => true,
+    };
+
+    let mut next = PipelineState {
+        if_id: state.if_id.clone(),
+        id_ex: IdEx {
+            bubble: Some(Bubble::Flush),
+            ..Default::default()
+        },
+        ex_mem,
+        mem_wb,
+        pipe_out,
+        stall: None,
+        fetch_wait: state.fetch_wait,
+        mem_wait: None,
+    };
+    if config.branch_stage == BranchStage::Memory && !on_path(next.ex_mem.pc) {
+        next.ex_mem = ExMem {
+            bubble: Some(Bubble::Flush),
+            ..Default::default()
+        };
+    }
 
     // stall in case of syscall
     // TODO: Maybe not the best solution but ¯\_(ツ)_/¯
-    if ex_mem.syscall || mem_wb.syscall {
-        return Ok((
-            PipelineState {
-                if_id: state.if_id,
-                id_ex: IdEx::default(),
-                ex_mem,
-                mem_wb,
-                pipe_out,
-            },
-            None,
-        ));
-    }
-    let id_ex = stages::decode(regs, state.if_id.clone())?;
-    // hazard detector
-    if state.id_ex.mem_read {
-        if state.id_ex.rt == id_ex.rs {
-            return Ok((
-                PipelineState {
-                    if_id: state.if_id,
-                    id_ex: IdEx::default(),
-                    ex_mem,
-                    mem_wb,
-                    pipe_out,
-                },
-                None,
-            ));
+    let syscall_stall = next.ex_mem.syscall || next.mem_wb.syscall;
+
+    if !on_path(state.if_id.pc) {
+        // squash the instruction being decoded
+        next.if_id = flushed();
+    } else if syscall_stall {
+        next.stall = Some(Stall::Syscall);
+    } else {
+        let id_ex = stages::decode(regs, state.if_id.clone())?;
+        next.stall = hazard::detect(&state, &id_ex, config);
+        if next.stall.is_none() {
+            next.id_ex = id_ex;
+            // the instruction after a branch resolved in an older stage was fetched before the
+            // branch resolved, anything fetched now is on the wrong path
+            next.if_id = match resolved {
+                Some(_) => flushed(),
+                None => {
+                    // an instruction cache miss leaves fetch idle until the block has arrived
+                    let wait = match state.fetch_wait {
+                        Some(wait) => wait,
+                        None => hw.caches.instruction.access(*pc, false),
+                    };
+                    if wait > 0 {
+                        hw.caches.instruction.stall();
+                        stats.stall(Stall::InstructionMiss);
+                        next.fetch_wait = Some(wait - 1);
+                        IfId {
+                            // the target of a branch is fetched once its delay slot arrives
+                            prediction: state.if_id.prediction.filter(|_| config.delay_slot),
+                            bubble: Some(Bubble::Stall(Stall::InstructionMiss)),
+                            ..Default::default()
+                        }
+                    } else {
+                        next.fetch_wait = None;
+                        fetch(pc, regs, mem, &state, config, hw)?
+                    }
+                }
+            };
+
+            // branches resolved in decode compare their operands ahead of the ALU
+            if config.branch_stage == BranchStage::Decode && (next.id_ex.branch || next.id_ex.jump)
+            {
+                let fwd_unit = if config.forwarding {
+                    ForwardingUnit::decode(&state)
+                } else {
+                    ForwardingUnit::disabled()
+                };
+                let early = stages::execute(next.id_ex.clone(), fwd_unit, config)?;
+                if let Some((_, target)) = resolve(&early, config, &mut hw.predictor, stats) {
+                    if config.delay_slot && next.if_id.pc == 0 {
+                        // the delay slot missed in the instruction cache, the target is fetched
+                        // once it arrives
+                        next.if_id.prediction = Some(target);
+                    } else {
+                        *pc = target;
+                        next.fetch_wait = None;
+                        if !config.delay_slot {
+                            next.if_id = flushed();
+                        }
+                    }
+                }
+            }
         }
-        if state.id_ex.rt == id_ex.rt {
-            return Ok((
-                PipelineState {
-                    if_id: state.if_id,
-                    id_ex: IdEx::default(),
-                    ex_mem,
-                    mem_wb,
-                    pipe_out,
-                },
-                None,
-            ));
+    }
+
+    // an instruction held in decode leaves a bubble labelled with the reason behind it
+    if let Some(stall) = next.stall {
+        stats.stall(stall);
+        next.id_ex = IdEx {
+            bubble: Some(Bubble::Stall(stall)),
+            ..Default::default()
+        };
+    }
+
+    if let Some((branch, target)) = resolved {
+        let slot = branch.wrapping_add(4);
+        if config.delay_slot && state.if_id.pc != slot && state.id_ex.pc != slot {
+            // the delay slot is still waiting on the instruction cache, the target is fetched once
+            // it arrives
+            next.if_id = IfId {
+                prediction: Some(target),
+                bubble: Some(Bubble::Stall(Stall::InstructionMiss)),
+                ..Default::default()
+            };
+        } else {
+            // a fetch that is still waiting on a miss was on the wrong path
+            *pc = target;
+            next.fetch_wait = None;
         }
     }
 
-    let if_id = stages::fetch(pc, mem);
+    Ok((next, None))
+}
 
-    Ok((
-        PipelineState {
-            if_id: if_id?,
-            id_ex,
-            ex_mem,
-            mem_wb,
-            pipe_out,
+/// Carries out the syscall that has just left the pipeline
+///
+/// A string or buffer the program can't read faults like a load by the syscall instruction, any
+/// other error is reported to the host
+fn run_syscall(
+    regs: &mut RegisterFile,
+    mem: &mut Memory,
+    mmu: &mut Mmu,
+    out: &PipelineOutput,
+) -> Result<Option<Syscall>> {
+    match handle_syscall(regs, mem, mmu) {
+        Ok(syscall) => Ok(syscall),
+        Err(e) => match e.downcast::<Exception>() {
+            Ok(exception) => Err(Fault::new(exception, out.pc, out.instruction).into()),
+            Err(e) => Ok(Some(Syscall::Error(format!("{}", e)))),
         },
-        None,
+    }
+}
+
+/// Creates an empty fetch latch in place of a squashed instruction
+fn flushed() -> IfId {
+    IfId {
+        bubble: Some(Bubble::Flush),
+        ..Default::default()
+    }
+}
+
+/// Fetches the next instruction and predicts where to fetch from after it
+///
+/// A pending interrupt is fetched in place of the instruction and raised once it reaches the end
+/// of the pipeline like any other exception, the pc is left as it is so the handler returns to
+/// the instruction that wasn't fetched
+fn fetch(
+    pc: &mut u32,
+    regs: &RegisterFile,
+    mem: &mut Memory,
+    state: &PipelineState,
+    config: PipelineConfig,
+    hw: &mut Hardware,
+) -> Result<IfId> {
+    if interrupt_pending(*pc, regs, mem, config) {
+        return Ok(IfId {
+            pc: *pc,
+            exception: Some(Exception::Interrupt),
+            ..Default::default()
+        });
+    }
+    let mut if_id = stages::fetch(pc, mem, &mut hw.mmu)?;
+    if_id.prediction = hw.predictor.predict(if_id.pc, if_id.instruction);
+
+    // the delay slot of a branch is fetched before its predicted target
+    let prediction = if config.delay_slot {
+        state.if_id.prediction
+    } else {
+        if_id.prediction
+    };
+    if let Some(target) = prediction {
+        *pc = target;
+    }
+    Ok(if_id)
+}
+
+/// Checks if an interrupt should be taken before the instruction at `pc`
+///
+/// An interrupt is held off in a delay slot as returning to the slot would skip its branch
+fn interrupt_pending(pc: u32, regs: &RegisterFile, mem: &Memory, config: PipelineConfig) -> bool {
+    let in_delay_slot = config.delay_slot && mem.get(pc.wrapping_sub(4)).is_ok_and(has_delay_slot);
+    regs.interrupt_pending() && !in_delay_slot
+}
+
+/// Checks a branch or jump that has just been resolved against its prediction
+///
+/// # Returns
+/// The address of the branch and the address fetching has to restart from if it was mispredicted
+fn resolve(
+    ins: &ExMem,
+    config: PipelineConfig,
+    predictor: &mut BranchPredictor,
+    stats: &mut Stats,
+) -> Option<(u32, u32)> {
+    if !ins.branch && !ins.jump {
+        return None;
+    }
+    let target = ins.redirect();
+    let correct = target == ins.prediction;
+    predictor.update(ins.pc, ins.branch, target, correct);
+    if correct {
+        return None;
+    }
+    stats.flushes += 1;
+
+    // a branch predicted taken that falls through continues after its delay slot
+    let fall_through = if config.delay_slot { 8 } else { 4 };
+    Some((
+        ins.pc,
+        target.unwrap_or_else(|| ins.pc.wrapping_add(fall_through)),
     ))
 }
+
+/// Checks if an instruction is a branch or jump that is followed by a delay slot
+pub fn has_delay_slot(instruction: u32) -> bool {
+    let op = instruction >> 26;
+    let rs = (instruction >> 21) & 0x1F;
+    let funct = instruction & 0x3F;
+    match op {
+        // jr and jalr
+        0x00 => funct == 0x08 || funct == 0x09,
+        // regimm, j, jal, beq, bne, blez and bgtz
+        0x01..=0x07 => true,
+        // eret
+        0x10 => rs == 0x10 && funct == 0x18,
+        // bc1f and bc1t
+        0x11 => rs == 0x08,
+        _ => false,
+    }
+}
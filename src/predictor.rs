@@ -0,0 +1,279 @@
+//! Branch prediction used by the pipeline to speculatively fetch past branches and jumps
+
+use std::collections::HashMap;
+
+use crate::PipelineConfig;
+
+/// Strategies used to predict the direction of a conditional branch
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Predictor {
+    #[default]
+    NotTaken,
+    Taken,
+    /// Backward branches are taken and forward branches are not
+    Btfnt,
+    /// A branch history table of 1-bit entries remembering the last outcome
+    OneBit,
+    /// A branch history table of 2-bit saturating counters
+    TwoBit,
+}
+
+impl Predictor {
+    pub const ALL: [Predictor; 5] = [
+        Self::NotTaken,
+        Self::Taken,
+        Self::Btfnt,
+        Self::OneBit,
+        Self::TwoBit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NotTaken => "Static Not Taken",
+            Self::Taken => "Static Taken",
+            Self::Btfnt => "Backward Taken, Forward Not Taken",
+            Self::OneBit => "1-bit",
+            Self::TwoBit => "2-bit",
+        }
+    }
+}
+
+/// How often a single branch or jump was taken and predicted correctly
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BranchStats {
+    pub executed: u64,
+    pub taken: u64,
+    pub correct: u64,
+}
+
+impl BranchStats {
+    /// Fraction of executions that were predicted correctly
+    pub fn accuracy(&self) -> f64 {
+        if self.executed == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.executed as f64
+        }
+    }
+}
+
+/// An entry of the branch target buffer
+#[derive(Debug, Clone, Copy)]
+struct BtbEntry {
+    pc: u32,
+    target: u32,
+    conditional: bool,
+}
+
+/// Predicts branches when they are fetched and learns from them once they resolve
+#[derive(Debug, Clone)]
+pub struct BranchPredictor {
+    predictor: Predictor,
+    bht: Vec<u8>,
+    btb: Vec<Option<BtbEntry>>,
+    stats: HashMap<u32, BranchStats>,
+}
+
+impl Default for BranchPredictor {
+    fn default() -> Self {
+        Self::new(PipelineConfig::default())
+    }
+}
+
+impl BranchPredictor {
+    /// Creates a predictor with empty tables sized by `config`
+    ///
+    /// Every history table entry starts out predicting not taken
+    pub fn new(config: PipelineConfig) -> Self {
+        Self {
+            predictor: config.predictor,
+            bht: vec![0; config.bht_entries.max(1) as usize],
+            btb: vec![None; config.btb_entries as usize],
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Predicts the instruction fetched from `pc`
+    ///
+    /// # Returns
+    /// The address to fetch next if the instruction is predicted to be a taken branch or jump
+    pub fn predict(&self, pc: u32, instruction: u32) -> Option<u32> {
+        // with a branch target buffer only branches and jumps that have been taken before have a
+        // target to fetch from, without one conditional branches are decoded as they are fetched
+        let (target, conditional) = if self.btb.is_empty() {
+            (branch_target(pc, instruction)?, true)
+        } else {
+            let entry = self.btb[self.index(pc, self.btb.len())].filter(|e| e.pc == pc)?;
+            (entry.target, entry.conditional)
+        };
+
+        let taken = !conditional
+            || match self.predictor {
+                Predictor::NotTaken => false,
+                Predictor::Taken => true,
+                Predictor::Btfnt => target <= pc,
+                Predictor::OneBit => self.bht[self.index(pc, self.bht.len())] != 0,
+                Predictor::TwoBit => self.bht[self.index(pc, self.bht.len())] >= 2,
+            };
+        taken.then_some(target)
+    }
+
+    /// Records the outcome of a resolved branch or jump at `pc`
+    ///
+    /// `target` is where the instruction redirected the pc to or `None` if it fell through
+    pub fn update(&mut self, pc: u32, conditional: bool, target: Option<u32>, correct: bool) {
+        let stats = self.stats.entry(pc).or_default();
+        stats.executed += 1;
+        stats.taken += target.is_some() as u64;
+        stats.correct += correct as u64;
+
+        if conditional {
+            let i = self.index(pc, self.bht.len());
+            self.bht[i] = match (self.predictor, target.is_some()) {
+                (Predictor::TwoBit, true) => (self.bht[i] + 1).min(3),
+                (Predictor::TwoBit, false) => self.bht[i].saturating_sub(1),
+                (_, taken) => taken as u8,
+            };
+        }
+
+        if let Some(target) = target {
+            if !self.btb.is_empty() {
+                let i = self.index(pc, self.btb.len());
+                self.btb[i] = Some(BtbEntry {
+                    pc,
+                    target,
+                    conditional,
+                });
+            }
+        }
+    }
+
+    /// Gets the statistics of every branch and jump that has been resolved sorted by address
+    pub fn stats(&self) -> Vec<(u32, BranchStats)> {
+        let mut stats: Vec<(u32, BranchStats)> = self.stats.iter().map(|(k, v)| (*k, *v)).collect();
+        stats.sort_by_key(|(pc, _)| *pc);
+        stats
+    }
+
+    /// Gets the contents of the branch history table
+    pub fn history(&self) -> &[u8] {
+        &self.bht
+    }
+
+    /// Tables are indexed by the low bits of the word address so distinct branches may alias
+    fn index(&self, pc: u32, len: usize) -> usize {
+        (pc >> 2) as usize % len
+    }
+}
+
+/// Decodes the target of a conditional branch
+fn branch_target(pc: u32, instruction: u32) -> Option<u32> {
+    let op = instruction >> 26;
+    let rs = (instruction >> 21) & 0x1F;
+    let is_branch = match op {
+        // regimm, beq, bne, blez and bgtz
+        0x01 | 0x04..=0x07 => true,
+        // bc1f and bc1t
+        0x11 => rs == 0x08,
+        _ => false,
+    };
+    let offset = ((instruction << 16) as i32 >> 14) as u32;
+    is_branch.then_some(pc.wrapping_add(4).wrapping_add(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `beq $zero, $zero, offset` where offset counts instructions from the delay slot
+    fn beq(offset: i16) -> u32 {
+        0x1000_0000 | offset as u16 as u32
+    }
+
+    fn predictor(predictor: Predictor, btb_entries: u32) -> BranchPredictor {
+        BranchPredictor::new(PipelineConfig {
+            predictor,
+            btb_entries,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn static_predictors() {
+        let back = beq(-2);
+        let forward = beq(3);
+        let p = predictor(Predictor::NotTaken, 0);
+        assert_eq!(p.predict(0x100, back), None);
+
+        let p = predictor(Predictor::Taken, 0);
+        assert_eq!(p.predict(0x100, back), Some(0xFC));
+        assert_eq!(p.predict(0x100, forward), Some(0x110));
+
+        let p = predictor(Predictor::Btfnt, 0);
+        assert_eq!(p.predict(0x100, back), Some(0xFC));
+        assert_eq!(p.predict(0x100, forward), None);
+
+        // only conditional branches are predicted without a branch target buffer
+        assert_eq!(p.predict(0x100, 0x0800_0000), None);
+    }
+
+    #[test]
+    fn one_bit_predictor_follows_the_last_outcome() {
+        let mut p = predictor(Predictor::OneBit, 0);
+        p.update(0x100, true, Some(0xFC), false);
+        assert_eq!(p.predict(0x100, beq(-2)), Some(0xFC));
+        p.update(0x100, true, None, false);
+        assert_eq!(p.predict(0x100, beq(-2)), None);
+    }
+
+    #[test]
+    fn two_bit_predictor_needs_two_misses_to_flip() {
+        let mut p = predictor(Predictor::TwoBit, 0);
+        p.update(0x100, true, Some(0xFC), false);
+        assert_eq!(p.predict(0x100, beq(-2)), None);
+        p.update(0x100, true, Some(0xFC), false);
+        p.update(0x100, true, Some(0xFC), true);
+        assert_eq!(p.predict(0x100, beq(-2)), Some(0xFC));
+
+        p.update(0x100, true, None, false);
+        assert_eq!(p.predict(0x100, beq(-2)), Some(0xFC));
+        p.update(0x100, true, None, false);
+        assert_eq!(p.predict(0x100, beq(-2)), None);
+        assert_eq!(p.history()[(0x100 >> 2) % 16], 1);
+    }
+
+    #[test]
+    fn branch_target_buffer_predicts_jumps_once_taken() {
+        let jump = 0x0800_0000;
+        let mut p = predictor(Predictor::NotTaken, 4);
+        assert_eq!(p.predict(0x100, jump), None);
+        p.update(0x100, false, Some(0x200), false);
+        assert_eq!(p.predict(0x100, jump), Some(0x200));
+        // another address mapping to the same entry misses
+        assert_eq!(p.predict(0x110, jump), None);
+
+        // conditional entries still go through the direction predictor
+        p.update(0x104, true, Some(0x300), false);
+        assert_eq!(p.predict(0x104, beq(0)), None);
+    }
+
+    #[test]
+    fn stats_count_every_resolution() {
+        let mut p = predictor(Predictor::NotTaken, 0);
+        p.update(0x104, true, None, true);
+        p.update(0x100, true, Some(0xFC), false);
+        p.update(0x100, true, None, true);
+        let stats = p.stats();
+        assert_eq!(stats[0].0, 0x100);
+        assert_eq!(
+            stats[0].1,
+            BranchStats {
+                executed: 2,
+                taken: 1,
+                correct: 1
+            }
+        );
+        assert_eq!(stats[0].1.accuracy(), 0.5);
+        assert_eq!(stats[1].1.executed, 1);
+    }
+}
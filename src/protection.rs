@@ -0,0 +1,290 @@
+//! Regions of the address space and the accesses each of them allows
+
+use std::{fmt, ops::Range};
+
+use crate::parser::model::{
+    DATA_BASE, HEAP_BASE, KDATA_BASE, KERNEL_BASE, MMIO_BASE, STACK_LIMIT, TEXT_BASE,
+};
+
+/// Ways an instruction can access memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Fetching an instruction
+    Execute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "Read from"),
+            Access::Write => write!(f, "Write to"),
+            Access::Execute => write!(f, "Fetch from"),
+        }
+    }
+}
+
+/// Accesses a region allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Permissions {
+    const CODE: Self = Self {
+        read: true,
+        write: false,
+        execute: true,
+    };
+    const DATA: Self = Self {
+        read: true,
+        write: true,
+        execute: false,
+    };
+
+    pub fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        }
+    }
+}
+
+/// Formats the permissions like `ls -l` does, e.g. `r-x`
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set, c| if set { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x')
+        )
+    }
+}
+
+/// Regions of the address space a program may access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Text,
+    Data,
+    Heap,
+    Stack,
+    KText,
+    KData,
+    /// Registers of the memory mapped devices
+    Mmio,
+}
+
+impl Region {
+    /// Every region in the order they are laid out in memory
+    pub const ALL: [Region; 7] = [
+        Region::Text,
+        Region::Data,
+        Region::Heap,
+        Region::Stack,
+        Region::KText,
+        Region::KData,
+        Region::Mmio,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Region::Text => ".text",
+            Region::Data => ".data",
+            Region::Heap => "heap",
+            Region::Stack => "stack",
+            Region::KText => ".ktext",
+            Region::KData => ".kdata",
+            Region::Mmio => "mmio",
+        }
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        match self {
+            Region::Text | Region::KText => Permissions::CODE,
+            Region::Data | Region::Heap | Region::Stack | Region::KData | Region::Mmio => {
+                Permissions::DATA
+            }
+        }
+    }
+}
+
+/// An access to an address that is outside every region or that its region doesn't allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub access: Access,
+    pub region: Option<Region>,
+    pub address: u32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.region {
+            Some(region) => write!(
+                f,
+                "{} 0x{:08X} in the {} segment which is {}",
+                self.access,
+                self.address,
+                region.name(),
+                region.permissions()
+            ),
+            None => write!(f, "{} unmapped address 0x{:08X}", self.access, self.address),
+        }
+    }
+}
+
+/// Where each region lies in the address space
+///
+/// The heap starts out empty, every other region has a fixed size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryMap {
+    heap_end: u32,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self {
+            heap_end: HEAP_BASE,
+        }
+    }
+}
+
+impl MemoryMap {
+    /// Gets the addresses a region covers
+    pub fn range(&self, region: Region) -> Range<u32> {
+        match region {
+            Region::Text => TEXT_BASE..DATA_BASE,
+            Region::Data => DATA_BASE..HEAP_BASE,
+            Region::Heap => HEAP_BASE..self.heap_end,
+            Region::Stack => STACK_LIMIT..KERNEL_BASE,
+            Region::KText => KERNEL_BASE..KDATA_BASE,
+            Region::KData => KDATA_BASE..MMIO_BASE,
+            // the last word isn't covered as the range would overflow
+            Region::Mmio => MMIO_BASE..u32::MAX,
+        }
+    }
+
+    /// Gets the program break, the first address past the end of the heap
+    pub fn heap_end(&self) -> u32 {
+        self.heap_end
+    }
+
+    /// Moves the program break by `amount` bytes rounded up to a whole word
+    ///
+    /// # Returns
+    /// The previous break or `None` if the heap would shrink below its base or grow into the
+    /// stack
+    pub fn grow_heap(&mut self, amount: i32) -> Option<u32> {
+        let amount = (amount as i64 + 3) & !3;
+        let end = self.heap_end as i64 + amount;
+        if end < HEAP_BASE as i64 || end > STACK_LIMIT as i64 {
+            return None;
+        }
+        let old = self.heap_end;
+        self.heap_end = end as u32;
+        Some(old)
+    }
+
+    /// Finds the region an address is in
+    pub fn region_of(&self, address: u32) -> Option<Region> {
+        Region::ALL
+            .iter()
+            .copied()
+            .find(|region| self.range(*region).contains(&address))
+    }
+
+    /// Checks that an address is mapped and its region allows the access
+    pub fn check(&self, address: u32, access: Access) -> Result<(), Violation> {
+        let region = self.region_of(address);
+        match region {
+            Some(region) if region.permissions().allows(access) => Ok(()),
+            _ => Err(Violation {
+                access,
+                region,
+                address,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_allow_their_accesses() {
+        let map = MemoryMap::default();
+        assert_eq!(map.check(TEXT_BASE, Access::Execute), Ok(()));
+        assert_eq!(map.check(TEXT_BASE, Access::Read), Ok(()));
+        assert_eq!(map.check(DATA_BASE, Access::Write), Ok(()));
+        assert_eq!(map.check(KERNEL_BASE - 4, Access::Write), Ok(()));
+        assert_eq!(map.check(KDATA_BASE, Access::Read), Ok(()));
+
+        let err = map.check(TEXT_BASE, Access::Write).unwrap_err();
+        assert_eq!(err.region, Some(Region::Text));
+        assert_eq!(
+            err.to_string(),
+            "Write to 0x00400000 in the .text segment which is r-x"
+        );
+        let err = map.check(DATA_BASE, Access::Execute).unwrap_err();
+        assert_eq!(err.region, Some(Region::Data));
+    }
+
+    #[test]
+    fn unmapped_addresses_are_violations() {
+        let map = MemoryMap::default();
+        let err = map.check(0, Access::Read).unwrap_err();
+        assert_eq!(err.region, None);
+        assert_eq!(err.to_string(), "Read from unmapped address 0x00000000");
+        // the heap starts out empty
+        assert_eq!(map.region_of(HEAP_BASE), None);
+        assert_eq!(map.region_of(MMIO_BASE), Some(Region::Mmio));
+        assert_eq!(map.region_of(u32::MAX), None);
+    }
+
+    #[test]
+    fn heap_grows_a_word_at_a_time() {
+        let mut map = MemoryMap::default();
+        assert_eq!(map.grow_heap(5), Some(HEAP_BASE));
+        assert_eq!(map.heap_end(), HEAP_BASE + 8);
+        assert_eq!(map.grow_heap(0), Some(HEAP_BASE + 8));
+        assert_eq!(map.grow_heap(-8), Some(HEAP_BASE + 8));
+        assert_eq!(map.heap_end(), HEAP_BASE);
+    }
+
+    #[test]
+    fn heap_cannot_shrink_below_its_base() {
+        let mut map = MemoryMap::default();
+        map.grow_heap(16);
+        assert_eq!(map.grow_heap(-20), None);
+        assert_eq!(map.grow_heap(i32::MIN), None);
+        assert_eq!(map.heap_end(), HEAP_BASE + 16);
+    }
+
+    #[test]
+    fn heap_cannot_grow_into_the_stack() {
+        let mut map = MemoryMap::default();
+        while map.grow_heap(i32::MAX & !3).is_some() {}
+        let room = (STACK_LIMIT - map.heap_end()) as i32;
+        assert_eq!(map.grow_heap(room + 4), None);
+        assert!(map.grow_heap(room).is_some());
+        assert_eq!(map.heap_end(), STACK_LIMIT);
+        assert_eq!(map.grow_heap(1), None);
+    }
+
+    #[test]
+    fn only_the_heap_below_the_break_is_mapped() {
+        let mut map = MemoryMap::default();
+        assert!(map.check(HEAP_BASE, Access::Read).is_err());
+        map.grow_heap(8);
+        assert!(map.check(HEAP_BASE + 4, Access::Write).is_ok());
+        assert_eq!(map.region_of(HEAP_BASE + 4), Some(Region::Heap));
+        assert!(map.check(HEAP_BASE + 8, Access::Read).is_err());
+    }
+}
@@ -2,7 +2,11 @@ use std::convert::TryFrom;
 
 use anyhow::{bail, Error};
 
-use crate::parser::model::STACK_BASE;
+use crate::{
+    devices::{RECEIVER_INTERRUPT, TRANSMITTER_INTERRUPT},
+    parser::model::STACK_BASE,
+    Exception,
+};
 
 /// List of registers
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
@@ -42,6 +46,29 @@ pub const SP: Register = Register(29);
 pub const FP: Register = Register(30);
 pub const RA: Register = Register(31);
 
+/// Floating point registers used for syscall arguments and results
+pub const F0: Register = Register(0);
+pub const F12: Register = Register(12);
+
+/// Coprocessor 0 registers
+pub const INDEX: Register = Register(0);
+pub const ENTRY_LO: Register = Register(2);
+pub const BAD_VADDR: Register = Register(8);
+pub const ENTRY_HI: Register = Register(10);
+pub const STATUS: Register = Register(12);
+pub const CAUSE: Register = Register(13);
+pub const EPC: Register = Register(14);
+
+/// Status register bit that is set while handling an exception
+const STATUS_EXL: u32 = 0x2;
+/// Cause register bit that is set when the exception was raised in a branch delay slot
+const CAUSE_BD: u32 = 0x8000_0000;
+/// Status register bit that enables interrupts
+const STATUS_IE: u32 = 0x1;
+/// Cause and Status bits of the interrupt lines, set in Cause when pending and in Status when
+/// not masked
+const INTERRUPT_MASK: u32 = 0xFF00;
+
 impl Register {
     pub fn name(&self) -> &'static str {
         match self.0 {
@@ -138,20 +165,41 @@ impl TryFrom<&str> for Register {
 #[derive(Debug)]
 pub struct RegisterFile {
     registers: [u32; 32],
+    // results of the multiply/divide unit
+    hi: u32,
+    lo: u32,
+    // coprocessor 1 registers, doubles use an even/odd pair with the low word in the even register
+    fpr: [u32; 32],
+    fcsr: u32,
+    // coprocessor 0 registers
+    cp0: [u32; 32],
 }
 
 impl Default for RegisterFile {
     fn default() -> Self {
         let mut registers = [0; 32];
         registers[29] = STACK_BASE; // set the initial stack pointer
-        Self { registers }
+        let mut cp0 = [0; 32];
+        cp0[STATUS.0 as usize] = 0x0000FF11; // user mode with interrupts enabled
+        Self {
+            registers,
+            hi: 0,
+            lo: 0,
+            fpr: [0; 32],
+            fcsr: 0,
+            cp0,
+        }
     }
 }
 
 impl RegisterFile {
     /// Handle writing to a register
+    ///
+    /// Writes to $zero are discarded
     pub fn write_register(&mut self, reg: Register, data: u32) {
-        self.registers[reg.0 as usize] = data;
+        if reg != ZERO {
+            self.registers[reg.0 as usize] = data;
+        }
     }
 
     pub fn read_register(&self, reg: Register) -> u32 {
@@ -161,4 +209,119 @@ impl RegisterFile {
     pub fn get_mut(&mut self, reg: Register) -> &mut u32 {
         &mut self.registers[reg.0 as usize]
     }
+
+    /// Reads the HI register
+    pub fn hi(&self) -> u32 {
+        self.hi
+    }
+
+    /// Reads the LO register
+    pub fn lo(&self) -> u32 {
+        self.lo
+    }
+
+    /// Writes both the HI and LO registers
+    pub fn write_hi_lo(&mut self, hi: u32, lo: u32) {
+        self.hi = hi;
+        self.lo = lo;
+    }
+
+    /// Reads a single floating point register
+    pub fn read_fpr(&self, reg: Register) -> u32 {
+        self.fpr[reg.0 as usize]
+    }
+
+    /// Writes a single floating point register
+    pub fn write_fpr(&mut self, reg: Register, data: u32) {
+        self.fpr[reg.0 as usize] = data;
+    }
+
+    /// Reads the floating point register pair starting at `reg`
+    ///
+    /// The high word is 0 when reading the last register
+    pub fn read_fpr_pair(&self, reg: Register) -> u64 {
+        let low = self.fpr[reg.0 as usize] as u64;
+        let high = self.fpr.get(reg.0 as usize + 1).copied().unwrap_or(0) as u64;
+        (high << 32) | low
+    }
+
+    /// Writes a double to the floating point register pair starting at `reg`
+    pub fn write_fpr_pair(&mut self, reg: Register, data: u64) {
+        self.fpr[reg.0 as usize] = data as u32;
+        if let Some(high) = self.fpr.get_mut(reg.0 as usize + 1) {
+            *high = (data >> 32) as u32;
+        }
+    }
+
+    /// Reads the floating point control and status register
+    pub fn fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    /// Reads the 8 floating point condition flags as a bitmask
+    ///
+    /// Flag 0 lives in bit 23 of the FCSR and flags 1-7 in bits 25-31
+    pub fn fcc(&self) -> u8 {
+        let cc0 = (self.fcsr >> 23) & 1;
+        let cc1_7 = (self.fcsr >> 25) << 1;
+        (cc1_7 | cc0) as u8
+    }
+
+    /// Writes all 8 floating point condition flags from a bitmask
+    pub fn write_fcc(&mut self, fcc: u8) {
+        let fcc = fcc as u32;
+        self.fcsr &= !(0xFE80_0000);
+        self.fcsr |= ((fcc & 1) << 23) | ((fcc >> 1) << 25);
+    }
+
+    /// Reads a coprocessor 0 register
+    pub fn read_cp0(&self, reg: Register) -> u32 {
+        self.cp0[reg.0 as usize]
+    }
+
+    /// Writes a coprocessor 0 register
+    pub fn write_cp0(&mut self, reg: Register, data: u32) {
+        self.cp0[reg.0 as usize] = data;
+    }
+
+    /// Checks if an exception is currently being handled
+    pub fn in_exception(&self) -> bool {
+        self.cp0[STATUS.0 as usize] & STATUS_EXL != 0
+    }
+
+    /// Sets the Cause bits of the interrupts the memory mapped devices are requesting
+    pub fn request_interrupts(&mut self, pending: u32) {
+        let devices = RECEIVER_INTERRUPT | TRANSMITTER_INTERRUPT;
+        let cause = &mut self.cp0[CAUSE.0 as usize];
+        *cause = (*cause & !devices) | (pending & devices);
+    }
+
+    /// Checks if an interrupt is pending that is enabled and not masked
+    pub fn interrupt_pending(&self) -> bool {
+        let status = self.cp0[STATUS.0 as usize];
+        let cause = self.cp0[CAUSE.0 as usize];
+        status & STATUS_IE != 0 && status & STATUS_EXL == 0 && cause & status & INTERRUPT_MASK != 0
+    }
+
+    /// Records an exception raised by the instruction at `pc` and enters kernel mode
+    ///
+    /// An exception in a branch delay slot points EPC at the branch and sets Cause.BD so returning
+    /// to EPC runs the branch again instead of skipping it
+    pub fn enter_exception(&mut self, exception: Exception, pc: u32, delay_slot: bool) {
+        let cause = &mut self.cp0[CAUSE.0 as usize];
+        *cause = (*cause & !(CAUSE_BD | 0x7C)) | (exception.code() << 2);
+        if delay_slot {
+            *cause |= CAUSE_BD;
+        }
+        if let Some(addr) = exception.bad_address() {
+            self.cp0[BAD_VADDR.0 as usize] = addr;
+        }
+        self.cp0[EPC.0 as usize] = if delay_slot { pc.wrapping_sub(4) } else { pc };
+        self.cp0[STATUS.0 as usize] |= STATUS_EXL;
+    }
+
+    /// Returns to user mode after handling an exception
+    pub fn exit_exception(&mut self) {
+        self.cp0[STATUS.0 as usize] &= !STATUS_EXL;
+    }
 }
@@ -1,18 +1,38 @@
 use crate::{
-    stages::execute::{op_ctrl::*, IdEx},
-    Register, RegisterFile,
+    hazard::Bubble,
+    mmu::tlb_ctrl::*,
+    stages::execute::{branch_ctrl::*, fp_ctrl::*, op_ctrl::*, IdEx},
+    Exception, Register, RegisterFile, Width, EPC, RA, ZERO,
 };
-use anyhow::{bail, Result};
+use anyhow::Result;
 
 // Struct representing this stages inputs
 #[derive(Debug, Default, Clone)]
 pub struct IfId {
     pub instruction: u32,
     pub pc: u32,
+    pub exception: Option<Exception>,
+    /// Target the branch predictor redirected fetch to after this instruction
+    pub prediction: Option<u32>,
+    pub bubble: Option<Bubble>,
+}
+
+/// Creates a bubble that carries an exception raised by an instruction down the pipeline
+fn raise(input: &IfId, exception: Exception) -> IdEx {
+    IdEx {
+        exception: Some(exception),
+        pc: input.pc,
+        instruction: input.instruction,
+        ..Default::default()
+    }
 }
 
 /// Decodes and instruction
 pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
+    if let Some(exception) = input.exception {
+        return Ok(raise(&input, exception));
+    }
+
     // instruction masks
     let fn_mask = 0b00000000000000000000000000111111;
     let sh_mask = 0b00000000000000000000011111000000;
@@ -32,18 +52,26 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
     let op = (input.instruction & op_mask) >> 26;
     let mut imm = input.instruction & imm_mask;
     let j_imm = input.instruction & j_mask;
+    // logical immediates are zero extended
+    let zero_imm = imm;
 
     // sign extend the imm value
     imm = ((imm << 16) as i32 >> 16) as u32;
 
     // make registers typed
     let rs: Register = rs.into();
-    let rt: Register = rt.into();
-    let rd: Register = rd.into();
+    let mut rt: Register = rt.into();
+    let mut rd: Register = rd.into();
 
     // read rs and rt
     let read_rs = reg_file.read_register(rs);
-    let read_rt = reg_file.read_register(rt);
+    let mut read_rt = reg_file.read_register(rt);
+
+    // coprocessor 1 instructions name their registers fs and ft and select a format in rs
+    let fs: Register = rd;
+    let ft: Register = rt;
+    let fd: Register = shamt.into();
+    let fmt = rs.value() as u8;
 
     // handle controls
     let reg_dst; // determines destination register (0: rt, 1: rd)
@@ -54,28 +82,53 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
     let mem_read; // if enabled read from alu result
     let alu_op; // alu operation
     let branch; // enable branching
-    let branch_not; // enable branch not equal
+    let mut branch_cond = BRANCH_EQ; // condition that must hold for a branch to be taken
     let jump; // enable jumping
+    let mut jump_reg = false; // if enabled jump to the address in rs instead of the immediate
+    let mut link = false; // if enabled the return address is written instead of the alu result
+    let mut hi_lo_write = false; // if enabled write the result of the multiply/divide unit
     let mut syscall = false;
+    let mut fp_op = FP_NONE; // operation performed by the floating point unit
+    let mut fp_write = false; // if enabled write the destination floating point register
+    let mut fp_double = false; // if enabled floating point writes and memory transfers use a register pair
+    let mut fcc_write = false; // if enabled write the floating point condition flags
+    let mut fp_cc = 0; // floating point condition flag used by compares and branches
+    let mut cp0_read = false; // if enabled the coprocessor 0 register in rd is written to rt
+    let mut cp0_write = false; // if enabled write rt to the coprocessor 0 register in rd
+    let mut eret = false; // if enabled return from an exception to the address in EPC
+    let mut tlb_op = TLB_NONE; // TLB instruction executed in writeback
+
+    // loads and stores encode their width in the low 2 bits of the opcode and whether a load is
+    // unsigned in the 3rd bit
+    let mut mem_width = match op & 0x3 {
+        0 => Width::Byte,
+        1 => Width::Half,
+        _ => Width::Word,
+    };
+    let mem_signed = op & 0x4 == 0;
 
     // This is where instructions are defined
     match op {
         0 => {
             syscall = funct == 0x0c;
+            // JR and JALR jump to the address held in rs
+            jump_reg = funct == 0x08 || funct == 0x09;
+            link = funct == 0x09;
+            // mult, div and moves to HI/LO write the multiply/divide unit instead of rd
+            hi_lo_write = matches!(funct, 0x11 | 0x13 | 0x18..=0x1b);
             // R-type instruction
             reg_dst = true;
             alu_src = false;
             mem_to_reg = false;
-            reg_write = true;
+            reg_write = funct != 0x08 && !hi_lo_write;
             mem_read = false;
             mem_write = false;
             branch = false;
-            branch_not = false;
-            jump = false;
+            jump = jump_reg;
             alu_op = OP_R;
         }
-        0x23 => {
-            // LW instruction
+        0x20 | 0x21 | 0x23 | 0x24 | 0x25 => {
+            // LB, LH, LW, LBU and LHU instructions
             reg_dst = false;
             alu_src = true;
             mem_to_reg = true;
@@ -83,12 +136,11 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = true;
             mem_write = false;
             branch = false;
-            branch_not = false;
             jump = false;
             alu_op = OP_ADD;
         }
-        0x2b => {
-            // SW instruction
+        0x28 | 0x29 | 0x2b => {
+            // SB, SH and SW instructions
             reg_dst = false;
             alu_src = true;
             mem_to_reg = false;
@@ -96,12 +148,11 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = true;
             branch = false;
-            branch_not = false;
             jump = false;
             alu_op = OP_ADD;
         }
-        0x8 => {
-            // ADDI instruction
+        0x8 | 0x9 => {
+            // ADDI and ADDIU instructions
             reg_dst = false;
             alu_src = true;
             mem_to_reg = false;
@@ -109,9 +160,9 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = false;
-            branch_not = false;
             jump = false;
-            alu_op = OP_ADD;
+            // only ADDI traps on overflow
+            alu_op = if op == 0x8 { OP_ADD_TRAP } else { OP_ADD };
         }
 
         0xc => {
@@ -123,9 +174,9 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = false;
-            branch_not = false;
             jump = false;
             alu_op = OP_AND;
+            imm = zero_imm;
         }
 
         0xf => {
@@ -138,7 +189,6 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             alu_op = OP_UPPER;
             branch = false;
-            branch_not = false;
             jump = false;
         }
 
@@ -151,9 +201,36 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = false;
-            branch_not = false;
             jump = false;
             alu_op = OP_OR;
+            imm = zero_imm;
+        }
+
+        0xe => {
+            // XORI instruction
+            reg_dst = false;
+            alu_src = true;
+            mem_to_reg = false;
+            reg_write = true;
+            mem_read = false;
+            mem_write = false;
+            branch = false;
+            jump = false;
+            alu_op = OP_XOR;
+            imm = zero_imm;
+        }
+
+        0xa | 0xb => {
+            // SLTI and SLTIU instructions
+            reg_dst = false;
+            alu_src = true;
+            mem_to_reg = false;
+            reg_write = true;
+            mem_read = false;
+            mem_write = false;
+            branch = false;
+            jump = false;
+            alu_op = if op == 0xa { OP_SLT } else { OP_SLTU };
         }
         0x4 => {
             // BEQ instruction
@@ -164,7 +241,6 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = true;
-            branch_not = false;
             jump = false;
             alu_op = OP_SUB;
         }
@@ -177,9 +253,127 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = true;
-            branch_not = true;
+            branch_cond = BRANCH_NE;
+            jump = false;
+            alu_op = OP_SUB;
+        }
+        0x1 | 0x6 | 0x7 => {
+            // REGIMM (BLTZ, BGEZ, BLTZAL, BGEZAL), BLEZ and BGTZ instructions compare rs to zero
+            branch_cond = match (op, rt.value()) {
+                (0x1, 0x00) | (0x1, 0x10) => BRANCH_LTZ,
+                (0x1, 0x01) | (0x1, 0x11) => BRANCH_GEZ,
+                (0x6, _) => BRANCH_LEZ,
+                (0x7, _) => BRANCH_GTZ,
+                _ => return Ok(raise(&input, Exception::ReservedInstruction)),
+            };
+            // the linking variants always write the return address even if not taken
+            link = op == 0x1 && rt.value() & 0x10 != 0;
+            reg_dst = true;
+            alu_src = false;
+            mem_to_reg = false;
+            reg_write = link;
+            mem_read = false;
+            mem_write = false;
+            branch = true;
             jump = false;
             alu_op = OP_SUB;
+            rd = RA;
+            // rt holds part of the opcode so compare against $zero instead
+            rt = ZERO;
+            read_rt = 0;
+        }
+        0x31 | 0x35 => {
+            // LWC1 and LDC1 instructions
+            reg_dst = false;
+            alu_src = true;
+            mem_to_reg = false;
+            reg_write = false;
+            mem_read = true;
+            mem_write = false;
+            branch = false;
+            jump = false;
+            alu_op = OP_ADD;
+            mem_width = Width::Word;
+            fp_write = true;
+            fp_double = op == 0x35;
+        }
+        0x39 | 0x3d => {
+            // SWC1 and SDC1 instructions
+            reg_dst = false;
+            alu_src = true;
+            mem_to_reg = false;
+            reg_write = false;
+            mem_read = false;
+            mem_write = true;
+            branch = false;
+            jump = false;
+            alu_op = OP_ADD;
+            mem_width = Width::Word;
+            fp_op = FP_STORE;
+            fp_double = op == 0x3d;
+        }
+        0x11 => {
+            // COP1 instructions
+            alu_src = false;
+            mem_to_reg = false;
+            mem_read = false;
+            mem_write = false;
+            jump = false;
+            alu_op = OP_ADD;
+            match fmt {
+                0x00 => {
+                    // MFC1 instruction
+                    reg_dst = false;
+                    reg_write = true;
+                    branch = false;
+                    fp_op = FP_MFC1;
+                }
+                0x04 => {
+                    // MTC1 instruction writes fs which is held in the rd field
+                    reg_dst = true;
+                    reg_write = false;
+                    branch = false;
+                    fp_op = FP_MTC1;
+                    fp_write = true;
+                }
+                0x08 => {
+                    // BC1F and BC1T instructions hold the condition flag and sense in rt
+                    reg_dst = false;
+                    reg_write = false;
+                    branch = true;
+                    branch_cond = if rt.value() & 1 == 1 {
+                        BRANCH_FP_TRUE
+                    } else {
+                        BRANCH_FP_FALSE
+                    };
+                    fp_cc = (rt.value() >> 2) as u8;
+                }
+                FMT_S | FMT_D | FMT_W if funct >= 0x30 => {
+                    // C.cond instructions hold the condition flag in the top of fd
+                    reg_dst = false;
+                    reg_write = false;
+                    branch = false;
+                    fp_op = FP_COMPARE;
+                    fcc_write = true;
+                    fp_cc = (fd.value() >> 2) as u8;
+                }
+                FMT_S | FMT_D | FMT_W => {
+                    // arithmetic and conversion instructions write fd
+                    reg_dst = true;
+                    reg_write = false;
+                    branch = false;
+                    fp_op = FP_ARITH;
+                    fp_write = true;
+                    rd = fd;
+                    fp_double = match funct {
+                        0x20 => false,
+                        0x21 => true,
+                        0x0c..=0x0f | 0x24 => false,
+                        _ => fmt == FMT_D,
+                    };
+                }
+                _ => return Ok(raise(&input, Exception::ReservedInstruction)),
+            }
         }
         0x02 => {
             // J instruction
@@ -190,16 +384,107 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
             mem_read = false;
             mem_write = false;
             branch = false;
-            branch_not = false;
             jump = true;
             alu_op = OP_ADD;
             imm = j_imm;
         }
-        _ => {
-            bail!("Unrecognized instruction opcode 0x{:x}", op)
+        0x03 => {
+            // JAL instruction
+            reg_dst = true;
+            alu_src = false;
+            mem_to_reg = false;
+            reg_write = true;
+            mem_read = false;
+            mem_write = false;
+            branch = false;
+            jump = true;
+            link = true;
+            alu_op = OP_ADD;
+            imm = j_imm;
+            rd = RA;
+        }
+        0x10 => {
+            // COP0 instructions
+            alu_src = false;
+            mem_to_reg = false;
+            mem_read = false;
+            mem_write = false;
+            branch = false;
+            alu_op = OP_ADD;
+            match rs.value() {
+                0x00 => {
+                    // MFC0 instruction
+                    reg_dst = false;
+                    reg_write = true;
+                    jump = false;
+                    cp0_read = true;
+                }
+                0x04 => {
+                    // MTC0 instruction
+                    reg_dst = true;
+                    reg_write = false;
+                    jump = false;
+                    cp0_write = true;
+                }
+                0x10 if funct == 0x18 => {
+                    // ERET instruction jumps to the address held in EPC
+                    reg_dst = false;
+                    reg_write = false;
+                    jump = true;
+                    eret = true;
+                    rd = EPC;
+                }
+                0x10 if matches!(funct, 0x01 | 0x02 | 0x06 | 0x08) => {
+                    // TLB instructions only read and write coprocessor 0 and the TLB
+                    reg_dst = false;
+                    reg_write = false;
+                    jump = false;
+                    tlb_op = match funct {
+                        0x01 => TLB_READ,
+                        0x02 => TLB_WRITE_INDEXED,
+                        0x06 => TLB_WRITE_RANDOM,
+                        _ => TLB_PROBE,
+                    };
+                }
+                _ => return Ok(raise(&input, Exception::ReservedInstruction)),
+            }
         }
+        _ => return Ok(raise(&input, Exception::ReservedInstruction)),
     }
 
+    // double precision values must be held in an even/odd register pair, an odd register is an
+    // encoding the coprocessor doesn't implement
+    let fp_reg = if reg_dst { rd } else { rt };
+    let double_source = (fp_op == FP_ARITH || fp_op == FP_COMPARE) && fmt == FMT_D;
+    if (fp_double && fp_reg.value() & 1 != 0)
+        || (double_source && (fs.value() | ft.value()) & 1 != 0)
+    {
+        return Ok(raise(&input, Exception::ReservedInstruction));
+    }
+
+    // values the instruction reads so the hazard unit only waits on real dependencies
+    let rs_read = match op {
+        // shifts by shamt, moves from HI/LO and syscall don't use rs
+        0 => !matches!(funct, 0x00 | 0x02 | 0x03 | 0x0c | 0x10 | 0x12),
+        // J, JAL, LUI and the coprocessor instructions don't use rs
+        0x02 | 0x03 | 0x0f | 0x10 | 0x11 => false,
+        _ => true,
+    };
+    let rt_read = match op {
+        // register jumps, syscall and moves to and from HI/LO don't use rt
+        0 => !matches!(funct, 0x08 | 0x09 | 0x0c | 0x10..=0x13),
+        // BEQ, BNE and the values written by stores
+        0x04 | 0x05 | 0x28 | 0x29 | 0x2b => true,
+        // moves to a coprocessor
+        0x10 | 0x11 => cp0_write || fp_op == FP_MTC1,
+        _ => false,
+    };
+    let fs_read = matches!(fp_op, FP_ARITH | FP_COMPARE | FP_MFC1);
+    // unary floating point operations leave ft empty
+    let ft_read = (fp_op == FP_ARITH && funct <= 0x03) || fp_op == FP_COMPARE || fp_op == FP_STORE;
+    let fp_read_double = double_source || (fp_op == FP_STORE && fp_double);
+    let hi_lo_read = op == 0 && matches!(funct, 0x10..=0x13);
+
     Ok(IdEx {
         alu_src,
         reg_dst,
@@ -207,6 +492,8 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
         op_funct: funct as u8,
         reg_1: read_rs,
         reg_2: read_rt,
+        hi: reg_file.hi(),
+        lo: reg_file.lo(),
         imm,
         shamt,
         rt,
@@ -214,11 +501,41 @@ pub fn decode(reg_file: &mut RegisterFile, input: IfId) -> Result<IdEx> {
         rd,
         mem_write,
         mem_read,
+        mem_width,
+        mem_signed,
         mem_to_reg,
         reg_write,
+        hi_lo_write,
+        fp_op,
+        fp_fmt: fmt,
+        fp_1: reg_file.read_fpr_pair(fs),
+        fp_2: reg_file.read_fpr_pair(ft),
+        fs,
+        ft,
+        fp_write,
+        fp_double,
+        fcc: reg_file.fcc(),
+        fp_cc,
+        fcc_write,
+        cp0_read,
+        cp0_write,
+        eret,
+        tlb_op,
+        cp0_value: reg_file.read_cp0(rd),
+        exception: None,
         branch,
-        branch_not,
+        branch_cond,
         jump,
+        jump_reg,
+        link,
+        rs_read,
+        rt_read,
+        fs_read,
+        ft_read,
+        fp_read_double,
+        hi_lo_read,
+        prediction: input.prediction,
+        bubble: input.bubble,
         pc: input.pc,
         syscall,
         instruction: input.instruction,
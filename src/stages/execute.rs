@@ -1,6 +1,6 @@
 use super::memory::ExMem;
-use crate::pipeline::ForwardingUnit;
-use crate::Register;
+use crate::pipeline::{ForwardingUnit, PipelineConfig};
+use crate::{hazard::Bubble, Exception, Register, Width, ZERO};
 use anyhow::{bail, Result};
 
 /// Struct representing this stages input
@@ -13,6 +13,8 @@ pub struct IdEx {
     pub op_funct: u8,
     pub reg_1: u32,
     pub reg_2: u32,
+    pub hi: u32,
+    pub lo: u32,
     pub imm: u32,
     pub shamt: u32,
     pub rt: Register,
@@ -20,14 +22,47 @@ pub struct IdEx {
     pub syscall: bool,
     // forwarded data
     pub branch: bool,
-    pub branch_not: bool,
+    pub branch_cond: u8,
     pub jump: bool,
+    pub jump_reg: bool,
+    pub link: bool,
+    pub prediction: Option<u32>,
     pub pc: u32,
+    // values read by the instruction
+    pub rs_read: bool,
+    pub rt_read: bool,
+    pub fs_read: bool,
+    pub ft_read: bool,
+    pub fp_read_double: bool,
+    pub hi_lo_read: bool,
     pub mem_write: bool,
     pub mem_read: bool,
+    pub mem_width: Width,
+    pub mem_signed: bool,
     pub mem_to_reg: bool,
     pub reg_write: bool,
+    pub hi_lo_write: bool,
     pub rs: Register,
+    // floating point data
+    pub fp_op: u8,
+    pub fp_fmt: u8,
+    pub fp_1: u64,
+    pub fp_2: u64,
+    pub fs: Register,
+    pub ft: Register,
+    pub fp_write: bool,
+    pub fp_double: bool,
+    pub fcc: u8,
+    pub fp_cc: u8,
+    pub fcc_write: bool,
+    // coprocessor 0 data
+    pub cp0_read: bool,
+    pub cp0_write: bool,
+    pub eret: bool,
+    pub tlb_op: u8,
+    pub cp0_value: u32,
+    pub exception: Option<Exception>,
+    pub bubble: Option<Bubble>,
 
     // demo thing
     pub instruction: u32,
@@ -40,27 +75,109 @@ pub mod op_ctrl {
     pub const OP_ADD: u8 = 3;
     pub const OP_SUB: u8 = 4;
     pub const OP_UPPER: u8 = 5;
+    pub const OP_XOR: u8 = 6;
+    pub const OP_SLT: u8 = 7;
+    pub const OP_SLTU: u8 = 8;
+    pub const OP_ADD_TRAP: u8 = 9;
 }
 use op_ctrl::*;
 
+pub mod branch_ctrl {
+    //! Conditions a branch can be taken on
+    pub const BRANCH_EQ: u8 = 0;
+    pub const BRANCH_NE: u8 = 1;
+    pub const BRANCH_LTZ: u8 = 2;
+    pub const BRANCH_GEZ: u8 = 3;
+    pub const BRANCH_LEZ: u8 = 4;
+    pub const BRANCH_GTZ: u8 = 5;
+    pub const BRANCH_FP_TRUE: u8 = 6;
+    pub const BRANCH_FP_FALSE: u8 = 7;
+}
+use branch_ctrl::*;
+
+pub mod fp_ctrl {
+    //! Floating point unit controls
+    pub const FP_NONE: u8 = 0;
+    pub const FP_ARITH: u8 = 1;
+    pub const FP_COMPARE: u8 = 2;
+    pub const FP_MFC1: u8 = 3;
+    pub const FP_MTC1: u8 = 4;
+    pub const FP_STORE: u8 = 5;
+
+    // operand formats from the fmt field
+    pub const FMT_S: u8 = 0x10;
+    pub const FMT_D: u8 = 0x11;
+    pub const FMT_W: u8 = 0x14;
+}
+use fp_ctrl::*;
+
+/// Creates a bubble that carries an exception raised by an instruction down the pipeline
+fn raise(input: &IdEx, exception: Exception) -> ExMem {
+    ExMem {
+        exception: Some(exception),
+        pc: input.pc,
+        instruction: input.instruction,
+        ..Default::default()
+    }
+}
+
 /// Runs execute stage
-pub fn execute(input: IdEx, fwd_unit: ForwardingUnit) -> Result<ExMem> {
+pub fn execute(input: IdEx, fwd_unit: ForwardingUnit, config: PipelineConfig) -> Result<ExMem> {
+    if let Some(exception) = input.exception {
+        return Ok(raise(&input, exception));
+    }
+
     let syscall = input.syscall;
     let mut use_shamt = false;
+    let mut use_var_shift = false;
+    let mut hi_lo_op = None;
+    let mut trap_overflow = false;
     // compute ALU control lines
     let alu_ctrl = match input.alu_op {
         OP_R => {
             // get info from instruction funct
             match input.op_funct {
-                0x20 => (false, false, ALU_ADD), // add
-                0x22 => (false, true, ALU_ADD),  // sub
-                0x24 => (false, false, ALU_AND), // and
-                0x2a => (false, false, ALU_SLT), // slt
-                0x25 => (false, false, ALU_OR),  // or
-                0x27 => (true, true, ALU_AND),   // nor
-                0x0c => (false, false, ALU_ADD), // syscall
-                0x06 => (false, false, ALU_SRL), // srlv
-                0x26 => (false, false, ALU_XOR), // xor
+                0x20 => {
+                    // add
+                    trap_overflow = true;
+                    (false, false, ALU_ADD)
+                }
+                0x21 => (false, false, ALU_ADD), // addu
+                0x22 => {
+                    // sub
+                    trap_overflow = true;
+                    (false, true, ALU_ADD)
+                }
+                0x23 => (false, true, ALU_ADD),   // subu
+                0x24 => (false, false, ALU_AND),  // and
+                0x2a => (false, false, ALU_SLT),  // slt
+                0x2b => (false, false, ALU_SLTU), // sltu
+                0x25 => (false, false, ALU_OR),   // or
+                0x27 => (true, true, ALU_AND),    // nor
+                0x0c => (false, false, ALU_ADD),  // syscall
+                0x26 => (false, false, ALU_XOR),  // xor
+                0x04 => {
+                    // sllv
+                    use_var_shift = true;
+                    (false, false, ALU_SLL)
+                }
+                0x06 => {
+                    // srlv
+                    use_var_shift = true;
+                    (false, false, ALU_SRL)
+                }
+                0x07 => {
+                    // srav
+                    use_var_shift = true;
+                    (false, false, ALU_SRA)
+                }
+                0x08 => (false, false, ALU_ADD), // jr
+                0x09 => (false, false, ALU_ADD), // jalr
+                0x10 | 0x11 | 0x12 | 0x13 | 0x18 | 0x19 | 0x1a | 0x1b => {
+                    // mfhi, mthi, mflo, mtlo, mult, multu, div, divu
+                    hi_lo_op = Some(input.op_funct);
+                    (false, false, ALU_ADD)
+                }
                 0x00 => {
                     // sll
                     use_shamt = true;
@@ -76,16 +193,21 @@ pub fn execute(input: IdEx, fwd_unit: ForwardingUnit) -> Result<ExMem> {
                     use_shamt = true;
                     (false, false, ALU_SRA)
                 }
-                _ => {
-                    bail!("Unkown funct: {}", input.op_funct)
-                }
+                _ => return Ok(raise(&input, Exception::ReservedInstruction)),
             }
         }
         OP_ADD => (false, false, ALU_ADD),
+        OP_ADD_TRAP => {
+            trap_overflow = true;
+            (false, false, ALU_ADD)
+        }
         OP_SUB => (false, true, ALU_ADD),
         OP_AND => (false, false, ALU_AND),
         OP_OR => (false, false, ALU_OR),
         OP_UPPER => (false, false, ALU_UPPER),
+        OP_XOR => (false, false, ALU_XOR),
+        OP_SLT => (false, false, ALU_SLT),
+        OP_SLTU => (false, false, ALU_SLTU),
         _ => {
             bail!("Unknown Instruction")
         }
@@ -96,21 +218,58 @@ pub fn execute(input: IdEx, fwd_unit: ForwardingUnit) -> Result<ExMem> {
     let mut arg2 = input.reg_2;
 
     // check forwarding unit on first register
-    if fwd_unit.mem_wb.0 && input.rs == fwd_unit.mem_wb.1 {
+    // $zero is never forwarded since writes to it are discarded
+    if fwd_unit.mem_wb.0 && input.rs == fwd_unit.mem_wb.1 && input.rs != ZERO {
         arg1 = fwd_unit.mem_wb.2;
     }
-    if fwd_unit.ex_mem.0 && input.rs == fwd_unit.ex_mem.1 {
+    if fwd_unit.ex_mem.0 && input.rs == fwd_unit.ex_mem.1 && input.rs != ZERO {
         arg1 = fwd_unit.ex_mem.2;
     }
 
+    // check forwarding unit on HI/LO
+    let (mut hi, mut lo) = (input.hi, input.lo);
+    if fwd_unit.hi_lo_mem_wb.0 {
+        hi = fwd_unit.hi_lo_mem_wb.1;
+        lo = fwd_unit.hi_lo_mem_wb.2;
+    }
+    if fwd_unit.hi_lo_ex_mem.0 {
+        hi = fwd_unit.hi_lo_ex_mem.1;
+        lo = fwd_unit.hi_lo_ex_mem.2;
+    }
+
     // check forwarding unit on second register
-    if fwd_unit.mem_wb.0 && input.rt == fwd_unit.mem_wb.1 {
+    if fwd_unit.mem_wb.0 && input.rt == fwd_unit.mem_wb.1 && input.rt != ZERO {
         arg2 = fwd_unit.mem_wb.2;
     }
-    if fwd_unit.ex_mem.0 && input.rt == fwd_unit.ex_mem.1 {
+    if fwd_unit.ex_mem.0 && input.rt == fwd_unit.ex_mem.1 && input.rt != ZERO {
         arg2 = fwd_unit.ex_mem.2;
     }
 
+    // stores write the forwarded value of rt
+    let mut write_data = arg2;
+
+    // check forwarding unit on the floating point register pairs
+    let fp_1 = forward_fpr(input.fp_1, input.fs, fwd_unit);
+    let fp_2 = forward_fpr(input.fp_2, input.ft, fwd_unit);
+
+    // check forwarding unit on the floating point condition flags
+    let mut fcc = input.fcc;
+    if fwd_unit.fcc_mem_wb.0 {
+        fcc = fwd_unit.fcc_mem_wb.1;
+    }
+    if fwd_unit.fcc_ex_mem.0 {
+        fcc = fwd_unit.fcc_ex_mem.1;
+    }
+
+    // check forwarding unit on the coprocessor 0 register in rd
+    let mut cp0_value = input.cp0_value;
+    if fwd_unit.cp0_mem_wb.0 && input.rd == fwd_unit.cp0_mem_wb.1 {
+        cp0_value = fwd_unit.cp0_mem_wb.2;
+    }
+    if fwd_unit.cp0_ex_mem.0 && input.rd == fwd_unit.cp0_ex_mem.1 {
+        cp0_value = fwd_unit.cp0_ex_mem.2;
+    }
+
     // Handle immediate arguments
     if input.alu_src {
         arg2 = input.imm;
@@ -123,28 +282,269 @@ pub fn execute(input: IdEx, fwd_unit: ForwardingUnit) -> Result<ExMem> {
         arg2 = input.shamt;
     }
 
-    let result = alu(arg1, arg2, alu_ctrl)?;
+    // variable shifts shift rt by the low 5 bits of rs
+    if use_var_shift {
+        let amount = arg1 & 0x1F;
+        arg1 = arg2;
+        arg2 = amount;
+    }
+
+    let (mut result, overflow) = alu(arg1, arg2, alu_ctrl)?;
+
+    // branches compare the difference of their operands
+    let branch_taken = match input.branch_cond {
+        BRANCH_EQ => result == 0,
+        BRANCH_NE => result != 0,
+        BRANCH_LTZ => (result as i32) < 0,
+        BRANCH_GEZ => (result as i32) >= 0,
+        BRANCH_LEZ => (result as i32) <= 0,
+        BRANCH_GTZ => (result as i32) > 0,
+        BRANCH_FP_TRUE => (fcc >> input.fp_cc) & 1 == 1,
+        BRANCH_FP_FALSE => (fcc >> input.fp_cc) & 1 == 0,
+        _ => bail!("Unknown branch condition: {}", input.branch_cond),
+    };
+
+    // signed arithmetic raises an exception instead of writing an overflowed result
+    let exception = if trap_overflow && overflow {
+        Some(Exception::Overflow)
+    } else {
+        None
+    };
+
+    // run the multiply/divide unit
+    match hi_lo_op {
+        Some(0x10) => result = hi,
+        Some(0x11) => hi = arg1,
+        Some(0x12) => result = lo,
+        Some(0x13) => lo = arg1,
+        Some(op) => {
+            let (h, l) = muldiv(arg1, arg2, (hi, lo), op)?;
+            hi = h;
+            lo = l;
+        }
+        None => {}
+    }
+
+    // run the floating point unit
+    let mut fp_result = 0;
+    match input.fp_op {
+        FP_ARITH => match fpu(fp_1, fp_2, input.fp_fmt, input.op_funct) {
+            Some(val) => fp_result = val,
+            None => return Ok(raise(&input, Exception::ReservedInstruction)),
+        },
+        FP_COMPARE => match fp_compare(fp_1, fp_2, input.fp_fmt, input.op_funct) {
+            Some(cond) => fcc = (fcc & !(1 << input.fp_cc)) | ((cond as u8) << input.fp_cc),
+            None => return Ok(raise(&input, Exception::ReservedInstruction)),
+        },
+        FP_MFC1 => result = fp_1 as u32,
+        FP_MTC1 => fp_result = write_data as u64,
+        FP_STORE => {
+            // floating point stores write ft instead of a general purpose register
+            write_data = fp_2 as u32;
+            fp_result = fp_2;
+        }
+        _ => {}
+    }
+
+    // moves to and from coprocessor 0 pass their value through the ALU result
+    if input.cp0_read {
+        result = cp0_value;
+    }
+    if input.cp0_write {
+        result = write_data;
+    }
+
+    // linking instructions write the return address instead of the ALU result, the delay slot
+    // has already executed by the time the callee returns
+    if input.link {
+        result = if config.delay_slot {
+            input.pc.wrapping_add(8)
+        } else {
+            input.pc.wrapping_add(4)
+        };
+    }
+
+    // register jumps use the forwarded value of rs, other jumps stay in the current 256MiB region
+    let jump_pc = if input.eret {
+        cp0_value
+    } else if input.jump_reg {
+        arg1
+    } else {
+        (input.pc.wrapping_add(4) & 0xF0000000) | (input.imm << 2)
+    };
 
     Ok(ExMem {
         alu_result: result,
         zero: result == 0,
-        write_data: input.reg_2,
+        write_data,
         write: input.mem_write,
         read: input.mem_read,
+        width: input.mem_width,
+        signed: input.mem_signed,
         mem_to_reg: input.mem_to_reg,
         write_register: if input.reg_dst { input.rd } else { input.rt },
-        reg_write: input.reg_write,
+        reg_write: input.reg_write && exception.is_none(),
+        hi_lo_write: input.hi_lo_write,
+        hi,
+        lo,
+        fp_result,
+        fp_write: input.fp_write,
+        fp_double: input.fp_double,
+        fcc_write: input.fcc_write,
+        fcc,
+        cp0_write: input.cp0_write,
+        eret: input.eret,
+        tlb_op: input.tlb_op,
         branch: input.branch,
-        branch_not: input.branch_not,
+        branch_taken,
         jump: input.jump,
-        jump_pc: input.imm << 2,
-        branch_pc: input.pc.wrapping_add((input.imm << 2) as i16 as u32), // casts are for sign extension
+        jump_pc,
+        // branch offsets are relative to the instruction after the branch
+        branch_pc: input.pc.wrapping_add(4).wrapping_add(input.imm << 2),
+        prediction: input.prediction,
+        store_register: input.rt,
+        fp_store: input.fp_op == FP_STORE,
+        bubble: input.bubble,
         syscall,
+        exception,
         instruction: input.instruction,
         pc: input.pc,
     })
 }
 
+/// Multiply/divide unit
+///
+/// Takes the current contents of HI and LO and returns their new contents. Division by zero leaves
+/// both unchanged since the result is unpredictable on real hardware.
+pub fn muldiv(a: u32, b: u32, hi_lo: (u32, u32), funct: u8) -> Result<(u32, u32)> {
+    Ok(match funct {
+        0x18 => {
+            // mult
+            let product = (a as i32 as i64) * (b as i32 as i64);
+            ((product >> 32) as u32, product as u32)
+        }
+        0x19 => {
+            // multu
+            let product = (a as u64) * (b as u64);
+            ((product >> 32) as u32, product as u32)
+        }
+        0x1a if b != 0 => {
+            // div
+            let (a, b) = (a as i32, b as i32);
+            (a.wrapping_rem(b) as u32, a.wrapping_div(b) as u32)
+        }
+        0x1b if b != 0 => (a % b, a / b), // divu
+        0x1a | 0x1b => hi_lo,
+        _ => bail!("Unknown multiply/divide operation: {}", funct),
+    })
+}
+
+/// Applies forwarded floating point register writes to the register pair read starting at `reg`
+///
+/// A single precision write only replaces the word of the pair it targets while a double
+/// precision write replaces both of its registers
+pub(crate) fn forward_fpr(value: u64, reg: Register, fwd_unit: ForwardingUnit) -> u64 {
+    let mut value = value;
+    for (write, dest, data, double) in [fwd_unit.fp_mem_wb, fwd_unit.fp_ex_mem] {
+        if !write {
+            continue;
+        }
+        let mut words = vec![(dest.value(), data as u32)];
+        if double {
+            words.push((dest.value() + 1, (data >> 32) as u32));
+        }
+        for (dest, word) in words {
+            if dest == reg.value() {
+                value = (value & 0xFFFFFFFF_00000000) | word as u64;
+            } else if dest == reg.value() + 1 {
+                value = (value & 0x00000000_FFFFFFFF) | (word as u64) << 32;
+            }
+        }
+    }
+    value
+}
+
+/// Interprets the bits of a floating point register pair in the given format
+fn to_float(bits: u64, fmt: u8) -> Option<f64> {
+    Some(match fmt {
+        FMT_S => f32::from_bits(bits as u32) as f64,
+        FMT_D => f64::from_bits(bits),
+        FMT_W => bits as u32 as i32 as f64,
+        _ => return None,
+    })
+}
+
+/// Rounds a value to the format it is written back in
+fn from_float(x: f64, fmt: u8) -> u64 {
+    match fmt {
+        FMT_S => (x as f32).to_bits() as u64,
+        _ => x.to_bits(),
+    }
+}
+
+/// Converts an already rounded value to a word, values that do not fit produce 0x7FFFFFFF
+fn to_word(x: f64) -> u64 {
+    // NaN is never contained in a range
+    if !(-2147483648.0..2147483648.0).contains(&x) {
+        0x7FFFFFFF
+    } else {
+        x as i32 as u32 as u64
+    }
+}
+
+/// Floating point unit
+///
+/// Operands are register pairs where single precision and word values only use the low 32 bits.
+/// Single precision operations are computed as doubles and rounded which gives the same result
+/// since a double holds more than twice the precision of a single.
+///
+/// Returns `None` if the operation is not defined for the format
+pub fn fpu(a: u64, b: u64, fmt: u8, funct: u8) -> Option<u64> {
+    // abs, mov and neg only touch the sign bit so NaNs are passed through unchanged
+    let sign = if fmt == FMT_D { 1 << 63 } else { 1 << 31 };
+    let bits = if fmt == FMT_D { a } else { a & 0xFFFFFFFF };
+
+    let x = to_float(a, fmt)?;
+    let y = to_float(b, fmt)?;
+    Some(match funct {
+        0x00 if fmt != FMT_W => from_float(x + y, fmt), // add
+        0x01 if fmt != FMT_W => from_float(x - y, fmt), // sub
+        0x02 if fmt != FMT_W => from_float(x * y, fmt), // mul
+        0x03 if fmt != FMT_W => from_float(x / y, fmt), // div
+        0x04 if fmt != FMT_W => from_float(x.sqrt(), fmt), // sqrt
+        0x05 if fmt != FMT_W => bits & !sign,           // abs
+        0x06 if fmt != FMT_W => bits,                   // mov
+        0x07 if fmt != FMT_W => bits ^ sign,            // neg
+        0x0c if fmt != FMT_W => to_word(x.round_ties_even()), // round.w
+        0x0d if fmt != FMT_W => to_word(x.trunc()),     // trunc.w
+        0x0e if fmt != FMT_W => to_word(x.ceil()),      // ceil.w
+        0x0f if fmt != FMT_W => to_word(x.floor()),     // floor.w
+        0x20 if fmt != FMT_S => from_float(x, FMT_S),   // cvt.s
+        0x21 if fmt != FMT_D => from_float(x, FMT_D),   // cvt.d
+        // conversions to word use the default round to nearest mode
+        0x24 if fmt != FMT_W => to_word(x.round_ties_even()), // cvt.w
+        _ => return None,
+    })
+}
+
+/// Evaluates a c.cond comparison
+///
+/// The low 3 bits of funct select whether the condition holds when the operands are unordered,
+/// equal or less than. Returns `None` if the operands are words.
+pub fn fp_compare(a: u64, b: u64, fmt: u8, funct: u8) -> Option<bool> {
+    if fmt == FMT_W {
+        return None;
+    }
+    let x = to_float(a, fmt)?;
+    let y = to_float(b, fmt)?;
+    let unordered = x.is_nan() || y.is_nan();
+    Some(
+        (funct & 0x1 != 0 && unordered)
+            || (funct & 0x2 != 0 && x == y)
+            || (funct & 0x4 != 0 && x < y),
+    )
+}
+
 pub mod alu_signals {
     //! ALU Controls
     pub const ALU_AND: u8 = 0;
@@ -156,13 +556,24 @@ pub mod alu_signals {
     pub const ALU_SRA: u8 = 6;
     pub const ALU_UPPER: u8 = 7;
     pub const ALU_XOR: u8 = 8;
+    pub const ALU_SLTU: u8 = 9;
 }
 use alu_signals::*;
 
 /// Simple ALU implementation.
-/// TODO: Handle carry flag
-pub fn alu(a: u32, b: u32, op: (bool, bool, u8)) -> Result<u32> {
-    //println!("{} {} {:?}", a, b, op);
+///
+/// Returns the result and whether an addition or subtraction overflowed when treated as signed
+pub fn alu(a: u32, b: u32, op: (bool, bool, u8)) -> Result<(u32, bool)> {
+    // compute the exact signed result so overflow can be detected even when negating i32::MIN
+    let wide = |x: u32, negate: bool| {
+        let x = x as i32 as i64;
+        if negate {
+            -x
+        } else {
+            x
+        }
+    };
+    let exact = wide(a, op.0) + wide(b, op.1);
 
     let a = if op.0 { !a } else { a };
     let b = if op.1 { !b } else { b };
@@ -171,10 +582,10 @@ pub fn alu(a: u32, b: u32, op: (bool, bool, u8)) -> Result<u32> {
     let arith_a = if op.0 { a.wrapping_add(1) } else { a };
     let arith_b = if op.1 { b.wrapping_add(1) } else { b };
 
-    Ok(match op.2 {
+    let result = match op.2 {
         ALU_AND => a & b,
         ALU_OR => a | b,
-        ALU_ADD => arith_a.overflowing_add(arith_b).0,
+        ALU_ADD => arith_a.wrapping_add(arith_b),
         ALU_SLL => a.overflowing_shl(b).0,
         ALU_XOR => a ^ b,
 
@@ -185,6 +596,13 @@ pub fn alu(a: u32, b: u32, op: (bool, bool, u8)) -> Result<u32> {
         ALU_UPPER => b << 16,
 
         ALU_SLT => {
+            if (a as i32) < (b as i32) {
+                1
+            } else {
+                0
+            }
+        }
+        ALU_SLTU => {
             if a < b {
                 1
             } else {
@@ -192,5 +610,83 @@ pub fn alu(a: u32, b: u32, op: (bool, bool, u8)) -> Result<u32> {
             }
         }
         _ => bail!("Unknown ALU instruction: {:?}", op),
-    })
+    };
+
+    let overflow = op.2 == ALU_ADD && exact != result as i32 as i64;
+    Ok((result, overflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A register instruction that has already read its operands
+    fn r_type(funct: u8, rs: u32, rt: u32) -> IdEx {
+        IdEx {
+            alu_op: OP_R,
+            op_funct: funct,
+            reg_1: rs,
+            reg_2: rt,
+            reg_write: true,
+            ..Default::default()
+        }
+    }
+
+    fn run(input: IdEx) -> ExMem {
+        execute(input, ForwardingUnit::disabled(), PipelineConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn add_traps_signed_overflow() {
+        let out = run(r_type(0x20, i32::MAX as u32, 1));
+        assert_eq!(out.exception, Some(Exception::Overflow));
+        assert!(!out.reg_write);
+    }
+
+    #[test]
+    fn sub_traps_signed_overflow() {
+        let out = run(r_type(0x22, i32::MIN as u32, 1));
+        assert_eq!(out.exception, Some(Exception::Overflow));
+    }
+
+    #[test]
+    fn addi_traps_signed_overflow() {
+        let out = run(IdEx {
+            alu_op: OP_ADD_TRAP,
+            alu_src: true,
+            reg_1: i32::MAX as u32,
+            imm: 1,
+            reg_write: true,
+            ..Default::default()
+        });
+        assert_eq!(out.exception, Some(Exception::Overflow));
+    }
+
+    #[test]
+    fn sub_of_min_from_zero_overflows() {
+        let out = run(r_type(0x22, 0, i32::MIN as u32));
+        assert_eq!(out.exception, Some(Exception::Overflow));
+    }
+
+    #[test]
+    fn signed_arithmetic_in_range_does_not_trap() {
+        let out = run(r_type(0x20, -1i32 as u32, 1));
+        assert_eq!(out.exception, None);
+        assert_eq!(out.alu_result, 0);
+
+        let out = run(r_type(0x22, i32::MIN as u32, -1i32 as u32));
+        assert_eq!(out.exception, None);
+        assert_eq!(out.alu_result, i32::MIN as u32 + 1);
+    }
+
+    #[test]
+    fn unsigned_arithmetic_wraps() {
+        let out = run(r_type(0x21, i32::MAX as u32, 1));
+        assert_eq!(out.exception, None);
+        assert_eq!(out.alu_result, 0x8000_0000);
+
+        let out = run(r_type(0x23, i32::MIN as u32, 1));
+        assert_eq!(out.exception, None);
+        assert_eq!(out.alu_result, i32::MAX as u32);
+    }
 }
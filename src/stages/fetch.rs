@@ -1,16 +1,27 @@
 use super::decode::IfId;
-use crate::Memory;
-use anyhow::{Context, Result};
+use crate::{mmu::Mmu, protection::Access, Exception, Memory};
+use anyhow::Result;
 
 /// Instruction fetch pipeline stage
 ///
 /// Fetches the currently pointed to instruction and increments the PC
-pub fn fetch(pc: &mut u32, mem: &mut Memory) -> Result<IfId> {
-    // fetch instruction and increment pc
-    let instruction = mem.get(*pc).context("In instruction fetch stage")?;
-    *pc += 4;
+pub fn fetch(pc: &mut u32, mem: &mut Memory, mmu: &mut Mmu) -> Result<IfId> {
+    // fetch instruction and increment pc, an address that can't be fetched raises an exception
+    // once it reaches the end of the pipeline
+    let fetched = mmu
+        .translate(*pc, Access::Execute)
+        .and_then(|addr| mem.get(addr).map_err(|_| Exception::AddressLoad(*pc)));
+    let (instruction, exception) = match fetched {
+        Ok(instruction) => (instruction, None),
+        Err(exception) => (0, Some(exception)),
+    };
+    // the pc wraps around at the top of the address space like the hardware adder
+    *pc = pc.wrapping_add(4);
     Ok(IfId {
         instruction,
-        pc: *pc - 4,
+        pc: pc.wrapping_sub(4),
+        exception,
+        prediction: None,
+        bubble: None,
     })
 }
@@ -1,6 +1,9 @@
-use super::writeback::MemWb;
-use crate::{Memory, Register};
-use anyhow::{Context, Result};
+use super::{execute::forward_fpr, writeback::MemWb};
+use crate::{
+    hazard::Bubble, mmu::Mmu, pipeline::ForwardingUnit, protection::Access, Exception, Memory,
+    Register, Width, ZERO,
+};
+use anyhow::Result;
 
 /// Struct representing this stages input
 #[derive(Debug, Default, Clone)]
@@ -9,45 +12,162 @@ pub struct ExMem {
     pub alu_result: u32,
     pub zero: bool,
     pub branch: bool,
-    pub branch_not: bool,
+    pub branch_taken: bool,
     pub jump: bool,
     pub write_data: u32,
     pub write: bool,
     pub read: bool,
+    pub width: Width,
+    pub signed: bool,
     pub branch_pc: u32,
     pub jump_pc: u32,
+    pub prediction: Option<u32>,
+    /// Register holding the value a store writes to memory
+    pub store_register: Register,
+    pub fp_store: bool,
+    pub tlb_op: u8,
     // forwarded data
     pub mem_to_reg: bool,
     pub write_register: Register,
     pub reg_write: bool,
+    pub hi_lo_write: bool,
+    pub hi: u32,
+    pub lo: u32,
+    pub fp_result: u64,
+    pub fp_write: bool,
+    pub fp_double: bool,
+    pub fcc_write: bool,
+    pub fcc: u8,
+    pub cp0_write: bool,
+    pub eret: bool,
     pub syscall: bool,
+    pub exception: Option<Exception>,
+    pub bubble: Option<Bubble>,
 
     // demo thing
     pub instruction: u32,
     pub pc: u32,
 }
 
+/// Creates a bubble that carries an exception raised by an instruction to writeback
+fn raise(input: &ExMem, exception: Exception) -> MemWb {
+    MemWb {
+        exception: Some(exception),
+        pc: input.pc,
+        instruction: input.instruction,
+        ..Default::default()
+    }
+}
+
+impl ExMem {
+    /// Gets the address fetching continues from if this instruction changes control flow
+    pub fn redirect(&self) -> Option<u32> {
+        if self.jump {
+            Some(self.jump_pc)
+        } else if self.branch && self.branch_taken {
+            Some(self.branch_pc)
+        } else {
+            None
+        }
+    }
+}
+
 /// Memory access pipeline stage
-pub fn memory(pc: &mut u32, memory: &mut Memory, input: ExMem) -> Result<MemWb> {
+///
+/// Stores read the value they write in this stage so it can be forwarded to them from writeback
+pub fn memory(
+    memory: &mut Memory,
+    mmu: &mut Mmu,
+    input: ExMem,
+    fwd_unit: ForwardingUnit,
+) -> Result<MemWb> {
+    if let Some(exception) = input.exception {
+        return Ok(raise(&input, exception));
+    }
+
     let mut read_data = 0;
+    let mut fp_data = input.fp_result;
+    let mut write_data = input.write_data;
+    let addr = input.alu_result;
 
-    // handle memory accesses
-    if input.write {
-        *memory.get_mut(input.alu_result)? = input.write_data;
+    // check forwarding unit on the value being stored
+    // $zero is never forwarded since writes to it are discarded
+    if input.write && input.fp_store {
+        fp_data = forward_fpr(fp_data, input.store_register, fwd_unit);
+        write_data = fp_data as u32;
+    } else if input.write
+        && fwd_unit.mem_wb.0
+        && input.store_register == fwd_unit.mem_wb.1
+        && input.store_register != ZERO
+    {
+        write_data = fwd_unit.mem_wb.2;
     }
-    if input.read {
-        read_data = memory.get(input.alu_result).context("In memory stage")?;
+
+    // doubleword transfers move an entire floating point register pair so must be 8 byte aligned,
+    // misaligned accesses raise an address error before the TLB is looked up
+    let align = if input.fp_double {
+        8
+    } else {
+        input.width.bytes()
+    };
+    if addr & (align - 1) != 0 {
+        if input.write {
+            return Ok(raise(&input, Exception::AddressStore(addr)));
+        }
+        if input.read {
+            return Ok(raise(&input, Exception::AddressLoad(addr)));
+        }
     }
 
-    if input.branch {
-        if (!input.branch_not && input.zero) || (input.branch_not && !input.zero) {
-            // branch to PC copmuted in execute stage
-            *pc = input.branch_pc;
+    // both words of a doubleword are in the same page so a single translation covers them
+    let access = if input.write {
+        Access::Write
+    } else {
+        Access::Read
+    };
+    let phys = if input.write || input.read {
+        match mmu.translate(addr, access) {
+            Ok(phys) => phys,
+            Err(exception) => return Ok(raise(&input, exception)),
+        }
+    } else {
+        addr
+    };
+
+    // handle memory accesses, accesses that fail raise an address error
+    if input.write {
+        let stored = memory.store(phys, input.width, write_data).and_then(|_| {
+            if input.fp_double {
+                memory.store(phys + 4, Width::Word, (fp_data >> 32) as u32)
+            } else {
+                Ok(())
+            }
+        });
+        if stored.is_err() {
+            return Ok(raise(&input, Exception::AddressStore(addr)));
         }
     }
+    if input.read {
+        let loaded = memory.load(phys, input.width).and_then(|low| {
+            if input.fp_double {
+                let high = memory.load(phys + 4, Width::Word)?;
+                Ok((low, (high as u64) << 32))
+            } else {
+                Ok((low, 0))
+            }
+        });
+        let (low, high) = match loaded {
+            Ok(data) => data,
+            Err(_) => return Ok(raise(&input, Exception::AddressLoad(addr))),
+        };
+        read_data = low;
+        fp_data = high | low as u64;
 
-    if input.jump {
-        *pc = input.jump_pc;
+        // sign extend narrow loads
+        if input.signed {
+            let shift = 32 - input.width.bytes() * 8;
+            read_data = (((read_data << shift) as i32) >> shift) as u32;
+        }
     }
 
     Ok(MemWb {
@@ -56,6 +176,19 @@ pub fn memory(pc: &mut u32, memory: &mut Memory, input: ExMem) -> Result<MemWb>
         alu_data: input.alu_result,
         write_register: input.write_register,
         reg_write: input.reg_write,
+        hi_lo_write: input.hi_lo_write,
+        hi: input.hi,
+        lo: input.lo,
+        fp_data,
+        fp_write: input.fp_write,
+        fp_double: input.fp_double,
+        fcc_write: input.fcc_write,
+        fcc: input.fcc,
+        cp0_write: input.cp0_write,
+        eret: input.eret,
+        tlb_op: input.tlb_op,
+        exception: None,
+        bubble: input.bubble,
         syscall: input.syscall,
         instruction: input.instruction,
         pc: input.pc,
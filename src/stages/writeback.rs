@@ -1,4 +1,8 @@
-use crate::{Register, RegisterFile};
+use crate::{
+    hazard::Bubble,
+    mmu::{tlb_ctrl::TLB_NONE, Mmu},
+    Exception, Register, RegisterFile,
+};
 
 /// struct representing this structs input
 #[derive(Debug, Default, Clone)]
@@ -8,6 +12,19 @@ pub struct MemWb {
     pub alu_data: u32,
     pub write_register: Register,
     pub reg_write: bool,
+    pub hi_lo_write: bool,
+    pub hi: u32,
+    pub lo: u32,
+    pub fp_data: u64,
+    pub fp_write: bool,
+    pub fp_double: bool,
+    pub fcc_write: bool,
+    pub fcc: u8,
+    pub cp0_write: bool,
+    pub eret: bool,
+    pub tlb_op: u8,
+    pub exception: Option<Exception>,
+    pub bubble: Option<Bubble>,
     pub syscall: bool,
     // demo thing
     pub instruction: u32,
@@ -17,12 +34,13 @@ pub struct MemWb {
 #[derive(Debug, Default, Clone)]
 pub struct PipelineOutput {
     pub syscall: bool,
+    pub bubble: Option<Bubble>,
     pub instruction: u32,
     pub pc: u32,
 }
 
 /// Writeback pipeline stage
-pub fn writeback(reg_file: &mut RegisterFile, input: MemWb) -> PipelineOutput {
+pub fn writeback(reg_file: &mut RegisterFile, mmu: &mut Mmu, input: MemWb) -> PipelineOutput {
     if input.reg_write {
         if input.mem_to_reg {
             reg_file.write_register(input.write_register, input.mem_data);
@@ -30,8 +48,31 @@ pub fn writeback(reg_file: &mut RegisterFile, input: MemWb) -> PipelineOutput {
             reg_file.write_register(input.write_register, input.alu_data);
         }
     }
+    if input.hi_lo_write {
+        reg_file.write_hi_lo(input.hi, input.lo);
+    }
+    if input.fp_write {
+        if input.fp_double {
+            reg_file.write_fpr_pair(input.write_register, input.fp_data);
+        } else {
+            reg_file.write_fpr(input.write_register, input.fp_data as u32);
+        }
+    }
+    if input.fcc_write {
+        reg_file.write_fcc(input.fcc);
+    }
+    if input.cp0_write {
+        reg_file.write_cp0(input.write_register, input.alu_data);
+    }
+    if input.tlb_op != TLB_NONE {
+        mmu.execute(input.tlb_op, reg_file);
+    }
+    if input.eret {
+        reg_file.exit_exception();
+    }
     PipelineOutput {
         syscall: input.syscall,
+        bubble: input.bubble,
         instruction: input.instruction,
         pc: input.pc,
     }
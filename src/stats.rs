@@ -0,0 +1,157 @@
+//! Performance counters kept while the pipeline runs
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    hazard::Stall,
+    parser::opcode_name,
+    stages::{memory::ExMem, writeback::PipelineOutput},
+};
+
+/// Counters describing how a program ran on the pipeline
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub load_use_stalls: u64,
+    pub data_stalls: u64,
+    pub syscall_stalls: u64,
+    /// Cycles spent waiting on instruction or data cache misses
+    pub cache_stalls: u64,
+    /// Number of times instructions fetched after a mispredicted branch or jump were squashed
+    pub flushes: u64,
+    pub branches_taken: u64,
+    pub branches_not_taken: u64,
+    pub loads: u64,
+    pub stores: u64,
+    /// Number of times each instruction retired keyed by its mnemonic
+    pub mix: HashMap<&'static str, u64>,
+}
+
+impl Stats {
+    /// Average number of cycles taken by each instruction or `None` before any has retired
+    pub fn cpi(&self) -> Option<f64> {
+        (self.instructions != 0).then(|| self.cycles as f64 / self.instructions as f64)
+    }
+
+    /// Formats the CPI with three decimals or as "n/a" before any instruction has retired
+    pub fn cpi_text(&self) -> String {
+        self.cpi()
+            .map_or_else(|| "n/a".to_string(), |cpi| format!("{:.3}", cpi))
+    }
+
+    /// Percentage of the retired instructions that `count` makes up
+    pub fn percent(&self, count: u64) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            count as f64 / self.instructions as f64 * 100.0
+        }
+    }
+
+    /// Gets the instruction mix sorted from the most to the least common instruction
+    pub fn mix(&self) -> Vec<(&'static str, u64)> {
+        let mut mix: Vec<(&'static str, u64)> = self.mix.iter().map(|(k, v)| (*k, *v)).collect();
+        mix.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        mix
+    }
+
+    pub(crate) fn stall(&mut self, stall: Stall) {
+        match stall {
+            Stall::LoadUse(_) => self.load_use_stalls += 1,
+            Stall::Data(_) => self.data_stalls += 1,
+            Stall::Syscall => self.syscall_stalls += 1,
+            Stall::InstructionMiss | Stall::DataMiss => self.cache_stalls += 1,
+        }
+    }
+
+    /// Counts the memory accesses and branches of an instruction entering the memory stage
+    ///
+    /// Squashed instructions never reach the memory stage so only the committed path is counted
+    pub(crate) fn memory(&mut self, ins: &ExMem) {
+        self.loads += ins.read as u64;
+        self.stores += ins.write as u64;
+        if ins.branch {
+            if ins.branch_taken {
+                self.branches_taken += 1;
+            } else {
+                self.branches_not_taken += 1;
+            }
+        }
+    }
+
+    /// Counts an instruction leaving the pipeline
+    pub(crate) fn retire(&mut self, out: &PipelineOutput) {
+        // bubbles carry the default pc of 0 which can never be fetched
+        if out.pc == 0 {
+            return;
+        }
+        self.instructions += 1;
+        let name = opcode_name(out.instruction).unwrap_or("unknown");
+        *self.mix.entry(name).or_default() += 1;
+    }
+
+    /// Formats the counters as a human readable summary
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Cycles              {}", self.cycles);
+        let _ = writeln!(out, "Instructions        {}", self.instructions);
+        let _ = writeln!(out, "CPI                 {}", self.cpi_text());
+        let _ = writeln!(out, "Load-use stalls     {}", self.load_use_stalls);
+        let _ = writeln!(out, "Data hazard stalls  {}", self.data_stalls);
+        let _ = writeln!(out, "Syscall stalls      {}", self.syscall_stalls);
+        let _ = writeln!(out, "Cache miss stalls   {}", self.cache_stalls);
+        let _ = writeln!(out, "Flushes             {}", self.flushes);
+        let _ = writeln!(out, "Branches taken      {}", self.branches_taken);
+        let _ = writeln!(out, "Branches not taken  {}", self.branches_not_taken);
+        let _ = writeln!(out, "Loads               {}", self.loads);
+        let _ = writeln!(out, "Stores              {}", self.stores);
+
+        out.push_str("\nINSTRUCTION MIX\n");
+        for (name, count) in self.mix() {
+            let _ = writeln!(
+                out,
+                "{:<8}{:>10}  {:5.1}%",
+                name,
+                count,
+                self.percent(count)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_without_instructions_has_no_nan() {
+        let mut stats = Stats {
+            cycles: 4,
+            ..Default::default()
+        };
+        // a mix entry without a retired instruction can't happen but must not divide by zero
+        stats.mix.insert("add", 1);
+        let report = stats.report();
+        assert!(!report.contains("NaN"), "{}", report);
+        assert!(report.contains("CPI                 n/a"));
+        assert_eq!(stats.percent(1), 0.0);
+    }
+
+    #[test]
+    fn cpi_and_mix_percentages() {
+        let mut stats = Stats {
+            cycles: 9,
+            instructions: 4,
+            ..Default::default()
+        };
+        stats.mix.insert("add", 3);
+        stats.mix.insert("lw", 1);
+        assert_eq!(stats.cpi(), Some(2.25));
+        assert_eq!(stats.percent(3), 75.0);
+        let report = stats.report();
+        assert!(report.contains("CPI                 2.250"));
+        assert!(report.contains("add              3   75.0%"), "{}", report);
+    }
+}
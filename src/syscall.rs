@@ -1,4 +1,7 @@
-use crate::{Memory, RegisterFile, A0, V0};
+use crate::{
+    files::Files, mmu::Mmu, protection::Access, Exception, Memory, RegisterFile, Width, A0, A1, A2,
+    F0, F12, V0,
+};
 use anyhow::{bail, Context, Result};
 
 #[derive(Debug)]
@@ -7,49 +10,300 @@ pub enum Syscall {
     Error(String),
     Quit,
     ReadInt,
+    ReadFloat,
+    ReadDouble,
+    /// Reads a line into the buffer at `addr` that holds `max_len` bytes including the NUL
+    ReadString {
+        addr: u32,
+        max_len: u32,
+    },
+    ReadChar,
+    /// Reads a line of at most `max_len` bytes into the buffer at `addr` from file descriptor 0
+    ReadConsole {
+        addr: u32,
+        max_len: u32,
+    },
+    OpenFile {
+        path: String,
+        flags: u32,
+    },
+    ReadFile {
+        fd: u32,
+        addr: u32,
+        max_len: u32,
+    },
+    WriteFile {
+        fd: u32,
+        data: Vec<u8>,
+    },
+    CloseFile(u32),
 }
 
-pub fn resolve_syscall(reg_file: &mut RegisterFile, syscall: &Syscall, value: &str) -> Result<()> {
+/// File descriptors that read from and write to the console
+const STDIN: u32 = 0;
+const STDOUT: u32 = 1;
+const STDERR: u32 = 2;
+
+/// Most bytes written by a single write syscall, longer writes are cut short and return the
+/// number of bytes written
+const MAX_WRITE: u32 = 1 << 20;
+
+/// Completes a syscall with the input the user entered
+///
+/// Buffers are written like stores by the program, a buffer it may not write to raises the
+/// [`Exception`] a store would and is left untouched
+///
+/// # Returns
+/// The number of bytes of `value` the syscall consumed, numbers consume the whole line while
+/// read char and read string only take what they store so the rest is left for the next read
+pub fn resolve_syscall(
+    reg_file: &mut RegisterFile,
+    mem: &mut Memory,
+    mmu: &mut Mmu,
+    syscall: &Syscall,
+    value: &str,
+) -> Result<usize> {
+    let line_len = value.find('\n').map_or(value.len(), |end| end + 1);
     match syscall {
         Syscall::ReadInt => {
-            let buffer = value.trim();
+            let buffer = value[..line_len].trim();
             let val = buffer
                 .parse::<i32>()
                 .with_context(|| format!("Attempting to parse '{}'", buffer))?
                 as u32;
             reg_file.write_register(V0, val);
+            Ok(line_len)
+        }
+        Syscall::ReadFloat => {
+            let buffer = value[..line_len].trim();
+            let val = buffer
+                .parse::<f32>()
+                .with_context(|| format!("Attempting to parse '{}'", buffer))?;
+            reg_file.write_fpr(F0, val.to_bits());
+            Ok(line_len)
         }
-        _ => {}
+        Syscall::ReadDouble => {
+            let buffer = value[..line_len].trim();
+            let val = buffer
+                .parse::<f64>()
+                .with_context(|| format!("Attempting to parse '{}'", buffer))?;
+            reg_file.write_fpr_pair(F0, val.to_bits());
+            Ok(line_len)
+        }
+        Syscall::ReadString { addr, max_len } => {
+            // a buffer without room for the NUL is left untouched
+            if *max_len as i32 <= 0 {
+                return Ok(0);
+            }
+            // like fgets this stops after a newline or once the buffer is full, leaving the rest
+            // of the line to be read next
+            let (mut bytes, mut used) = (vec![], 0);
+            let input = value.as_bytes();
+            while bytes.len() + 1 < *max_len as usize {
+                let (b, len) = match &input[used..] {
+                    [b'\r', b'\n', ..] => (b'\n', 2),
+                    [b, ..] => (*b, 1),
+                    [] => break,
+                };
+                bytes.push(b);
+                used += len;
+                if b == b'\n' {
+                    break;
+                }
+            }
+            bytes.push(0);
+            store_bytes(mem, mmu, *addr, &bytes)?;
+            Ok(used)
+        }
+        Syscall::ReadConsole { addr, max_len } => {
+            // like reading a terminal this returns at most one line and an empty value is the end
+            // of the input
+            let len = line_len.min(*max_len as usize);
+            store_bytes(mem, mmu, *addr, &value.as_bytes()[..len])?;
+            reg_file.write_register(V0, len as u32);
+            Ok(len)
+        }
+        Syscall::ReadChar => {
+            // a line ending reads as a single newline, as does the empty line the editor sends
+            // when enter is pressed on its own
+            let (c, len) = match value.chars().next() {
+                Some('\r') if value.starts_with("\r\n") => ('\n', 2),
+                Some(c) => (c, c.len_utf8()),
+                None => ('\n', 0),
+            };
+            reg_file.write_register(V0, c as u32);
+            Ok(len)
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Writes bytes into a buffer of the program as byte stores
+///
+/// Every byte is translated and checked before any is written so a buffer that runs into memory
+/// the program can't write to is left as it was
+fn store_bytes(mem: &mut Memory, mmu: &mut Mmu, addr: u32, bytes: &[u8]) -> Result<(), Exception> {
+    let phys = (0..bytes.len() as u32)
+        .map(|i| mmu.translate(addr.wrapping_add(i), Access::Write))
+        .collect::<Result<Vec<u32>, Exception>>()?;
+    for (i, (phys, b)) in phys.into_iter().zip(bytes).enumerate() {
+        mem.store(phys, Width::Byte, *b as u32)
+            .map_err(|_| Exception::AddressStore(addr.wrapping_add(i as u32)))?;
     }
     Ok(())
 }
 
-pub fn handle_syscall(reg_file: &mut RegisterFile, mem: &mut Memory) -> Result<Syscall> {
-    // Handle syscall instructions
+/// Reads a byte from a buffer of the program as a byte load
+fn load_byte(mem: &mut Memory, mmu: &mut Mmu, addr: u32) -> Result<u8, Exception> {
+    let phys = mmu.translate(addr, Access::Read)?;
+    mem.load(phys, Width::Byte)
+        .map(|b| b as u8)
+        .map_err(|_| Exception::AddressLoad(addr))
+}
+
+/// Handles a syscall instruction
+///
+/// Buffers and strings are read like loads by the program, one it may not read raises the
+/// [`Exception`] a load would before anything is printed or written
+///
+/// # Returns
+/// The syscall the host has to carry out or `None` if it has been completed already
+pub fn handle_syscall(
+    reg_file: &mut RegisterFile,
+    mem: &mut Memory,
+    mmu: &mut Mmu,
+) -> Result<Option<Syscall>> {
     let v0 = reg_file.read_register(V0);
+    if v0 == 9 {
+        let map = mmu.map_mut();
+        // sbrk
+        let amount = reg_file.read_register(A0) as i32;
+        let old = match map.grow_heap(amount) {
+            Some(old) => old,
+            None => bail!(
+                "sbrk of {} bytes would move the break at 0x{:08X} out of the heap",
+                amount,
+                map.heap_end()
+            ),
+        };
+        reg_file.write_register(V0, old);
+        return Ok(None);
+    }
+    if v0 == 15 {
+        // write to file
+        let addr = reg_file.read_register(A1);
+        let len = reg_file.read_register(A2).min(MAX_WRITE);
+        let data = (0..len)
+            .map(|i| load_byte(mem, mmu, addr.wrapping_add(i)))
+            .collect::<Result<Vec<u8>, Exception>>()?;
+        return Ok(Some(Syscall::WriteFile {
+            fd: reg_file.read_register(A0),
+            data,
+        }));
+    }
+    host_syscall(reg_file, mem, mmu, v0).map(Some)
+}
+
+/// Carries out a syscall on a file of the file system of the machine
+///
+/// Each file syscall returns the number of bytes read or written or the new descriptor in `$v0`,
+/// a failed one returns -1. Files are never looked up for the descriptors of the console. Reading
+/// into a buffer the program may not write to raises the [`Exception`] a store would, nothing is
+/// read from the file so the syscall can be run again
+///
+/// # Returns
+/// The syscall the host still has to carry out, any syscall other than a file syscall is left as
+/// it is
+pub fn file_syscall(
+    files: &mut Files,
+    reg_file: &mut RegisterFile,
+    mem: &mut Memory,
+    mmu: &mut Mmu,
+    syscall: Syscall,
+) -> Result<Option<Syscall>, Exception> {
+    let result = match syscall {
+        Syscall::OpenFile { path, flags } => files.open(&path, flags).ok(),
+        Syscall::ReadFile {
+            fd: STDIN,
+            addr,
+            max_len,
+        } => return Ok(Some(Syscall::ReadConsole { addr, max_len })),
+        Syscall::ReadFile { fd, addr, max_len } => match files.peek(fd, max_len as usize) {
+            Ok(data) => {
+                store_bytes(mem, mmu, addr, data)?;
+                let len = data.len();
+                files.read(fd, len).ok().map(|_| len as u32)
+            }
+            Err(_) => None,
+        },
+        Syscall::WriteFile {
+            fd: STDOUT | STDERR,
+            data,
+        } => {
+            reg_file.write_register(V0, data.len() as u32);
+            return Ok(Some(Syscall::Print(
+                String::from_utf8_lossy(&data).into_owned(),
+            )));
+        }
+        Syscall::WriteFile { fd, data } => files.write(fd, &data).ok().map(|_| data.len() as u32),
+        Syscall::CloseFile(fd) => {
+            files.close(fd);
+            return Ok(None);
+        }
+        syscall => return Ok(Some(syscall)),
+    };
+    reg_file.write_register(V0, result.unwrap_or(-1i32 as u32));
+    Ok(None)
+}
+
+/// Reads the NUL terminated string at `ptr`
+fn read_string(mem: &mut Memory, mmu: &mut Mmu, mut ptr: u32) -> Result<String> {
+    // to make this unicode aware we need to bundle it into a buffer first
+    let mut buffer = vec![];
+    let mut b = load_byte(mem, mmu, ptr)?;
+    while b != 0 {
+        buffer.push(b);
+        ptr = ptr.wrapping_add(1);
+        b = load_byte(mem, mmu, ptr)?;
+    }
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Handles the syscalls that are carried out outside of the pipeline
+fn host_syscall(
+    reg_file: &mut RegisterFile,
+    mem: &mut Memory,
+    mmu: &mut Mmu,
+    v0: u32,
+) -> Result<Syscall> {
     match v0 {
         1 => {
             // print int
             let arg = reg_file.read_register(A0);
             Ok(Syscall::Print(format!("{}", arg as i32)))
         }
+        2 => {
+            // print float
+            let arg = f32::from_bits(reg_file.read_fpr(F12));
+            Ok(Syscall::Print(format!("{:?}", arg)))
+        }
+        3 => {
+            // print double
+            let arg = f64::from_bits(reg_file.read_fpr_pair(F12));
+            Ok(Syscall::Print(format!("{:?}", arg)))
+        }
         4 => {
             // print string
-            let mut ptr = reg_file.read_register(A0);
-
-            println!("SYSCALL 4 {ptr}");
-            // to make this unicode aware we need to bundle it into a buffer first
-            let mut buffer = vec![];
-            let mut b = mem.get_byte(ptr)?;
-            while b != 0 {
-                buffer.push(b);
-                ptr += 1;
-                b = mem.get_byte(ptr)?;
-            }
-            let s = String::from_utf8(buffer)?;
-            Ok(Syscall::Print(format!("{}", s)))
+            let s = read_string(mem, mmu, reg_file.read_register(A0))?;
+            Ok(Syscall::Print(s))
         }
         5 => Ok(Syscall::ReadInt),
+        6 => Ok(Syscall::ReadFloat),
+        7 => Ok(Syscall::ReadDouble),
+        8 => Ok(Syscall::ReadString {
+            addr: reg_file.read_register(A0),
+            max_len: reg_file.read_register(A1),
+        }),
         10 => Ok(Syscall::Quit),
 
         11 => {
@@ -58,10 +312,17 @@ pub fn handle_syscall(reg_file: &mut RegisterFile, mem: &mut Memory) -> Result<S
             let c = char::from_u32(arg).unwrap_or('�');
             Ok(Syscall::Print(format!("{}", c)))
         }
-        12 => {
-            bail!("read char syscall not yet implemented");
-            // implementing this properly will require a single point for handling stdin
-        }
+        12 => Ok(Syscall::ReadChar),
+        13 => Ok(Syscall::OpenFile {
+            path: read_string(mem, mmu, reg_file.read_register(A0))?,
+            flags: reg_file.read_register(A1),
+        }),
+        14 => Ok(Syscall::ReadFile {
+            fd: reg_file.read_register(A0),
+            addr: reg_file.read_register(A1),
+            max_len: reg_file.read_register(A2),
+        }),
+        16 => Ok(Syscall::CloseFile(reg_file.read_register(A0))),
         34 => {
             // print int hex
             let arg = reg_file.read_register(A0);
@@ -88,3 +349,88 @@ pub fn handle_syscall(reg_file: &mut RegisterFile, mem: &mut Memory) -> Result<S
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::model::{DATA_BASE, TEXT_BASE};
+
+    /// Resolves a syscall against fresh registers and memory
+    fn resolve(syscall: Syscall, input: &str) -> (Result<usize>, RegisterFile, Memory) {
+        let mut regs = RegisterFile::default();
+        let mut mem = Memory::new();
+        let used = resolve_syscall(&mut regs, &mut mem, &mut Mmu::default(), &syscall, input);
+        (used, regs, mem)
+    }
+
+    fn buffer(mem: &Memory, len: u32) -> Vec<u8> {
+        (0..len)
+            .map(|i| mem.get_byte(DATA_BASE + i).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn numbers_consume_the_line() {
+        let (used, regs, _) = resolve(Syscall::ReadInt, " -12\n7\n");
+        assert_eq!(used.unwrap(), 5);
+        assert_eq!(regs.read_register(V0), -12i32 as u32);
+        let (used, regs, _) = resolve(Syscall::ReadDouble, "2.5");
+        assert_eq!(used.unwrap(), 3);
+        assert_eq!(f64::from_bits(regs.read_fpr_pair(F0)), 2.5);
+        assert!(resolve(Syscall::ReadFloat, "x\n").0.is_err());
+    }
+
+    #[test]
+    fn read_char_consumes_one_character() {
+        let (used, regs, _) = resolve(Syscall::ReadChar, "ab\n");
+        assert_eq!(used.unwrap(), 1);
+        assert_eq!(regs.read_register(V0), 'a' as u32);
+        let (used, regs, _) = resolve(Syscall::ReadChar, "é\n");
+        assert_eq!(used.unwrap(), 2);
+        assert_eq!(regs.read_register(V0), 'é' as u32);
+        let (used, regs, _) = resolve(Syscall::ReadChar, "\r\nx");
+        assert_eq!(used.unwrap(), 2);
+        assert_eq!(regs.read_register(V0), '\n' as u32);
+        let (used, regs, _) = resolve(Syscall::ReadChar, "");
+        assert_eq!(used.unwrap(), 0);
+        assert_eq!(regs.read_register(V0), '\n' as u32);
+    }
+
+    #[test]
+    fn read_string_stops_after_a_newline_or_a_full_buffer() {
+        let read = |max_len| Syscall::ReadString {
+            addr: DATA_BASE,
+            max_len,
+        };
+        let (used, _, mem) = resolve(read(8), "hi\r\nthere\n");
+        assert_eq!(used.unwrap(), 4);
+        assert_eq!(buffer(&mem, 4), b"hi\n\0");
+
+        let (used, _, mem) = resolve(read(4), "hello\n");
+        assert_eq!(used.unwrap(), 3);
+        assert_eq!(buffer(&mem, 4), b"hel\0");
+
+        // the last line of the input may not end in a newline
+        let (used, _, mem) = resolve(read(8), "end");
+        assert_eq!(used.unwrap(), 3);
+        assert_eq!(buffer(&mem, 4), b"end\0");
+
+        let (used, _, mem) = resolve(read(1), "hello\n");
+        assert_eq!(used.unwrap(), 0);
+        assert_eq!(buffer(&mem, 1), b"\0");
+        assert_eq!(resolve(read(0), "hello\n").0.unwrap(), 0);
+    }
+
+    #[test]
+    fn read_string_into_code_faults_like_a_store() {
+        let syscall = Syscall::ReadString {
+            addr: TEXT_BASE,
+            max_len: 8,
+        };
+        let (used, _, mem) = resolve(syscall, "hello\n");
+        let exception = used.unwrap_err().downcast::<Exception>().unwrap();
+        assert_eq!(exception.code(), 5);
+        assert_eq!(exception.bad_address(), Some(TEXT_BASE));
+        assert_eq!(mem.get_byte(TEXT_BASE).unwrap(), 0);
+    }
+}
@@ -0,0 +1,280 @@
+//! Bounded history of which instruction occupied each pipeline stage every cycle
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Write},
+};
+
+use crate::{hazard::Bubble, parser::disassemble, pipeline::PipelineState};
+
+/// Names of the pipeline stages in the order instructions pass through them
+pub const STAGES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+
+/// Number of cycles remembered before the oldest ones are dropped
+const CAPACITY: usize = 1000;
+
+/// A dynamic instance of an instruction in the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occupant {
+    /// Tells apart instances of an instruction that is fetched more than once
+    pub id: u64,
+    pub pc: u32,
+    pub instruction: u32,
+}
+
+impl fmt::Display for Occupant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:08X} {}",
+            self.pc,
+            disassemble(self.instruction, self.pc)
+        )
+    }
+}
+
+/// What a pipeline stage held during one cycle
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub occupant: Option<Occupant>,
+    /// Set if the stage held a bubble or if its instruction was stalled or squashed there
+    pub bubble: Option<Bubble>,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.occupant, self.bubble) {
+            (Some(occupant), Some(bubble)) => write!(f, "{} ({})", occupant, bubble),
+            (Some(occupant), None) => write!(f, "{}", occupant),
+            (None, Some(bubble)) => write!(f, "{}", bubble),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The contents of every stage during one cycle
+#[derive(Debug, Clone)]
+pub struct Cycle {
+    /// Cycles are numbered from 1 since the machine was last reset
+    pub number: u64,
+    pub stages: [Slot; 5],
+}
+
+/// A cell of the pipeline diagram
+#[derive(Debug, Clone, Copy)]
+pub struct Cell {
+    /// Index of the first stage the instruction was in during the cycle
+    pub stage: usize,
+    /// Index of the last stage, only differs from `stage` when running single cycle
+    pub last_stage: usize,
+    pub bubble: Option<Bubble>,
+}
+
+impl Cell {
+    pub fn label(&self) -> String {
+        match self.bubble {
+            Some(Bubble::Stall(_)) => "stall".to_string(),
+            Some(Bubble::Flush) => "flush".to_string(),
+            None if self.stage == self.last_stage => STAGES[self.stage].to_string(),
+            None => format!("{}-{}", STAGES[self.stage], STAGES[self.last_stage]),
+        }
+    }
+}
+
+/// A row of the pipeline diagram following one instruction through the stages
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub occupant: Occupant,
+    /// One cell per cycle kept in the timeline, `None` while the instruction is not in the pipeline
+    pub cells: Vec<Option<Cell>>,
+}
+
+/// Records the pipeline every cycle so it can be drawn as a pipeline diagram
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    cycles: VecDeque<Cycle>,
+    recorded: u64,
+    next_id: u64,
+}
+
+impl Timeline {
+    /// Records the state the pipeline was left in by a cycle
+    pub(crate) fn record(&mut self, state: &PipelineState, single_cycle: bool) {
+        let mut latches = [
+            (state.if_id.pc, state.if_id.instruction, state.if_id.bubble),
+            (state.id_ex.pc, state.id_ex.instruction, state.id_ex.bubble),
+            (
+                state.ex_mem.pc,
+                state.ex_mem.instruction,
+                state.ex_mem.bubble,
+            ),
+            (
+                state.mem_wb.pc,
+                state.mem_wb.instruction,
+                state.mem_wb.bubble,
+            ),
+            (
+                state.pipe_out.pc,
+                state.pipe_out.instruction,
+                state.pipe_out.bubble,
+            ),
+        ];
+
+        // an instruction held in decode stays in the fetch latch while a bubble takes its place,
+        // nothing is fetched behind it
+        if let Some(stall) = state.stall {
+            if state.id_ex.pc == 0 {
+                let bubble = Some(Bubble::Stall(stall));
+                latches[1] = (state.if_id.pc, state.if_id.instruction, bubble);
+                latches[0] = (0, 0, bubble);
+            }
+        }
+
+        let mut stages = [Slot::default(); 5];
+        if single_cycle {
+            // a single instruction passes through every stage in one cycle
+            if state.pipe_out.pc != 0 {
+                let occupant = self.occupant(state.pipe_out.pc, state.pipe_out.instruction);
+                stages = [Slot {
+                    occupant: Some(occupant),
+                    bubble: None,
+                }; 5];
+            }
+        } else {
+            let prev = self.cycles.back().map(|c| c.stages).unwrap_or_default();
+            let mut claimed = [false; 5];
+            // walk from writeback so instructions that moved on are matched before the ones
+            // behind them
+            for s in (0..5).rev() {
+                let (pc, instruction, bubble) = latches[s];
+                // an instruction either moved on from the stage before it or stayed where it was
+                let mut candidates = s.checked_sub(1).into_iter().chain(Some(s));
+                let found = candidates.find(|&p| {
+                    !claimed[p]
+                        && match prev[p].occupant {
+                            Some(o) if pc != 0 => o.pc == pc,
+                            // an instruction squashed on its way into a stage leaves a flush
+                            // bubble there
+                            Some(_) => {
+                                p < s && bubble == Some(Bubble::Flush) && prev[p].bubble.is_none()
+                            }
+                            None => false,
+                        }
+                });
+
+                let occupant = match found {
+                    Some(p) => {
+                        claimed[p] = true;
+                        prev[p].occupant
+                    }
+                    None if pc != 0 => Some(self.occupant(pc, instruction)),
+                    None => None,
+                };
+                // an instruction that stayed in place during a stall was held there
+                let bubble = match (found, state.stall) {
+                    (Some(p), Some(stall)) if p == s => Some(Bubble::Stall(stall)),
+                    _ => bubble,
+                };
+                stages[s] = Slot { occupant, bubble };
+            }
+        }
+
+        self.recorded += 1;
+        self.cycles.push_back(Cycle {
+            number: self.recorded,
+            stages,
+        });
+        if self.cycles.len() > CAPACITY {
+            self.cycles.pop_front();
+        }
+    }
+
+    fn occupant(&mut self, pc: u32, instruction: u32) -> Occupant {
+        self.next_id += 1;
+        Occupant {
+            id: self.next_id,
+            pc,
+            instruction,
+        }
+    }
+
+    /// Gets the cycles that are remembered from oldest to newest
+    pub fn cycles(&self) -> &VecDeque<Cycle> {
+        &self.cycles
+    }
+
+    /// Arranges the history as a pipeline diagram with one row per instruction in fetch order
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows: BTreeMap<u64, Row> = BTreeMap::new();
+        for (i, cycle) in self.cycles.iter().enumerate() {
+            for (s, slot) in cycle.stages.iter().enumerate() {
+                let occupant = match slot.occupant {
+                    Some(occupant) => occupant,
+                    None => continue,
+                };
+                let row = rows.entry(occupant.id).or_insert_with(|| Row {
+                    occupant,
+                    cells: vec![None; self.cycles.len()],
+                });
+                match &mut row.cells[i] {
+                    Some(cell) => cell.last_stage = s,
+                    cell => {
+                        *cell = Some(Cell {
+                            stage: s,
+                            last_stage: s,
+                            bubble: slot.bubble,
+                        })
+                    }
+                }
+            }
+        }
+        rows.into_values().collect()
+    }
+
+    /// Formats the history as a textbook pipeline diagram
+    pub fn to_text(&self) -> String {
+        let rows = self.rows();
+        let labels: Vec<String> = rows.iter().map(|r| r.occupant.to_string()).collect();
+        let width = labels.iter().map(|l| l.len()).max().unwrap_or(0).max(5);
+
+        let mut out = format!("{:<width$}", "Cycle", width = width);
+        for cycle in &self.cycles {
+            let _ = write!(out, " {:<5}", cycle.number);
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+
+        for (row, label) in rows.iter().zip(labels) {
+            let mut line = format!("{:<width$}", label, width = width);
+            for cell in &row.cells {
+                let label = cell.map(|c| c.label()).unwrap_or_default();
+                let _ = write!(line, " {:<5}", label);
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Formats the history as CSV with one line per cycle and one column per stage
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("cycle,{}\n", STAGES.join(","));
+        for cycle in &self.cycles {
+            let _ = write!(out, "{}", cycle.number);
+            for slot in &cycle.stages {
+                let _ = write!(out, ",{}", csv_field(&slot.to_string()));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Quotes a CSV field if it contains a separator or quote
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}